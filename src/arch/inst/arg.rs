@@ -47,6 +47,35 @@ macro_rules! of_register {
     )
 }
 
+macro_rules! of_float_register {
+    ($e:expr) => (
+        Arg {
+            mappings: Mappings(&[Mapping {
+                source_offset: 0,
+                dest_offset: $e,
+                length: 5,
+            }]),
+            arg_type: ArgType::FloatRegister,
+            sign_bit: None,
+        }
+    )
+}
+
+// x8-x15 or f8-f15 in 3 bits
+macro_rules! of_compressed_register {
+    ($e:expr, $arg_type:ident) => (
+        Arg {
+            mappings: Mappings(&[Mapping {
+                source_offset: 0,
+                dest_offset: $e,
+                length: 3,
+            }]),
+            arg_type: ArgType::$arg_type,
+            sign_bit: None,
+        }
+    )
+}
+
 macro_rules! of_patch {
     ($source_offset:expr, $dest_offset:expr, $length:expr) => (
         Mappings(&[Mapping{
@@ -60,11 +89,21 @@ macro_rules! of_patch {
 pub const RD: Arg = of_register!(7);
 pub const RS1: Arg = of_register!(15);
 pub const RS2: Arg = of_register!(20);
+pub const FRD: Arg = of_float_register!(7);
+pub const FRS1: Arg = of_float_register!(15);
+pub const FRS2: Arg = of_float_register!(20);
+pub const FRS3: Arg = of_float_register!(27);
 pub const SHAMT: Arg = Arg{
     mappings: of_patch!(0, 20, 5),
     arg_type: ArgType::General,
     sign_bit: None,
 };
+// RV64 shifts take 6 bits. On RV32 shamt[5] is reserved
+pub const SHAMT6: Arg = Arg{
+    mappings: of_patch!(0, 20, 6),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
 pub const IMM12: Arg = Arg{
     mappings: of_patch!(0, 20, 12),
     arg_type: ArgType::General,
@@ -113,13 +152,170 @@ pub const ZIMM: Arg = Arg{
     arg_type: ArgType::General,
     sign_bit: Some(4),
 };
+pub const RM: Arg = Arg{
+    mappings: of_patch!(0, 12, 3),
+    arg_type: ArgType::RoundingMode,
+    sign_bit: None,
+};
+pub const PRED: Arg = Arg{
+    mappings: of_patch!(0, 24, 4),
+    arg_type: ArgType::FenceSet,
+    sign_bit: None,
+};
+pub const SUCC: Arg = Arg{
+    mappings: of_patch!(0, 20, 4),
+    arg_type: ArgType::FenceSet,
+    sign_bit: None,
+};
+
+// Compressed
+
+pub const C_RS2: Arg = of_register!(2);
+pub const C_FRS2: Arg = of_float_register!(2);
+pub const C_RDP: Arg = of_compressed_register!(2, CompressedRegister);
+pub const C_RS1P: Arg = of_compressed_register!(7, CompressedRegister);
+pub const C_RS2P: Arg = of_compressed_register!(2, CompressedRegister);
+pub const C_FRDP: Arg = of_compressed_register!(2, CompressedFloatRegister);
+pub const C_FRS2P: Arg = of_compressed_register!(2, CompressedFloatRegister);
+pub const C_IMM6: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 0, dest_offset: 2, length: 5 },
+        Mapping{ source_offset: 5, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: Some(5),
+};
+// shamt[5] is reserved on RV32
+pub const C_SHAMT: Arg = Arg{
+    mappings: of_patch!(0, 2, 5),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_SHAMT6: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 0, dest_offset: 2, length: 5 },
+        Mapping{ source_offset: 5, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_LUI_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 12, dest_offset: 2, length: 5 },
+        Mapping{ source_offset: 17, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: Some(17),
+};
+pub const C_ADDI16SP_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 5, dest_offset: 2, length: 1 },
+        Mapping{ source_offset: 7, dest_offset: 3, length: 2 },
+        Mapping{ source_offset: 6, dest_offset: 5, length: 1 },
+        Mapping{ source_offset: 4, dest_offset: 6, length: 1 },
+        Mapping{ source_offset: 9, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: Some(9),
+};
+pub const C_ADDI4SPN_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 3, dest_offset: 5, length: 1 },
+        Mapping{ source_offset: 2, dest_offset: 6, length: 1 },
+        Mapping{ source_offset: 6, dest_offset: 7, length: 4 },
+        Mapping{ source_offset: 4, dest_offset: 11, length: 2 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_LW_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 6, dest_offset: 5, length: 1 },
+        Mapping{ source_offset: 2, dest_offset: 6, length: 1 },
+        Mapping{ source_offset: 3, dest_offset: 10, length: 3 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_LD_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 6, dest_offset: 5, length: 2 },
+        Mapping{ source_offset: 3, dest_offset: 10, length: 3 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_LWSP_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 6, dest_offset: 2, length: 2 },
+        Mapping{ source_offset: 2, dest_offset: 4, length: 3 },
+        Mapping{ source_offset: 5, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_LDSP_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 6, dest_offset: 2, length: 3 },
+        Mapping{ source_offset: 3, dest_offset: 5, length: 2 },
+        Mapping{ source_offset: 5, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_SWSP_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 6, dest_offset: 7, length: 2 },
+        Mapping{ source_offset: 2, dest_offset: 9, length: 4 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_SDSP_IMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 6, dest_offset: 7, length: 3 },
+        Mapping{ source_offset: 3, dest_offset: 10, length: 3 },
+    ]),
+    arg_type: ArgType::General,
+    sign_bit: None,
+};
+pub const C_BIMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 5, dest_offset: 2, length: 1 },
+        Mapping{ source_offset: 1, dest_offset: 3, length: 2 },
+        Mapping{ source_offset: 6, dest_offset: 5, length: 2 },
+        Mapping{ source_offset: 3, dest_offset: 10, length: 2 },
+        Mapping{ source_offset: 8, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::Address,
+    sign_bit: Some(8),
+};
+pub const C_JIMM: Arg = Arg{
+    mappings: Mappings(&[
+        Mapping{ source_offset: 5, dest_offset: 2, length: 1 },
+        Mapping{ source_offset: 1, dest_offset: 3, length: 3 },
+        Mapping{ source_offset: 7, dest_offset: 6, length: 1 },
+        Mapping{ source_offset: 6, dest_offset: 7, length: 1 },
+        Mapping{ source_offset: 10, dest_offset: 8, length: 1 },
+        Mapping{ source_offset: 8, dest_offset: 9, length: 2 },
+        Mapping{ source_offset: 4, dest_offset: 11, length: 1 },
+        Mapping{ source_offset: 11, dest_offset: 12, length: 1 },
+    ]),
+    arg_type: ArgType::Address,
+    sign_bit: Some(11),
+};
 
 #[derive(Debug)]
 pub enum ArgType {
     Register,
+    FloatRegister,
     Address, // all relative
     General,
     Csr,
+    RoundingMode,
+    FenceSet,
+    CompressedRegister,
+    CompressedFloatRegister,
 }
 
 #[derive(Debug)]
@@ -137,6 +333,10 @@ impl Arg {
     pub fn read(&self, src: u32) -> u32 {
         // map and sign extend if signed
         let v = self.mappings.backward_write(0, src);
+        match &self.arg_type {
+            &ArgType::CompressedRegister | &ArgType::CompressedFloatRegister => return v + 8,
+            _ => (),
+        }
         if let Some(sign_bit) = self.sign_bit {
             sign_extend(v, sign_bit)
         } else {
@@ -0,0 +1,119 @@
+// RVC
+// Every 16-bit instruction stands for a 32-bit one. The table gives the 16-bit encoding the same way
+// INSTS does, and how its args fill the args of the 32-bit instruction
+
+use super::*;
+use super::arg::*;
+use super::opcode::*;
+use self::Expand::*;
+
+#[derive(Debug)]
+pub enum Expand {
+    Src(usize), // arg of the 16-bit instruction
+    Fixed(u32),
+}
+
+#[derive(Debug)]
+pub struct CInst {
+    pub inst: Inst,
+    pub expansion: &'static str,
+    pub expansion_args: &'static [Expand],
+    // args that are reserved when zero, or encode an instruction listed earlier
+    pub nonzero: &'static [usize],
+    // Only valid in this XLEN. Some encodings mean different instructions in RV32 and RV64
+    pub xlen: Option<u32>,
+}
+
+impl CInst {
+    pub fn is_valid_in(&self, xlen: u32) -> bool {
+        self.xlen.map_or(true, |x| x == xlen)
+    }
+}
+
+const ZERO: u32 = 0;
+const RA: u32 = 1;
+const SP: u32 = 2;
+
+macro_rules! c_inst {
+($quadrant:ident, $funct3:path, [$($arg:ident),*], [$($patch:ident = $value:expr),*], $expansion:expr, [$($expand:expr),*], [$($nonzero:expr),*]) => (
+    c_inst!(None; $quadrant, $funct3, [$($arg),*], [$($patch = $value),*], $expansion, [$($expand),*], [$($nonzero),*])
+);
+(rv32; $($rest:tt)*) => (c_inst!(Some(32); $($rest)*));
+(rv64; $($rest:tt)*) => (c_inst!(Some(64); $($rest)*));
+($xlen:expr; $quadrant:ident, $funct3:path, [$($arg:ident),*], [$($patch:ident = $value:expr),*], $expansion:expr, [$($expand:expr),*], [$($nonzero:expr),*]) => (
+    CInst {
+        inst: Inst {
+            args: &[$(&$arg),*],
+            opcodes: &[
+                Opcode{ patch: &patch::C_OP, value: opcode::C_OP::$quadrant as u32 },
+                Opcode{ patch: &patch::C_FUNCT3, value: $funct3 as u32 },
+                $(Opcode{ patch: &patch::$patch, value: $value as u32 }),*
+            ],
+        },
+        expansion: $expansion,
+        expansion_args: &[$($expand),*],
+        nonzero: &[$($nonzero),*],
+        xlen: $xlen,
+    }
+)
+}
+
+// Order matters where encodings overlap. The more specific comes first.
+// Shift amounts are 6 bits. shamt[5] set is reserved in RV32, and the expansion is illegal there too
+pub const CINSTS: &'static [(&'static str, CInst)] = &[
+    // Quadrant 0
+    ("c.addi4spn", c_inst!(C0, FUNCT3_C0::ADDI4SPN, [C_RDP, C_ADDI4SPN_IMM], [], "addi", [Src(0), Fixed(SP), Src(1)], [1])),
+    ("c.fld", c_inst!(C0, FUNCT3_C0::FLD, [C_FRDP, C_RS1P, C_LD_IMM], [], "fld", [Src(0), Src(1), Src(2)], [])),
+    ("c.lw", c_inst!(C0, FUNCT3_C0::LW, [C_RDP, C_RS1P, C_LW_IMM], [], "lw", [Src(0), Src(1), Src(2)], [])),
+    ("c.flw", c_inst!(rv32; C0, FUNCT3_C0::FLW, [C_FRDP, C_RS1P, C_LW_IMM], [], "flw", [Src(0), Src(1), Src(2)], [])),
+    ("c.ld", c_inst!(rv64; C0, FUNCT3_C0::FLW, [C_RDP, C_RS1P, C_LD_IMM], [], "ld", [Src(0), Src(1), Src(2)], [])),
+    ("c.fsd", c_inst!(C0, FUNCT3_C0::FSD, [C_RS1P, C_FRS2P, C_LD_IMM], [], "fsd", [Src(0), Src(1), Src(2)], [])),
+    ("c.sw", c_inst!(C0, FUNCT3_C0::SW, [C_RS1P, C_RS2P, C_LW_IMM], [], "sw", [Src(0), Src(1), Src(2)], [])),
+    ("c.fsw", c_inst!(rv32; C0, FUNCT3_C0::FSW, [C_RS1P, C_FRS2P, C_LW_IMM], [], "fsw", [Src(0), Src(1), Src(2)], [])),
+    ("c.sd", c_inst!(rv64; C0, FUNCT3_C0::FSW, [C_RS1P, C_RS2P, C_LD_IMM], [], "sd", [Src(0), Src(1), Src(2)], [])),
+
+    // Quadrant 1
+    ("c.nop", c_inst!(C1, FUNCT3_C1::ADDI, [], [C_BIT12 = 0, C_RD = 0, C_RS2 = 0], "addi", [Fixed(ZERO), Fixed(ZERO), Fixed(0)], [])),
+    ("c.addi", c_inst!(C1, FUNCT3_C1::ADDI, [RD, C_IMM6], [], "addi", [Src(0), Src(0), Src(1)], [])),
+    ("c.jal", c_inst!(rv32; C1, FUNCT3_C1::JAL, [C_JIMM], [], "jal", [Fixed(RA), Src(0)], [])),
+    ("c.addiw", c_inst!(rv64; C1, FUNCT3_C1::JAL, [RD, C_IMM6], [], "addiw", [Src(0), Src(0), Src(1)], [0])),
+    ("c.li", c_inst!(C1, FUNCT3_C1::LI, [RD, C_IMM6], [], "addi", [Src(0), Fixed(ZERO), Src(1)], [])),
+    ("c.addi16sp", c_inst!(C1, FUNCT3_C1::LUI, [C_ADDI16SP_IMM], [C_RD = SP], "addi", [Fixed(SP), Fixed(SP), Src(0)], [0])),
+    ("c.lui", c_inst!(C1, FUNCT3_C1::LUI, [RD, C_LUI_IMM], [], "lui", [Src(0), Src(1)], [1])),
+    ("c.srli", c_inst!(C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_SHAMT6], [C_FUNCT2 = FUNCT2_C_MISC_ALU::SRLI], "srli", [Src(0), Src(0), Src(1)], [])),
+    ("c.srai", c_inst!(C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_SHAMT6], [C_FUNCT2 = FUNCT2_C_MISC_ALU::SRAI], "srai", [Src(0), Src(0), Src(1)], [])),
+    ("c.andi", c_inst!(C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_IMM6], [C_FUNCT2 = FUNCT2_C_MISC_ALU::ANDI], "andi", [Src(0), Src(0), Src(1)], [])),
+    ("c.sub", c_inst!(C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_RS2P], [C_BIT12 = 0, C_FUNCT2 = FUNCT2_C_MISC_ALU::ARITH, C_FUNCT2_ARITH = FUNCT2_C_ARITH::SUB], "sub", [Src(0), Src(0), Src(1)], [])),
+    ("c.xor", c_inst!(C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_RS2P], [C_BIT12 = 0, C_FUNCT2 = FUNCT2_C_MISC_ALU::ARITH, C_FUNCT2_ARITH = FUNCT2_C_ARITH::XOR], "xor", [Src(0), Src(0), Src(1)], [])),
+    ("c.or", c_inst!(C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_RS2P], [C_BIT12 = 0, C_FUNCT2 = FUNCT2_C_MISC_ALU::ARITH, C_FUNCT2_ARITH = FUNCT2_C_ARITH::OR], "or", [Src(0), Src(0), Src(1)], [])),
+    ("c.and", c_inst!(C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_RS2P], [C_BIT12 = 0, C_FUNCT2 = FUNCT2_C_MISC_ALU::ARITH, C_FUNCT2_ARITH = FUNCT2_C_ARITH::AND], "and", [Src(0), Src(0), Src(1)], [])),
+    ("c.subw", c_inst!(rv64; C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_RS2P], [C_BIT12 = 1, C_FUNCT2 = FUNCT2_C_MISC_ALU::ARITH, C_FUNCT2_ARITH = FUNCT2_C_ARITH_W::SUBW], "subw", [Src(0), Src(0), Src(1)], [])),
+    ("c.addw", c_inst!(rv64; C1, FUNCT3_C1::MISC_ALU, [C_RS1P, C_RS2P], [C_BIT12 = 1, C_FUNCT2 = FUNCT2_C_MISC_ALU::ARITH, C_FUNCT2_ARITH = FUNCT2_C_ARITH_W::ADDW], "addw", [Src(0), Src(0), Src(1)], [])),
+    ("c.j", c_inst!(C1, FUNCT3_C1::J, [C_JIMM], [], "jal", [Fixed(ZERO), Src(0)], [])),
+    ("c.beqz", c_inst!(C1, FUNCT3_C1::BEQZ, [C_RS1P, C_BIMM], [], "beq", [Src(0), Fixed(ZERO), Src(1)], [])),
+    ("c.bnez", c_inst!(C1, FUNCT3_C1::BNEZ, [C_RS1P, C_BIMM], [], "bne", [Src(0), Fixed(ZERO), Src(1)], [])),
+
+    // Quadrant 2
+    ("c.slli", c_inst!(C2, FUNCT3_C2::SLLI, [RD, C_SHAMT6], [], "slli", [Src(0), Src(0), Src(1)], [])),
+    ("c.fldsp", c_inst!(C2, FUNCT3_C2::FLDSP, [FRD, C_LDSP_IMM], [], "fld", [Src(0), Fixed(SP), Src(1)], [])),
+    ("c.lwsp", c_inst!(C2, FUNCT3_C2::LWSP, [RD, C_LWSP_IMM], [], "lw", [Src(0), Fixed(SP), Src(1)], [0])),
+    ("c.flwsp", c_inst!(rv32; C2, FUNCT3_C2::FLWSP, [FRD, C_LWSP_IMM], [], "flw", [Src(0), Fixed(SP), Src(1)], [])),
+    ("c.ldsp", c_inst!(rv64; C2, FUNCT3_C2::FLWSP, [RD, C_LDSP_IMM], [], "ld", [Src(0), Fixed(SP), Src(1)], [0])),
+    ("c.jr", c_inst!(C2, FUNCT3_C2::JR_MV_ADD, [RD], [C_BIT12 = 0, C_RS2 = 0], "jalr", [Fixed(ZERO), Src(0), Fixed(0)], [0])),
+    ("c.mv", c_inst!(C2, FUNCT3_C2::JR_MV_ADD, [RD, C_RS2], [C_BIT12 = 0], "add", [Src(0), Fixed(ZERO), Src(1)], [1])),
+    ("c.ebreak", c_inst!(C2, FUNCT3_C2::JR_MV_ADD, [], [C_BIT12 = 1, C_RD = 0, C_RS2 = 0], "ebreak", [], [])),
+    ("c.jalr", c_inst!(C2, FUNCT3_C2::JR_MV_ADD, [RD], [C_BIT12 = 1, C_RS2 = 0], "jalr", [Fixed(RA), Src(0), Fixed(0)], [0])),
+    ("c.add", c_inst!(C2, FUNCT3_C2::JR_MV_ADD, [RD, C_RS2], [C_BIT12 = 1], "add", [Src(0), Src(0), Src(1)], [1])),
+    ("c.fsdsp", c_inst!(C2, FUNCT3_C2::FSDSP, [C_FRS2, C_SDSP_IMM], [], "fsd", [Fixed(SP), Src(0), Src(1)], [])),
+    ("c.swsp", c_inst!(C2, FUNCT3_C2::SWSP, [C_RS2, C_SWSP_IMM], [], "sw", [Fixed(SP), Src(0), Src(1)], [])),
+    ("c.fswsp", c_inst!(rv32; C2, FUNCT3_C2::FSWSP, [C_FRS2, C_SWSP_IMM], [], "fsw", [Fixed(SP), Src(0), Src(1)], [])),
+    ("c.sdsp", c_inst!(rv64; C2, FUNCT3_C2::FSWSP, [C_RS2, C_SDSP_IMM], [], "sd", [Fixed(SP), Src(0), Src(1)], [])),
+];
+
+pub fn compressed_inst(name: &str) -> Option<&'static CInst> {
+    CINSTS.iter().find(|x| x.0 == name).map(|x| &x.1)
+}
+
+pub fn is_compressed_inst(name: &str) -> bool {
+    compressed_inst(name).is_some()
+}
@@ -2,6 +2,7 @@ pub mod patch;
 pub mod register;
 pub mod arg;
 pub mod opcode;
+pub mod compressed;
 
 use self::patch::Patch;
 use self::arg::*;
@@ -54,14 +55,15 @@ macro_rules! store_inst {
 )
 }
 
+// shamt is 6 bits for RV64. funct6 is funct7 without its low bit
 macro_rules! i_type_shift_inst {
 ($funct3:ident) => (
     Inst{
-        args: &[&RD, &RS1, &SHAMT],
+        args: &[&RD, &RS1, &SHAMT6],
         opcodes: &[
             Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM as u32 },
             Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM::$funct3 as u32 },
-            Opcode{ patch: &patch::FUNCT7, value: 0 },
+            Opcode{ patch: &patch::FUNCT6, value: 0 },
         ],
     }
 )
@@ -70,16 +72,111 @@ macro_rules! i_type_shift_inst {
 macro_rules! i_type_shift_inst_alt {
 ($funct3:ident) => (
     Inst{
-        args: &[&RD, &RS1, &SHAMT],
+        args: &[&RD, &RS1, &SHAMT6],
         opcodes: &[
             Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM as u32 },
             Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM_ALT::$funct3 as u32 },
-            Opcode{ patch: &patch::FUNCT7, value: 0x20 },
+            Opcode{ patch: &patch::FUNCT6, value: 0x10 },
+        ],
+    }
+)
+}
+
+// RV64 word instructions
+macro_rules! i_type_32_inst {
+($funct3:ident) => (
+    Inst{
+        args: &[&RD, &RS1, &IMM12],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM_32 as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM_32::$funct3 as u32 },
+        ],
+    }
+)
+}
+
+macro_rules! i_type_32_shift_inst {
+($funct3:ident, $funct7:expr) => (
+    Inst{
+        args: &[&RD, &RS1, &SHAMT],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM_32 as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM_32::$funct3 as u32 },
+            Opcode{ patch: &patch::FUNCT7, value: $funct7 },
+        ],
+    }
+)
+}
+
+macro_rules! r_type_32_inst {
+($funct3:path, $funct7:expr) => (
+    Inst{
+        args: &[&RD, &RS1, &RS2],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_32 as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: $funct3 as u32 },
+            Opcode{ patch: &patch::FUNCT7, value: $funct7 as u32 },
+        ],
+    }
+)
+}
+
+// Zba, Zbb, Zbs
+macro_rules! r_type_zb_inst {
+($opcode:ident, $funct3:path, $funct7:ident) => (
+    Inst{
+        args: &[&RD, &RS1, &RS2],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::$opcode as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: $funct3 as u32 },
+            Opcode{ patch: &patch::FUNCT7, value: opcode::FUNCT7_OP::$funct7 as u32 },
+        ],
+    }
+)
+}
+
+// rs2 selects the operation
+macro_rules! unary_zb_inst {
+($opcode:ident, $funct3:path, $funct7:expr, $rs2:expr) => (
+    Inst{
+        args: &[&RD, &RS1],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::$opcode as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: $funct3 as u32 },
+            Opcode{ patch: &patch::FUNCT7, value: $funct7 as u32 },
+            Opcode{ patch: &patch::RS2, value: $rs2 as u32 },
+        ],
+    }
+)
+}
+
+macro_rules! i_type_zb_inst {
+($funct3:ident, $funct6:path) => (
+    Inst{
+        args: &[&RD, &RS1, &SHAMT6],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM::$funct3 as u32 },
+            Opcode{ patch: &patch::FUNCT6, value: $funct6 as u32 },
         ],
     }
 )
 }
 
+// orc.b and rev8. The shift amount is fixed
+macro_rules! i_type_zb_fixed_inst {
+($funct6:path, $shamt:path) => (
+    Inst{
+        args: &[&RD, &RS1],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM::SRLI as u32 },
+            Opcode{ patch: &patch::FUNCT6, value: $funct6 as u32 },
+            Opcode{ patch: &patch::SHAMT6, value: $shamt },
+        ],
+    }
+)
+}
 
 macro_rules! u_type_inst {
 ($opcode:ident) => (
@@ -118,6 +215,187 @@ macro_rules! r_type_inst_alt {
 )
 }
 
+macro_rules! r_type_inst_muldiv {
+($funct3:ident) => (
+    Inst{
+        args: &[&RD, &RS1, &RS2],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_MULDIV::$funct3 as u32 },
+            Opcode{ patch: &patch::FUNCT7, value: opcode::FUNCT7_OP::MULDIV as u32 },
+        ],
+    }
+)
+}
+
+// aqrl is (aq << 1) | rl. width is W, or D on RV64
+macro_rules! amo_inst {
+($funct5:ident, $width:ident, $aqrl:expr) => (
+    Inst{
+        args: &[&RD, &RS1, &RS2],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::AMO as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_AMO::$width as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_AMO::$funct5 as u32 },
+            Opcode{ patch: &patch::AQRL, value: $aqrl },
+        ],
+    }
+)
+}
+
+macro_rules! lr_inst {
+($width:ident, $aqrl:expr) => (
+    Inst{
+        args: &[&RD, &RS1],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::AMO as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_AMO::$width as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_AMO::LR as u32 },
+            Opcode{ patch: &patch::AQRL, value: $aqrl },
+            Opcode{ patch: &patch::RS2, value: 0 },
+        ],
+    }
+)
+}
+
+macro_rules! fp_load_inst {
+($funct3:ident) => (
+    Inst{
+        args: &[&FRD, &RS1, &IMM12],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::LOAD_FP as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_LOAD_FP::$funct3 as u32 },
+        ],
+    }
+)
+}
+
+macro_rules! fp_store_inst {
+($funct3:ident) => (
+    Inst{
+        args: &[&RS1, &FRS2, &SIMM12],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::STORE_FP as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: opcode::FUNCT3_STORE_FP::$funct3 as u32 },
+        ],
+    }
+)
+}
+
+// The plain form uses the dynamic rounding mode. The _rm form takes it as the last argument
+macro_rules! fp_fma_inst {
+($opcode:ident, $fmt:ident) => (
+    Inst{
+        args: &[&FRD, &FRS1, &FRS2, &FRS3],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::$opcode as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+            Opcode{ patch: &patch::RM, value: opcode::RM::DYN as u32 },
+        ],
+    }
+)
+}
+
+macro_rules! fp_fma_rm_inst {
+($opcode:ident, $fmt:ident) => (
+    Inst{
+        args: &[&FRD, &FRS1, &FRS2, &FRS3, &RM],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::$opcode as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+        ],
+    }
+)
+}
+
+macro_rules! fp_arith_inst {
+($funct5:ident, $fmt:ident) => (
+    Inst{
+        args: &[&FRD, &FRS1, &FRS2],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_FP as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_OP_FP::$funct5 as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+            Opcode{ patch: &patch::RM, value: opcode::RM::DYN as u32 },
+        ],
+    }
+)
+}
+
+macro_rules! fp_arith_rm_inst {
+($funct5:ident, $fmt:ident) => (
+    Inst{
+        args: &[&FRD, &FRS1, &FRS2, &RM],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_FP as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_OP_FP::$funct5 as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+        ],
+    }
+)
+}
+
+// One source operand, with rs2 fixed. sqrt and conversions
+macro_rules! fp_unary_inst {
+($rd:ident, $rs1:ident, $funct5:ident, $fmt:ident, $rs2:expr) => (
+    Inst{
+        args: &[&$rd, &$rs1],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_FP as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_OP_FP::$funct5 as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+            Opcode{ patch: &patch::RS2, value: $rs2 },
+            Opcode{ patch: &patch::RM, value: opcode::RM::DYN as u32 },
+        ],
+    }
+)
+}
+
+macro_rules! fp_unary_rm_inst {
+($rd:ident, $rs1:ident, $funct5:ident, $fmt:ident, $rs2:expr) => (
+    Inst{
+        args: &[&$rd, &$rs1, &RM],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_FP as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_OP_FP::$funct5 as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+            Opcode{ patch: &patch::RS2, value: $rs2 },
+        ],
+    }
+)
+}
+
+// funct3 selects the operation instead of a rounding mode
+macro_rules! fp_funct3_inst {
+($rd:ident, $funct5:ident, $fmt:ident, $funct3:expr) => (
+    Inst{
+        args: &[&$rd, &FRS1, &FRS2],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_FP as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_OP_FP::$funct5 as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+            Opcode{ patch: &patch::FUNCT3, value: $funct3 as u32 },
+        ],
+    }
+)
+}
+
+// Moves and fclass
+macro_rules! fp_move_inst {
+($rd:ident, $rs1:ident, $funct5:ident, $fmt:ident, $funct3:expr) => (
+    Inst{
+        args: &[&$rd, &$rs1],
+        opcodes: &[
+            Opcode{ patch: &patch::OPCODE, value: opcode::OPCODE::OP_FP as u32 },
+            Opcode{ patch: &patch::FUNCT5, value: opcode::FUNCT5_OP_FP::$funct5 as u32 },
+            Opcode{ patch: &patch::FMT, value: opcode::FMT::$fmt as u32 },
+            Opcode{ patch: &patch::RS2, value: 0 },
+            Opcode{ patch: &patch::FUNCT3, value: $funct3 as u32 },
+        ],
+    }
+)
+}
+
 macro_rules! branch_inst {
 ($funct3:ident) => (
     Inst{
@@ -199,6 +477,15 @@ pub const INSTS: &[(&'static str, Inst)] = &[
             Opcode { patch: &patch::RS1, value: 0 },
         ],
     }),
+    // addiw rd, rs1, 0. RV64
+    ("sext.w", Inst {
+        args: &[&arg::RD, &arg::RS1],
+        opcodes: &[
+            Opcode { patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM_32 as u32 },
+            Opcode { patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM_32::ADDIW as u32 },
+            Opcode { patch: &patch::IMM12, value: 0 },
+        ],
+    }),
     // sltiu rd, rs1, 1
     ("seqz", Inst {
         args: &[&arg::RD, &arg::RS1],
@@ -272,6 +559,99 @@ pub const INSTS: &[(&'static str, Inst)] = &[
     ("sub", r_type_inst_alt!(SUB)),
     ("sra", r_type_inst_alt!(SRA)),
 
+    // M extension
+    ("mul", r_type_inst_muldiv!(MUL)),
+    ("mulh", r_type_inst_muldiv!(MULH)),
+    ("mulhsu", r_type_inst_muldiv!(MULHSU)),
+    ("mulhu", r_type_inst_muldiv!(MULHU)),
+    ("div", r_type_inst_muldiv!(DIV)),
+    ("divu", r_type_inst_muldiv!(DIVU)),
+    ("rem", r_type_inst_muldiv!(REM)),
+    ("remu", r_type_inst_muldiv!(REMU)),
+
+    // RV64I
+    ("addiw", i_type_32_inst!(ADDIW)),
+    ("slliw", i_type_32_shift_inst!(SLLIW, 0)),
+    ("srliw", i_type_32_shift_inst!(SRLIW, 0)),
+    ("sraiw", i_type_32_shift_inst!(SRLIW, 0x20)),
+
+    ("addw", r_type_32_inst!(opcode::FUNCT3_OP_32::ADDW, opcode::FUNCT7_OP::BASE)),
+    ("sllw", r_type_32_inst!(opcode::FUNCT3_OP_32::SLLW, opcode::FUNCT7_OP::BASE)),
+    ("srlw", r_type_32_inst!(opcode::FUNCT3_OP_32::SRLW, opcode::FUNCT7_OP::BASE)),
+    ("subw", r_type_32_inst!(opcode::FUNCT3_OP_32_ALT::SUBW, opcode::FUNCT7_OP::ALT)),
+    ("sraw", r_type_32_inst!(opcode::FUNCT3_OP_32_ALT::SRAW, opcode::FUNCT7_OP::ALT)),
+
+    // RV64M
+    ("mulw", r_type_32_inst!(opcode::FUNCT3_OP_32_MULDIV::MULW, opcode::FUNCT7_OP::MULDIV)),
+    ("divw", r_type_32_inst!(opcode::FUNCT3_OP_32_MULDIV::DIVW, opcode::FUNCT7_OP::MULDIV)),
+    ("divuw", r_type_32_inst!(opcode::FUNCT3_OP_32_MULDIV::DIVUW, opcode::FUNCT7_OP::MULDIV)),
+    ("remw", r_type_32_inst!(opcode::FUNCT3_OP_32_MULDIV::REMW, opcode::FUNCT7_OP::MULDIV)),
+    ("remuw", r_type_32_inst!(opcode::FUNCT3_OP_32_MULDIV::REMUW, opcode::FUNCT7_OP::MULDIV)),
+
+    // Zba
+    ("sh1add", r_type_zb_inst!(OP, opcode::FUNCT3_OP_SHADD::SH1ADD, SHADD)),
+    ("sh2add", r_type_zb_inst!(OP, opcode::FUNCT3_OP_SHADD::SH2ADD, SHADD)),
+    ("sh3add", r_type_zb_inst!(OP, opcode::FUNCT3_OP_SHADD::SH3ADD, SHADD)),
+    // RV64
+    ("add.uw", r_type_zb_inst!(OP_32, opcode::FUNCT3_OP_ZEXT::ADD_UW, ZEXT)),
+    ("sh1add.uw", r_type_zb_inst!(OP_32, opcode::FUNCT3_OP_SHADD::SH1ADD, SHADD)),
+    ("sh2add.uw", r_type_zb_inst!(OP_32, opcode::FUNCT3_OP_SHADD::SH2ADD, SHADD)),
+    ("sh3add.uw", r_type_zb_inst!(OP_32, opcode::FUNCT3_OP_SHADD::SH3ADD, SHADD)),
+    ("slli.uw", Inst {
+        args: &[&arg::RD, &arg::RS1, &arg::SHAMT6],
+        opcodes: &[
+            Opcode { patch: &patch::OPCODE, value: opcode::OPCODE::OP_IMM_32 as u32 },
+            Opcode { patch: &patch::FUNCT3, value: opcode::FUNCT3_OP_IMM_32::SLLIW as u32 },
+            Opcode { patch: &patch::FUNCT6, value: opcode::FUNCT7_OP::ZEXT as u32 >> 1 },
+        ],
+    }),
+
+    // Zbb
+    ("andn", r_type_inst_alt!(ANDN)),
+    ("orn", r_type_inst_alt!(ORN)),
+    ("xnor", r_type_inst_alt!(XNOR)),
+
+    ("clz", unary_zb_inst!(OP_IMM, opcode::FUNCT3_OP_IMM::SLLI, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::CLZ)),
+    ("ctz", unary_zb_inst!(OP_IMM, opcode::FUNCT3_OP_IMM::SLLI, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::CTZ)),
+    ("cpop", unary_zb_inst!(OP_IMM, opcode::FUNCT3_OP_IMM::SLLI, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::CPOP)),
+    ("sext.b", unary_zb_inst!(OP_IMM, opcode::FUNCT3_OP_IMM::SLLI, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::SEXT_B)),
+    ("sext.h", unary_zb_inst!(OP_IMM, opcode::FUNCT3_OP_IMM::SLLI, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::SEXT_H)),
+    // zext.h is OP_32 in RV64
+    ("zext.h", unary_zb_inst!(OP, opcode::FUNCT3_OP_ZEXT::ZEXT_H, opcode::FUNCT7_OP::ZEXT, 0)),
+    ("zext.h", unary_zb_inst!(OP_32, opcode::FUNCT3_OP_ZEXT::ZEXT_H, opcode::FUNCT7_OP::ZEXT, 0)),
+
+    ("min", r_type_zb_inst!(OP, opcode::FUNCT3_OP_MINMAX::MIN, MINMAX)),
+    ("minu", r_type_zb_inst!(OP, opcode::FUNCT3_OP_MINMAX::MINU, MINMAX)),
+    ("max", r_type_zb_inst!(OP, opcode::FUNCT3_OP_MINMAX::MAX, MINMAX)),
+    ("maxu", r_type_zb_inst!(OP, opcode::FUNCT3_OP_MINMAX::MAXU, MINMAX)),
+
+    ("rol", r_type_zb_inst!(OP, opcode::FUNCT3_OP_ROT::ROL, ROT)),
+    ("ror", r_type_zb_inst!(OP, opcode::FUNCT3_OP_ROT::ROR, ROT)),
+    ("rori", i_type_zb_inst!(SRLI, opcode::FUNCT6_OP_IMM_SRL::RORI)),
+
+    ("orc.b", i_type_zb_fixed_inst!(opcode::FUNCT6_OP_IMM_SRL::ORC_B, opcode::SHAMT_ORC_B)),
+    // The shift amount is XLEN - 8
+    ("rev8", i_type_zb_fixed_inst!(opcode::FUNCT6_OP_IMM_SRL::REV8, opcode::SHAMT_REV8_32)),
+    ("rev8", i_type_zb_fixed_inst!(opcode::FUNCT6_OP_IMM_SRL::REV8, opcode::SHAMT_REV8_64)),
+
+    // RV64
+    ("clzw", unary_zb_inst!(OP_IMM_32, opcode::FUNCT3_OP_IMM_32::SLLIW, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::CLZ)),
+    ("ctzw", unary_zb_inst!(OP_IMM_32, opcode::FUNCT3_OP_IMM_32::SLLIW, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::CTZ)),
+    ("cpopw", unary_zb_inst!(OP_IMM_32, opcode::FUNCT3_OP_IMM_32::SLLIW, opcode::FUNCT7_OP::ROT, opcode::RS2_OP_IMM_UNARY::CPOP)),
+    ("rolw", r_type_zb_inst!(OP_32, opcode::FUNCT3_OP_ROT::ROL, ROT)),
+    ("rorw", r_type_zb_inst!(OP_32, opcode::FUNCT3_OP_ROT::ROR, ROT)),
+    ("roriw", i_type_32_shift_inst!(SRLIW, opcode::FUNCT7_OP::ROT as u32)),
+
+    // Zbs
+    ("bclr", r_type_zb_inst!(OP, opcode::FUNCT3_OP_BIT::BIT, BCLR)),
+    ("bext", r_type_zb_inst!(OP, opcode::FUNCT3_OP_BIT::BEXT, BCLR)),
+    ("binv", r_type_zb_inst!(OP, opcode::FUNCT3_OP_BIT::BIT, BINV)),
+    ("bset", r_type_zb_inst!(OP, opcode::FUNCT3_OP_BIT::BIT, BSET)),
+    ("bclri", i_type_zb_inst!(SLLI, opcode::FUNCT6_OP_IMM_SLL::BCLRI)),
+    ("bexti", i_type_zb_inst!(SRLI, opcode::FUNCT6_OP_IMM_SRL::BEXTI)),
+    ("binvi", i_type_zb_inst!(SLLI, opcode::FUNCT6_OP_IMM_SLL::BINVI)),
+    ("bseti", i_type_zb_inst!(SLLI, opcode::FUNCT6_OP_IMM_SLL::BSETI)),
+
     // Control transfer
     ("jal", Inst {
         args: &[&arg::RD, &arg::JIMM20],
@@ -305,8 +685,276 @@ pub const INSTS: &[(&'static str, Inst)] = &[
     ("sh", store_inst!(SH)),
     ("sw", store_inst!(SW)),
 
+    // RV64
+    ("ld", load_inst!(LD)),
+    ("lwu", load_inst!(LWU)),
+    ("sd", store_inst!(SD)),
+
+    // A extension
+    ("lr.w", lr_inst!(W, 0)),
+    ("lr.w.aq", lr_inst!(W, 2)),
+    ("lr.w.rl", lr_inst!(W, 1)),
+    ("lr.w.aqrl", lr_inst!(W, 3)),
+    ("sc.w", amo_inst!(SC, W, 0)),
+    ("sc.w.aq", amo_inst!(SC, W, 2)),
+    ("sc.w.rl", amo_inst!(SC, W, 1)),
+    ("sc.w.aqrl", amo_inst!(SC, W, 3)),
+
+    ("amoswap.w", amo_inst!(AMOSWAP, W, 0)),
+    ("amoswap.w.aq", amo_inst!(AMOSWAP, W, 2)),
+    ("amoswap.w.rl", amo_inst!(AMOSWAP, W, 1)),
+    ("amoswap.w.aqrl", amo_inst!(AMOSWAP, W, 3)),
+
+    ("amoadd.w", amo_inst!(AMOADD, W, 0)),
+    ("amoadd.w.aq", amo_inst!(AMOADD, W, 2)),
+    ("amoadd.w.rl", amo_inst!(AMOADD, W, 1)),
+    ("amoadd.w.aqrl", amo_inst!(AMOADD, W, 3)),
+
+    ("amoxor.w", amo_inst!(AMOXOR, W, 0)),
+    ("amoxor.w.aq", amo_inst!(AMOXOR, W, 2)),
+    ("amoxor.w.rl", amo_inst!(AMOXOR, W, 1)),
+    ("amoxor.w.aqrl", amo_inst!(AMOXOR, W, 3)),
+
+    ("amoand.w", amo_inst!(AMOAND, W, 0)),
+    ("amoand.w.aq", amo_inst!(AMOAND, W, 2)),
+    ("amoand.w.rl", amo_inst!(AMOAND, W, 1)),
+    ("amoand.w.aqrl", amo_inst!(AMOAND, W, 3)),
+
+    ("amoor.w", amo_inst!(AMOOR, W, 0)),
+    ("amoor.w.aq", amo_inst!(AMOOR, W, 2)),
+    ("amoor.w.rl", amo_inst!(AMOOR, W, 1)),
+    ("amoor.w.aqrl", amo_inst!(AMOOR, W, 3)),
+
+    ("amomin.w", amo_inst!(AMOMIN, W, 0)),
+    ("amomin.w.aq", amo_inst!(AMOMIN, W, 2)),
+    ("amomin.w.rl", amo_inst!(AMOMIN, W, 1)),
+    ("amomin.w.aqrl", amo_inst!(AMOMIN, W, 3)),
+
+    ("amomax.w", amo_inst!(AMOMAX, W, 0)),
+    ("amomax.w.aq", amo_inst!(AMOMAX, W, 2)),
+    ("amomax.w.rl", amo_inst!(AMOMAX, W, 1)),
+    ("amomax.w.aqrl", amo_inst!(AMOMAX, W, 3)),
+
+    ("amominu.w", amo_inst!(AMOMINU, W, 0)),
+    ("amominu.w.aq", amo_inst!(AMOMINU, W, 2)),
+    ("amominu.w.rl", amo_inst!(AMOMINU, W, 1)),
+    ("amominu.w.aqrl", amo_inst!(AMOMINU, W, 3)),
+
+    ("amomaxu.w", amo_inst!(AMOMAXU, W, 0)),
+    ("amomaxu.w.aq", amo_inst!(AMOMAXU, W, 2)),
+    ("amomaxu.w.rl", amo_inst!(AMOMAXU, W, 1)),
+    ("amomaxu.w.aqrl", amo_inst!(AMOMAXU, W, 3)),
+
+    // RV64A
+    ("lr.d", lr_inst!(D, 0)),
+    ("lr.d.aq", lr_inst!(D, 2)),
+    ("lr.d.rl", lr_inst!(D, 1)),
+    ("lr.d.aqrl", lr_inst!(D, 3)),
+    ("sc.d", amo_inst!(SC, D, 0)),
+    ("sc.d.aq", amo_inst!(SC, D, 2)),
+    ("sc.d.rl", amo_inst!(SC, D, 1)),
+    ("sc.d.aqrl", amo_inst!(SC, D, 3)),
+
+    ("amoswap.d", amo_inst!(AMOSWAP, D, 0)),
+    ("amoswap.d.aq", amo_inst!(AMOSWAP, D, 2)),
+    ("amoswap.d.rl", amo_inst!(AMOSWAP, D, 1)),
+    ("amoswap.d.aqrl", amo_inst!(AMOSWAP, D, 3)),
+
+    ("amoadd.d", amo_inst!(AMOADD, D, 0)),
+    ("amoadd.d.aq", amo_inst!(AMOADD, D, 2)),
+    ("amoadd.d.rl", amo_inst!(AMOADD, D, 1)),
+    ("amoadd.d.aqrl", amo_inst!(AMOADD, D, 3)),
+
+    ("amoxor.d", amo_inst!(AMOXOR, D, 0)),
+    ("amoxor.d.aq", amo_inst!(AMOXOR, D, 2)),
+    ("amoxor.d.rl", amo_inst!(AMOXOR, D, 1)),
+    ("amoxor.d.aqrl", amo_inst!(AMOXOR, D, 3)),
+
+    ("amoand.d", amo_inst!(AMOAND, D, 0)),
+    ("amoand.d.aq", amo_inst!(AMOAND, D, 2)),
+    ("amoand.d.rl", amo_inst!(AMOAND, D, 1)),
+    ("amoand.d.aqrl", amo_inst!(AMOAND, D, 3)),
+
+    ("amoor.d", amo_inst!(AMOOR, D, 0)),
+    ("amoor.d.aq", amo_inst!(AMOOR, D, 2)),
+    ("amoor.d.rl", amo_inst!(AMOOR, D, 1)),
+    ("amoor.d.aqrl", amo_inst!(AMOOR, D, 3)),
+
+    ("amomin.d", amo_inst!(AMOMIN, D, 0)),
+    ("amomin.d.aq", amo_inst!(AMOMIN, D, 2)),
+    ("amomin.d.rl", amo_inst!(AMOMIN, D, 1)),
+    ("amomin.d.aqrl", amo_inst!(AMOMIN, D, 3)),
+
+    ("amomax.d", amo_inst!(AMOMAX, D, 0)),
+    ("amomax.d.aq", amo_inst!(AMOMAX, D, 2)),
+    ("amomax.d.rl", amo_inst!(AMOMAX, D, 1)),
+    ("amomax.d.aqrl", amo_inst!(AMOMAX, D, 3)),
+
+    ("amominu.d", amo_inst!(AMOMINU, D, 0)),
+    ("amominu.d.aq", amo_inst!(AMOMINU, D, 2)),
+    ("amominu.d.rl", amo_inst!(AMOMINU, D, 1)),
+    ("amominu.d.aqrl", amo_inst!(AMOMINU, D, 3)),
+
+    ("amomaxu.d", amo_inst!(AMOMAXU, D, 0)),
+    ("amomaxu.d.aq", amo_inst!(AMOMAXU, D, 2)),
+    ("amomaxu.d.rl", amo_inst!(AMOMAXU, D, 1)),
+    ("amomaxu.d.aqrl", amo_inst!(AMOMAXU, D, 3)),
+
+    // F extension
+    ("flw", fp_load_inst!(FLW)),
+    ("fsw", fp_store_inst!(FSW)),
+
+    ("fmadd.s", fp_fma_inst!(MADD, S)),
+    ("fmadd.s", fp_fma_rm_inst!(MADD, S)),
+    ("fmsub.s", fp_fma_inst!(MSUB, S)),
+    ("fmsub.s", fp_fma_rm_inst!(MSUB, S)),
+    ("fnmsub.s", fp_fma_inst!(NMSUB, S)),
+    ("fnmsub.s", fp_fma_rm_inst!(NMSUB, S)),
+    ("fnmadd.s", fp_fma_inst!(NMADD, S)),
+    ("fnmadd.s", fp_fma_rm_inst!(NMADD, S)),
+
+    ("fadd.s", fp_arith_inst!(FADD, S)),
+    ("fadd.s", fp_arith_rm_inst!(FADD, S)),
+    ("fsub.s", fp_arith_inst!(FSUB, S)),
+    ("fsub.s", fp_arith_rm_inst!(FSUB, S)),
+    ("fmul.s", fp_arith_inst!(FMUL, S)),
+    ("fmul.s", fp_arith_rm_inst!(FMUL, S)),
+    ("fdiv.s", fp_arith_inst!(FDIV, S)),
+    ("fdiv.s", fp_arith_rm_inst!(FDIV, S)),
+    ("fsqrt.s", fp_unary_inst!(FRD, FRS1, FSQRT, S, 0)),
+    ("fsqrt.s", fp_unary_rm_inst!(FRD, FRS1, FSQRT, S, 0)),
+
+    ("fsgnj.s", fp_funct3_inst!(FRD, FSGNJ, S, opcode::FUNCT3_FSGNJ::FSGNJ)),
+    ("fsgnjn.s", fp_funct3_inst!(FRD, FSGNJ, S, opcode::FUNCT3_FSGNJ::FSGNJN)),
+    ("fsgnjx.s", fp_funct3_inst!(FRD, FSGNJ, S, opcode::FUNCT3_FSGNJ::FSGNJX)),
+    ("fmin.s", fp_funct3_inst!(FRD, FMINMAX, S, opcode::FUNCT3_FMINMAX::FMIN)),
+    ("fmax.s", fp_funct3_inst!(FRD, FMINMAX, S, opcode::FUNCT3_FMINMAX::FMAX)),
+
+    ("feq.s", fp_funct3_inst!(RD, FCMP, S, opcode::FUNCT3_FCMP::FEQ)),
+    ("flt.s", fp_funct3_inst!(RD, FCMP, S, opcode::FUNCT3_FCMP::FLT)),
+    ("fle.s", fp_funct3_inst!(RD, FCMP, S, opcode::FUNCT3_FCMP::FLE)),
+    ("fclass.s", fp_move_inst!(RD, FRS1, FMV_X_CLASS, S, opcode::FUNCT3_FMV_X_CLASS::FCLASS)),
+
+    ("fcvt.w.s", fp_unary_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.w.s", fp_unary_rm_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.wu.s", fp_unary_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::WU as u32)),
+    ("fcvt.wu.s", fp_unary_rm_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::WU as u32)),
+    ("fcvt.s.w", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.s.w", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.s.wu", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::WU as u32)),
+    ("fcvt.s.wu", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::WU as u32)),
+
+    ("fmv.x.w", fp_move_inst!(RD, FRS1, FMV_X_CLASS, S, opcode::FUNCT3_FMV_X_CLASS::FMV_X)),
+    ("fmv.w.x", fp_move_inst!(FRD, RS1, FMV_FROM_X, S, 0)),
+
+    // RV64F
+    ("fcvt.l.s", fp_unary_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.l.s", fp_unary_rm_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.lu.s", fp_unary_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::LU as u32)),
+    ("fcvt.lu.s", fp_unary_rm_inst!(RD, FRS1, FCVT_W, S, opcode::RS2_FCVT_W::LU as u32)),
+    ("fcvt.s.l", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.s.l", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.s.lu", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::LU as u32)),
+    ("fcvt.s.lu", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, S, opcode::RS2_FCVT_W::LU as u32)),
+
+    // D extension
+    ("fld", fp_load_inst!(FLD)),
+    ("fsd", fp_store_inst!(FSD)),
+
+    ("fmadd.d", fp_fma_inst!(MADD, D)),
+    ("fmadd.d", fp_fma_rm_inst!(MADD, D)),
+    ("fmsub.d", fp_fma_inst!(MSUB, D)),
+    ("fmsub.d", fp_fma_rm_inst!(MSUB, D)),
+    ("fnmsub.d", fp_fma_inst!(NMSUB, D)),
+    ("fnmsub.d", fp_fma_rm_inst!(NMSUB, D)),
+    ("fnmadd.d", fp_fma_inst!(NMADD, D)),
+    ("fnmadd.d", fp_fma_rm_inst!(NMADD, D)),
+
+    ("fadd.d", fp_arith_inst!(FADD, D)),
+    ("fadd.d", fp_arith_rm_inst!(FADD, D)),
+    ("fsub.d", fp_arith_inst!(FSUB, D)),
+    ("fsub.d", fp_arith_rm_inst!(FSUB, D)),
+    ("fmul.d", fp_arith_inst!(FMUL, D)),
+    ("fmul.d", fp_arith_rm_inst!(FMUL, D)),
+    ("fdiv.d", fp_arith_inst!(FDIV, D)),
+    ("fdiv.d", fp_arith_rm_inst!(FDIV, D)),
+    ("fsqrt.d", fp_unary_inst!(FRD, FRS1, FSQRT, D, 0)),
+    ("fsqrt.d", fp_unary_rm_inst!(FRD, FRS1, FSQRT, D, 0)),
+
+    ("fsgnj.d", fp_funct3_inst!(FRD, FSGNJ, D, opcode::FUNCT3_FSGNJ::FSGNJ)),
+    ("fsgnjn.d", fp_funct3_inst!(FRD, FSGNJ, D, opcode::FUNCT3_FSGNJ::FSGNJN)),
+    ("fsgnjx.d", fp_funct3_inst!(FRD, FSGNJ, D, opcode::FUNCT3_FSGNJ::FSGNJX)),
+    ("fmin.d", fp_funct3_inst!(FRD, FMINMAX, D, opcode::FUNCT3_FMINMAX::FMIN)),
+    ("fmax.d", fp_funct3_inst!(FRD, FMINMAX, D, opcode::FUNCT3_FMINMAX::FMAX)),
+
+    ("feq.d", fp_funct3_inst!(RD, FCMP, D, opcode::FUNCT3_FCMP::FEQ)),
+    ("flt.d", fp_funct3_inst!(RD, FCMP, D, opcode::FUNCT3_FCMP::FLT)),
+    ("fle.d", fp_funct3_inst!(RD, FCMP, D, opcode::FUNCT3_FCMP::FLE)),
+    ("fclass.d", fp_move_inst!(RD, FRS1, FMV_X_CLASS, D, opcode::FUNCT3_FMV_X_CLASS::FCLASS)),
+
+    ("fcvt.w.d", fp_unary_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.w.d", fp_unary_rm_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.wu.d", fp_unary_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::WU as u32)),
+    ("fcvt.wu.d", fp_unary_rm_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::WU as u32)),
+    ("fcvt.d.w", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.d.w", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::W as u32)),
+    ("fcvt.d.wu", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::WU as u32)),
+    ("fcvt.d.wu", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::WU as u32)),
+
+    // RV64D
+    ("fcvt.l.d", fp_unary_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.l.d", fp_unary_rm_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.lu.d", fp_unary_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::LU as u32)),
+    ("fcvt.lu.d", fp_unary_rm_inst!(RD, FRS1, FCVT_W, D, opcode::RS2_FCVT_W::LU as u32)),
+    ("fcvt.d.l", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.d.l", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::L as u32)),
+    ("fcvt.d.lu", fp_unary_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::LU as u32)),
+    ("fcvt.d.lu", fp_unary_rm_inst!(FRD, RS1, FCVT_FROM_W, D, opcode::RS2_FCVT_W::LU as u32)),
+
+    ("fmv.x.d", fp_move_inst!(RD, FRS1, FMV_X_CLASS, D, opcode::FUNCT3_FMV_X_CLASS::FMV_X)),
+    ("fmv.d.x", fp_move_inst!(FRD, RS1, FMV_FROM_X, D, 0)),
+
+    // rs2 is the source format
+    ("fcvt.s.d", fp_unary_inst!(FRD, FRS1, FCVT_FMT, S, opcode::FMT::D as u32)),
+    ("fcvt.s.d", fp_unary_rm_inst!(FRD, FRS1, FCVT_FMT, S, opcode::FMT::D as u32)),
+    ("fcvt.d.s", fp_unary_inst!(FRD, FRS1, FCVT_FMT, D, opcode::FMT::S as u32)),
+    ("fcvt.d.s", fp_unary_rm_inst!(FRD, FRS1, FCVT_FMT, D, opcode::FMT::S as u32)),
+
     // Memory model
-    // fence..
+    // Without arguments, fence orders everything
+    ("fence", Inst {
+        args: &[],
+        opcodes: &[
+            Opcode { patch: &patch::OPCODE, value: opcode::OPCODE::MISC_MEM as u32 },
+            Opcode { patch: &patch::FUNCT3, value: opcode::FUNCT3_MISC_MEM::FENCE as u32 },
+            Opcode { patch: &patch::RD, value: 0 },
+            Opcode { patch: &patch::RS1, value: 0 },
+            Opcode { patch: &patch::PRED, value: FENCE_IORW },
+            Opcode { patch: &patch::SUCC, value: FENCE_IORW },
+            Opcode { patch: &patch::FM, value: 0 },
+        ],
+    }),
+    ("fence", Inst {
+        args: &[&arg::PRED, &arg::SUCC],
+        opcodes: &[
+            Opcode { patch: &patch::OPCODE, value: opcode::OPCODE::MISC_MEM as u32 },
+            Opcode { patch: &patch::FUNCT3, value: opcode::FUNCT3_MISC_MEM::FENCE as u32 },
+            Opcode { patch: &patch::RD, value: 0 },
+            Opcode { patch: &patch::RS1, value: 0 },
+            Opcode { patch: &patch::FM, value: 0 },
+        ],
+    }),
+    // Zifencei
+    ("fence.i", Inst {
+        args: &[],
+        opcodes: &[
+            Opcode { patch: &patch::OPCODE, value: opcode::OPCODE::MISC_MEM as u32 },
+            Opcode { patch: &patch::FUNCT3, value: opcode::FUNCT3_MISC_MEM::FENCE_I as u32 },
+            Opcode { patch: &patch::RD, value: 0 },
+            Opcode { patch: &patch::RS1, value: 0 },
+            Opcode { patch: &patch::IMM12, value: 0 },
+        ],
+    }),
 
     // SYSTEM
     ("csrrw", csr_inst!(CSRRW)),
@@ -329,6 +977,16 @@ pub const INSTS: &[(&'static str, Inst)] = &[
 
     ("wfi", env_inst!(WFI)),
 
+    ("sfence.vma", Inst {
+        args: &[&arg::RS1, &arg::RS2],
+        opcodes: &[
+            Opcode { patch: &patch::OPCODE, value: opcode::OPCODE::SYSTEM as u32 },
+            Opcode { patch: &patch::RD, value: 0 },
+            Opcode { patch: &patch::FUNCT3, value: opcode::FUNCT3_SYSTEM::PRIV as u32 },
+            Opcode { patch: &patch::FUNCT7, value: opcode::FUNCT7_SFENCE_VMA },
+        ],
+    }),
+
     // Low priority pseudoinstructions
 ];
 
@@ -342,13 +1000,69 @@ pub fn inst(name: &str) -> Option<&'static Inst> {
     None
 }
 
+// Some instructions have an optional argument, as separate entries with the same name
+pub fn inst_with_arity(name: &str, arity: usize) -> Option<&'static Inst> {
+    for &(n, ref i) in INSTS {
+        if n == name && i.args.len() == arity {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
 pub fn is_inst(name: &str) -> bool {
     INSTS.iter().any(|x| x.0 == name)
 }
 
+const ROUNDING_MODES: &'static [(&'static str, u32)] = &[
+    ("rne", opcode::RM::RNE as u32),
+    ("rtz", opcode::RM::RTZ as u32),
+    ("rdn", opcode::RM::RDN as u32),
+    ("rup", opcode::RM::RUP as u32),
+    ("rmm", opcode::RM::RMM as u32),
+    ("dyn", opcode::RM::DYN as u32),
+];
+
+pub fn rounding_mode_name(value: u32) -> Option<&'static str> {
+    ROUNDING_MODES.iter().find(|&&(_, v)| v == value).map(|&(n, _)| n)
+}
+
+pub fn rounding_mode_value(name: &str) -> Option<u32> {
+    ROUNDING_MODES.iter().find(|&&(n, _)| n == name).map(|&(_, v)| v)
+}
+
+// fence predecessor and successor sets, written as a subset of "iorw" in that order
+const FENCE_SET: &'static [(char, u32)] = &[('i', 0x8), ('o', 0x4), ('r', 0x2), ('w', 0x1)];
+pub const FENCE_IORW: u32 = 0xF;
+
+pub fn fence_set_name(value: u32) -> Option<String> {
+    if value == 0 || value > FENCE_IORW {
+        return None;
+    }
+    Some(FENCE_SET.iter().filter(|&&(_, b)| value & b != 0).map(|&(c, _)| c).collect())
+}
+
+pub fn fence_set_value(name: &str) -> Option<u32> {
+    let mut rest = name;
+    let mut value = 0;
+    for &(c, b) in FENCE_SET {
+        if rest.starts_with(c) {
+            value |= b;
+            rest = &rest[1..];
+        }
+    }
+    if value == 0 || !rest.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
 pub fn format_arg(arg: &Arg, v: u32) -> String {
     match &arg.arg_type {
-        &ArgType::Register => format!("{}", ::arch::inst::register::abi_name(v as u8)),
+        &ArgType::Register | &ArgType::CompressedRegister => format!("{}", ::arch::inst::register::abi_name(v as u8)),
+        &ArgType::FloatRegister | &ArgType::CompressedFloatRegister => format!("{}", ::arch::inst::register::float_abi_name(v as u8)),
         &ArgType::Address => format!("0x{:08X}", v),
         &ArgType::General => format!("{}", v as i32),
         &ArgType::Csr => {
@@ -358,11 +1072,20 @@ pub fn format_arg(arg: &Arg, v: u32) -> String {
                 format!("(csr {})", v)
             }
         },
+        &ArgType::RoundingMode => {
+            if let Some(s) = rounding_mode_name(v) {
+                format!("{}", s)
+            } else {
+                format!("(rm {})", v)
+            }
+        },
+        &ArgType::FenceSet => fence_set_name(v).unwrap_or_else(|| format!("{}", v)),
     }
 }
 
 pub enum INST_TYPE {
     R,
+    R4,
     I,
     S,
     SB,
@@ -375,13 +1098,25 @@ use self::opcode::*;
 pub fn inst_type(opcode: OPCODE) -> INST_TYPE {
     match opcode {
         OPCODE::OP => INST_TYPE::R,
+        OPCODE::OP_32 => INST_TYPE::R,
+        OPCODE::AMO => INST_TYPE::R,
+        OPCODE::OP_FP => INST_TYPE::R,
+
+        OPCODE::MADD => INST_TYPE::R4,
+        OPCODE::MSUB => INST_TYPE::R4,
+        OPCODE::NMSUB => INST_TYPE::R4,
+        OPCODE::NMADD => INST_TYPE::R4,
 
         OPCODE::LOAD => INST_TYPE::I,
+        OPCODE::LOAD_FP => INST_TYPE::I,
         OPCODE::OP_IMM => INST_TYPE::I,
+        OPCODE::OP_IMM_32 => INST_TYPE::I,
         OPCODE::JALR => INST_TYPE::I,
+        OPCODE::MISC_MEM => INST_TYPE::I,
         OPCODE::SYSTEM => INST_TYPE::I,
 
         OPCODE::STORE => INST_TYPE::S,
+        OPCODE::STORE_FP => INST_TYPE::S,
 
         OPCODE::BRANCH => INST_TYPE::SB,
 
@@ -49,8 +49,10 @@ pub enum FUNCT3_LOAD {
     LB = 0,
     LH,
     LW,
+    LD, // RV64
     LBU,
     LHU,
+    LWU, // RV64
 }
 }
 
@@ -100,6 +102,48 @@ pub enum FUNCT3_OP_IMM_ALT {
 }
 }
 
+// RV64. Operate on the low 32 bits and sign-extend the result
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_IMM_32 {
+    ADDIW = 0,
+    SLLIW,
+    SRLIW = 5, // and sraiw
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_32 {
+    ADDW = 0,
+    SLLW,
+    SRLW = 5,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_32_ALT {
+    SUBW = 0,
+    SRAW = 5,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_32_MULDIV {
+    MULW = 0,
+    DIVW = 4,
+    DIVUW,
+    REMW,
+    REMUW,
+}
+}
+
 enum_from_primitive! {
 #[derive(PartialEq, Eq)]
 #[derive(Debug)]
@@ -107,6 +151,7 @@ pub enum FUNCT3_STORE {
     SB = 0,
     SH,
     SW,
+    SD, // RV64
 }
 }
 
@@ -131,7 +176,15 @@ enum_from_primitive! {
 #[derive(Debug)]
 pub enum FUNCT7_OP {
     BASE = 0,
+    MULDIV = 1,
+    ZEXT = 0x04, // Zba, Zbb
+    MINMAX = 0x05, // Zbb
+    SHADD = 0x10, // Zba
+    BSET = 0x14, // Zbs
     ALT = 0x20,
+    BCLR = 0x24, // Zbs, and bext
+    ROT = 0x30, // Zbb
+    BINV = 0x34, // Zbs
 }
 }
 
@@ -140,7 +193,260 @@ enum_from_primitive! {
 #[derive(Debug)]
 pub enum FUNCT3_OP_ALT {
     SUB = 0,
+    XNOR = 4, // Zbb
     SRA = 5,
+    ORN = 6, // Zbb
+    ANDN = 7, // Zbb
+}
+}
+
+// Zba, Zbb, Zbs. These also select OP_32 instructions, which work on the low 32 bits
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_ZEXT {
+    ADD_UW = 0, // RV64, OP_32
+    ZEXT_H = 4,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_MINMAX {
+    MIN = 4,
+    MINU,
+    MAX,
+    MAXU,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_SHADD {
+    SH1ADD = 2,
+    SH2ADD = 4,
+    SH3ADD = 6,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_ROT {
+    ROL = 1,
+    ROR = 5,
+}
+}
+
+// bclr, bset and binv are told apart by funct7
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_BIT {
+    BIT = 1,
+    BEXT = 5,
+}
+}
+
+// funct6 above the shift amount of OP_IMM
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT6_OP_IMM_SLL {
+    SLLI = 0,
+    BSETI = 0x0A,
+    BCLRI = 0x12,
+    UNARY = 0x18, // RS2_OP_IMM_UNARY in the shift amount
+    BINVI = 0x1A,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT6_OP_IMM_SRL {
+    SRLI = 0,
+    ORC_B = 0x0A,
+    SRAI = 0x10,
+    BEXTI = 0x12,
+    RORI = 0x18,
+    REV8 = 0x1A,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum RS2_OP_IMM_UNARY {
+    CLZ = 0,
+    CTZ,
+    CPOP,
+    SEXT_B = 4,
+    SEXT_H,
+}
+}
+
+// Fixed shift amounts of orc.b and rev8
+pub const SHAMT_ORC_B: u32 = 0x07;
+pub const SHAMT_REV8_32: u32 = 0x18;
+pub const SHAMT_REV8_64: u32 = 0x38;
+
+// RV32M
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_OP_MULDIV {
+    MUL = 0,
+    MULH,
+    MULHSU,
+    MULHU,
+    DIV,
+    DIVU,
+    REM,
+    REMU,
+}
+}
+
+// RV32A
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_AMO {
+    W = 2,
+    D, // RV64
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT5_AMO {
+    AMOADD = 0x00,
+    AMOSWAP = 0x01,
+    LR = 0x02,
+    SC = 0x03,
+    AMOXOR = 0x04,
+    AMOOR = 0x08,
+    AMOAND = 0x0C,
+    AMOMIN = 0x10,
+    AMOMAX = 0x14,
+    AMOMINU = 0x18,
+    AMOMAXU = 0x1C,
+}
+}
+
+// RV32F/D
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_LOAD_FP {
+    FLW = 2,
+    FLD,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_STORE_FP {
+    FSW = 2,
+    FSD,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+pub enum FMT {
+    S = 0,
+    D,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+pub enum FUNCT5_OP_FP {
+    FADD = 0x00,
+    FSUB = 0x01,
+    FMUL = 0x02,
+    FDIV = 0x03,
+    FSGNJ = 0x04,
+    FMINMAX = 0x05,
+    FCVT_FMT = 0x08, // between S and D
+    FSQRT = 0x0B,
+    FCMP = 0x14,
+    FCVT_W = 0x18, // to integer
+    FCVT_FROM_W = 0x1A,
+    FMV_X_CLASS = 0x1C,
+    FMV_FROM_X = 0x1E,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_FSGNJ {
+    FSGNJ = 0,
+    FSGNJN,
+    FSGNJX,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_FMINMAX {
+    FMIN = 0,
+    FMAX,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_FCMP {
+    FLE = 0,
+    FLT,
+    FEQ,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_FMV_X_CLASS {
+    FMV_X = 0,
+    FCLASS,
+}
+}
+
+// rs2 of fcvt to and from integers
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum RS2_FCVT_W {
+    W = 0,
+    WU,
+    L, // RV64
+    LU,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum RM {
+    RNE = 0,
+    RTZ,
+    RDN,
+    RUP,
+    RMM,
+    DYN = 7,
 }
 }
 
@@ -191,7 +497,96 @@ pub enum FUNCT12_PRIV {
 //    HRET = 0x202,
     MRET = 0x302,
     WFI = 0x105,
+}
+}
+
+// sfence.vma takes rs1 and rs2, so only the upper 7 bits of funct12 are fixed
+pub const FUNCT7_SFENCE_VMA: u32 = 0x09;
+
+// Compressed
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum C_OP {
+    C0 = 0,
+    C1,
+    C2,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_C0 {
+    ADDI4SPN = 0,
+    FLD,
+    LW,
+    FLW, // c.ld on RV64
+    FSD = 5,
+    SW,
+    FSW, // c.sd on RV64
+}
+}
 
-    SFENCEVM = 0x104,
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_C1 {
+    ADDI = 0,
+    JAL, // c.addiw on RV64
+    LI,
+    LUI, // and addi16sp
+    MISC_ALU,
+    J,
+    BEQZ,
+    BNEZ,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT3_C2 {
+    SLLI = 0,
+    FLDSP,
+    LWSP,
+    FLWSP, // c.ldsp on RV64
+    JR_MV_ADD, // and ebreak, jalr
+    FSDSP,
+    SWSP,
+    FSWSP, // c.sdsp on RV64
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT2_C_MISC_ALU {
+    SRLI = 0,
+    SRAI,
+    ANDI,
+    ARITH,
+}
+}
+
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT2_C_ARITH {
+    SUB = 0,
+    XOR,
+    OR,
+    AND,
+}
+}
+
+// With bit 12 set. RV64
+enum_from_primitive! {
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+pub enum FUNCT2_C_ARITH_W {
+    SUBW = 0,
+    ADDW,
 }
 }
@@ -43,15 +43,34 @@ pub const ZIMM: Patch = Patch{ offset: 15, length: 5 };
 pub const OPCODE: Patch = Patch{ offset: 2, length: 5 };
 pub const RD: Patch = Patch{ offset: 7, length: 5 };
 pub const SHAMT: Patch = Patch{ offset: 20, length: 5 };
+pub const SHAMT6: Patch = Patch{ offset: 20, length: 6 }; // RV64
 pub const FUNCT3: Patch = Patch{ offset: 12, length: 3 };
 pub const RS1: Patch = Patch{ offset: 15, length: 5 };
 pub const RS2: Patch = Patch{ offset: 20, length: 5 };
 pub const FUNCT7: Patch = Patch{ offset: 25, length: 7 };
+pub const FUNCT6: Patch = Patch{ offset: 26, length: 6 }; // Above a 6-bit shamt
+pub const FUNCT5: Patch = Patch{ offset: 27, length: 5 };
+pub const AQRL: Patch = Patch{ offset: 25, length: 2 };
+pub const FMT: Patch = Patch{ offset: 25, length: 2 };
+pub const RS3: Patch = Patch{ offset: 27, length: 5 };
+pub const RM: Patch = Patch{ offset: 12, length: 3 };
 pub const FUNCT12: Patch = Patch{ offset: 20, length: 12 };
 pub const IMM12: Patch = Patch{ offset: 20, length: 12 };
 pub const SIMM12LO: Patch = Patch{ offset: 7, length: 5 };
 pub const SIMM12HI: Patch = Patch{ offset: 25, length: 7 };
 pub const IMM20: Patch = Patch{ offset: 12, length: 20 };
+pub const SUCC: Patch = Patch{ offset: 20, length: 4 };
+pub const PRED: Patch = Patch{ offset: 24, length: 4 };
+pub const FM: Patch = Patch{ offset: 28, length: 4 };
+
+// Compressed
+pub const C_OP: Patch = Patch{ offset: 0, length: 2 };
+pub const C_FUNCT3: Patch = Patch{ offset: 13, length: 3 };
+pub const C_BIT12: Patch = Patch{ offset: 12, length: 1 };
+pub const C_RD: Patch = Patch{ offset: 7, length: 5 };
+pub const C_RS2: Patch = Patch{ offset: 2, length: 5 };
+pub const C_FUNCT2: Patch = Patch{ offset: 10, length: 2 };
+pub const C_FUNCT2_ARITH: Patch = Patch{ offset: 5, length: 2 };
 
 #[cfg(test)]
 mod test {
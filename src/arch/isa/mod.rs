@@ -0,0 +1,223 @@
+// ISA strings, and which instructions they allow
+//
+// "rv32imac_zicsr" is XLEN, then single letter extensions, then multi-letter ones after
+// underscores. G is IMAFD with Zicsr and Zifencei, B is Zba, Zbb and Zbs.
+// The machine and the assembler are both held to one
+
+#[cfg(test)]
+mod test;
+
+use ::arch::inst::patch;
+use ::arch::inst::opcode::*;
+use ::arch::system::extension;
+
+use enum_primitive::FromPrimitive;
+
+#[derive(Debug, PartialEq)]
+pub enum IsaError {
+    Format,
+    UnknownExtension(String),
+    MissingDependency(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Isa {
+    pub xlen: u32,
+    // Single letters, as misa bits
+    pub extensions: u32,
+    pub zicsr: bool,
+    pub zifencei: bool,
+    pub zba: bool,
+    pub zbb: bool,
+    pub zbs: bool,
+}
+
+// Everything but bit manipulation, as before there was a choice
+impl Default for Isa {
+    fn default() -> Isa {
+        Isa::parse("rv32gc").unwrap()
+    }
+}
+
+const LETTERS: &'static str = "imafdc";
+
+impl Isa {
+    pub fn parse(s: &str) -> Result<Isa, IsaError> {
+        let s = s.to_lowercase();
+        let mut parts = s.split('_');
+        let first = parts.next().unwrap();
+
+        let xlen = if first.starts_with("rv32") {
+            32
+        } else if first.starts_with("rv64") {
+            64
+        } else {
+            return Err(IsaError::Format);
+        };
+
+        let mut isa = Isa {
+            xlen: xlen,
+            extensions: 0,
+            zicsr: false,
+            zifencei: false,
+            zba: false,
+            zbb: false,
+            zbs: false,
+        };
+
+        // The base comes first
+        let mut letters = first[4..].chars();
+        match letters.next() {
+            Some('i') => isa.extensions |= extension('I'),
+            Some('g') => isa.add("imafd_zicsr_zifencei")?,
+            Some(c) => return Err(IsaError::UnknownExtension(c.to_string())),
+            None => return Err(IsaError::Format),
+        }
+
+        for c in letters {
+            match c {
+                'b' => isa.add("zba_zbb_zbs")?,
+                c if LETTERS.contains(c) => isa.extensions |= extension(c.to_ascii_uppercase()),
+                c => return Err(IsaError::UnknownExtension(c.to_string())),
+            }
+        }
+
+        for name in parts {
+            isa.add(name)?;
+        }
+
+        if isa.has('D') && !isa.has('F') {
+            return Err(IsaError::MissingDependency(String::from("d needs f")));
+        }
+
+        Ok(isa)
+    }
+
+    // Letters, or multi-letter names separated by underscores
+    fn add(&mut self, names: &str) -> Result<(), IsaError> {
+        for name in names.split('_') {
+            match name {
+                "zicsr" => self.zicsr = true,
+                "zifencei" => self.zifencei = true,
+                "zba" => self.zba = true,
+                "zbb" => self.zbb = true,
+                "zbs" => self.zbs = true,
+                s if !s.is_empty() && s.chars().all(|c| LETTERS.contains(c)) => {
+                    for c in s.chars() {
+                        self.extensions |= extension(c.to_ascii_uppercase());
+                    }
+                }
+                s => return Err(IsaError::UnknownExtension(String::from(s))),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has(&self, letter: char) -> bool {
+        self.extensions & extension(letter) != 0
+    }
+
+    // Extension bits of misa. B only stands for all three of its parts
+    pub fn misa_extensions(&self) -> u32 {
+        let b = if self.zba && self.zbb && self.zbs { extension('B') } else { 0 };
+        self.extensions | b
+    }
+
+    // Whether the extensions include the instruction. 16-bit instructions are checked as what
+    // they expand to. Encodings nothing defines are left for the decoder to reject
+    pub fn accepts(&self, word: u32) -> bool {
+        if ::encode::is_compressed(word) {
+            return self.has('C') && match ::decode::expand(word & 0xFFFF, self.xlen) {
+                Some(expanded) => self.accepts(expanded),
+                None => false,
+            };
+        }
+
+        let opcode = match OPCODE::from_u32(patch::OPCODE.read(word)) {
+            Some(opcode) => opcode,
+            None => return true,
+        };
+        let funct3 = patch::FUNCT3.read(word);
+        let funct6 = patch::FUNCT6.read(word);
+        let funct7 = patch::FUNCT7.read(word);
+        let rv64 = self.xlen == 64;
+
+        match opcode {
+            OPCODE::LOAD => rv64 || (funct3 != FUNCT3_LOAD::LD as u32 && funct3 != FUNCT3_LOAD::LWU as u32),
+            OPCODE::STORE => rv64 || funct3 != FUNCT3_STORE::SD as u32,
+            OPCODE::LOAD_FP | OPCODE::STORE_FP => match FUNCT3_LOAD_FP::from_u32(funct3) {
+                Some(FUNCT3_LOAD_FP::FLW) => self.has('F'),
+                Some(FUNCT3_LOAD_FP::FLD) => self.has('D'),
+                None => true,
+            },
+
+            OPCODE::OP => match FUNCT7_OP::from_u32(funct7) {
+                Some(FUNCT7_OP::MULDIV) => self.has('M'),
+                Some(FUNCT7_OP::ALT) => match FUNCT3_OP_ALT::from_u32(funct3) {
+                    Some(FUNCT3_OP_ALT::SUB) | Some(FUNCT3_OP_ALT::SRA) => true,
+                    _ => self.zbb,
+                },
+                Some(FUNCT7_OP::ZEXT) | Some(FUNCT7_OP::MINMAX) | Some(FUNCT7_OP::ROT) => self.zbb,
+                Some(FUNCT7_OP::SHADD) => self.zba,
+                Some(FUNCT7_OP::BSET) | Some(FUNCT7_OP::BCLR) | Some(FUNCT7_OP::BINV) => self.zbs,
+                _ => true,
+            },
+            OPCODE::OP_32 => rv64 && match FUNCT7_OP::from_u32(funct7) {
+                Some(FUNCT7_OP::MULDIV) => self.has('M'),
+                Some(FUNCT7_OP::ZEXT) => if funct3 == FUNCT3_OP_ZEXT::ADD_UW as u32 { self.zba } else { self.zbb },
+                Some(FUNCT7_OP::SHADD) => self.zba,
+                Some(FUNCT7_OP::ROT) => self.zbb,
+                _ => true,
+            },
+            OPCODE::OP_IMM => match FUNCT3_OP_IMM::from_u32(funct3) {
+                Some(FUNCT3_OP_IMM::SLLI) => match FUNCT6_OP_IMM_SLL::from_u32(funct6) {
+                    Some(FUNCT6_OP_IMM_SLL::SLLI) | None => true,
+                    Some(FUNCT6_OP_IMM_SLL::UNARY) => self.zbb,
+                    _ => self.zbs,
+                },
+                Some(FUNCT3_OP_IMM::SRLI) => match FUNCT6_OP_IMM_SRL::from_u32(funct6) {
+                    Some(FUNCT6_OP_IMM_SRL::SRLI) | Some(FUNCT6_OP_IMM_SRL::SRAI) | None => true,
+                    Some(FUNCT6_OP_IMM_SRL::BEXTI) => self.zbs,
+                    _ => self.zbb,
+                },
+                _ => true,
+            },
+            OPCODE::OP_IMM_32 => rv64 && match (FUNCT3_OP_IMM_32::from_u32(funct3), funct7) {
+                // slli.uw has shamt[5] in the low bit
+                (Some(FUNCT3_OP_IMM_32::SLLIW), 0x04) | (Some(FUNCT3_OP_IMM_32::SLLIW), 0x05) => self.zba,
+                (Some(FUNCT3_OP_IMM_32::ADDIW), _) => true,
+                (_, f) => f != FUNCT7_OP::ROT as u32 || self.zbb,
+            },
+
+            OPCODE::AMO => self.has('A') && (rv64 || funct3 != FUNCT3_AMO::D as u32),
+            OPCODE::MADD | OPCODE::MSUB | OPCODE::NMSUB | OPCODE::NMADD => self.accepts_fmt(patch::FMT.read(word)),
+            OPCODE::OP_FP => {
+                let fmt = patch::FMT.read(word);
+                let rs2 = patch::RS2.read(word);
+                let funct5 = patch::FUNCT5.read(word);
+                // Conversions between S and D need both
+                let other_fmt = if funct5 == FUNCT5_OP_FP::FCVT_FMT as u32 { rs2 } else { fmt };
+                // The L and LU conversions, and moves of a double to and from a register, are RV64
+                let wide = match FUNCT5_OP_FP::from_u32(funct5) {
+                    Some(FUNCT5_OP_FP::FCVT_W) | Some(FUNCT5_OP_FP::FCVT_FROM_W) => rs2 >= RS2_FCVT_W::L as u32,
+                    Some(FUNCT5_OP_FP::FMV_X_CLASS) => fmt == FMT::D as u32 && funct3 == 0,
+                    Some(FUNCT5_OP_FP::FMV_FROM_X) => fmt == FMT::D as u32,
+                    _ => false,
+                };
+                self.accepts_fmt(fmt) && self.accepts_fmt(other_fmt) && (rv64 || !wide)
+            }
+
+            OPCODE::MISC_MEM => funct3 != FUNCT3_MISC_MEM::FENCE_I as u32 || self.zifencei,
+            OPCODE::SYSTEM => funct3 == FUNCT3_SYSTEM::PRIV as u32 || self.zicsr,
+            _ => true,
+        }
+    }
+
+    fn accepts_fmt(&self, fmt: u32) -> bool {
+        match FMT::from_u32(fmt) {
+            Some(FMT::S) => self.has('F'),
+            Some(FMT::D) => self.has('D'),
+            None => true,
+        }
+    }
+}
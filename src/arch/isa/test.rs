@@ -0,0 +1,20 @@
+use super::*;
+
+#[test]
+fn test_parse() {
+    let isa = Isa::parse("rv32imac_zicsr").unwrap();
+    assert_eq!(isa.xlen, 32);
+    assert_eq!(isa.extensions, "IMAC".chars().fold(0, |m, c| m | extension(c)));
+    assert!(isa.zicsr && !isa.zifencei);
+
+    let isa = Isa::parse("RV64GCB").unwrap();
+    assert_eq!(isa.xlen, 64);
+    assert!(isa.has('D') && isa.zifencei && isa.zbs);
+    assert_ne!(isa.misa_extensions() & extension('B'), 0);
+
+    assert_eq!(Isa::parse("rv32e"), Err(IsaError::UnknownExtension(String::from("e"))));
+    assert_eq!(Isa::parse("rv32imq"), Err(IsaError::UnknownExtension(String::from("q"))));
+    assert_eq!(Isa::parse("rv32i_zfoo"), Err(IsaError::UnknownExtension(String::from("zfoo"))));
+    assert_eq!(Isa::parse("rv128i"), Err(IsaError::Format));
+    assert!(Isa::parse("rv32id").is_err());
+}
@@ -1,2 +1,3 @@
 pub mod inst;
-pub mod system;
\ No newline at end of file
+pub mod system;
+pub mod isa;
@@ -48,6 +48,7 @@ pub enum CSR {
     SIDELEG = 0x103,
     SIE = 0x104,
     STVEC = 0x105,
+    SCOUNTEREN = 0x106,
 
     // Supervisor trap handling
     SSCRATCH = 0x140,
@@ -57,7 +58,7 @@ pub enum CSR {
     SIP = 0x144,
 
     // Supervisor protection and translation
-    SPTBR = 0x180,
+    SATP = 0x180,
 
 
     //// Machine level
@@ -75,6 +76,7 @@ pub enum CSR {
     MIDELEG = 0x303,
     MIE = 0x304,
     MTVEC = 0x305,
+    MCOUNTEREN = 0x306,
 
     // Machine trap handling
     MSCRATCH = 0x340,
@@ -83,13 +85,28 @@ pub enum CSR {
     MTVAL = 0x343,
     MIP = 0x344,
 
-    // Machine protection and translation
-    MBASE = 0x380,
-    MBOUND = 0x381,
-    MIBASE = 0x382,
-    MIBOUND = 0x383,
-    MDBASE = 0x384,
-    MDBOUND = 0x385,
+    // Machine memory protection
+    PMPCFG0 = 0x3A0,
+    PMPCFG1 = 0x3A1,
+    PMPCFG2 = 0x3A2,
+    PMPCFG3 = 0x3A3,
+
+    PMPADDR0 = 0x3B0,
+    PMPADDR1 = 0x3B1,
+    PMPADDR2 = 0x3B2,
+    PMPADDR3 = 0x3B3,
+    PMPADDR4 = 0x3B4,
+    PMPADDR5 = 0x3B5,
+    PMPADDR6 = 0x3B6,
+    PMPADDR7 = 0x3B7,
+    PMPADDR8 = 0x3B8,
+    PMPADDR9 = 0x3B9,
+    PMPADDR10 = 0x3BA,
+    PMPADDR11 = 0x3BB,
+    PMPADDR12 = 0x3BC,
+    PMPADDR13 = 0x3BD,
+    PMPADDR14 = 0x3BE,
+    PMPADDR15 = 0x3BF,
 
     // Machine counter/timers
     MCYCLE = 0xB00,
@@ -97,10 +114,6 @@ pub enum CSR {
     MCYCLEH = 0xB80,
     MINSTRETH = 0xB82,
 
-    // Machine counter setup
-    MUCOUNTEREN = 0x320,
-    MSCOUNTEREN = 0x321,
-
     // Debug/trace registers (shared with debug mode)
 
     // Debug mode registers
@@ -141,6 +154,7 @@ const CSRS: &'static [(&'static str, u32)] = &[
     ("sideleg", CSR::SIDELEG as u32),
     ("sie", CSR::SIE as u32),
     ("stvec", CSR::STVEC as u32),
+    ("scounteren", CSR::SCOUNTEREN as u32),
 
     // Supervisor trap handling
     ("sscratch", CSR::SSCRATCH as u32),
@@ -150,7 +164,7 @@ const CSRS: &'static [(&'static str, u32)] = &[
     ("sip", CSR::SIP as u32),
 
     // Supervisor protection and translation
-    ("sptbr", CSR::SPTBR as u32),
+    ("satp", CSR::SATP as u32),
 
 
     //// Machine level
@@ -168,6 +182,7 @@ const CSRS: &'static [(&'static str, u32)] = &[
     ("mideleg", CSR::MIDELEG as u32),
     ("mie", CSR::MIE as u32),
     ("mtvec", CSR::MTVEC as u32),
+    ("mcounteren", CSR::MCOUNTEREN as u32),
 
     // Machine trap handling
     ("mscratch", CSR::MSCRATCH as u32),
@@ -175,16 +190,48 @@ const CSRS: &'static [(&'static str, u32)] = &[
     ("mcause", CSR::MCAUSE as u32),
     ("mtval", CSR::MTVAL as u32),
     ("mip", CSR::MIP as u32),
+
+    // Machine memory protection
+    ("pmpcfg0", CSR::PMPCFG0 as u32),
+    ("pmpcfg1", CSR::PMPCFG1 as u32),
+    ("pmpcfg2", CSR::PMPCFG2 as u32),
+    ("pmpcfg3", CSR::PMPCFG3 as u32),
+
+    ("pmpaddr0", CSR::PMPADDR0 as u32),
+    ("pmpaddr1", CSR::PMPADDR1 as u32),
+    ("pmpaddr2", CSR::PMPADDR2 as u32),
+    ("pmpaddr3", CSR::PMPADDR3 as u32),
+    ("pmpaddr4", CSR::PMPADDR4 as u32),
+    ("pmpaddr5", CSR::PMPADDR5 as u32),
+    ("pmpaddr6", CSR::PMPADDR6 as u32),
+    ("pmpaddr7", CSR::PMPADDR7 as u32),
+    ("pmpaddr8", CSR::PMPADDR8 as u32),
+    ("pmpaddr9", CSR::PMPADDR9 as u32),
+    ("pmpaddr10", CSR::PMPADDR10 as u32),
+    ("pmpaddr11", CSR::PMPADDR11 as u32),
+    ("pmpaddr12", CSR::PMPADDR12 as u32),
+    ("pmpaddr13", CSR::PMPADDR13 as u32),
+    ("pmpaddr14", CSR::PMPADDR14 as u32),
+    ("pmpaddr15", CSR::PMPADDR15 as u32),
+
+    // Machine counter/timers
+    ("mcycle", CSR::MCYCLE as u32),
+    ("minstret", CSR::MINSTRET as u32),
+    ("mcycleh", CSR::MCYCLEH as u32),
+    ("minstreth", CSR::MINSTRETH as u32),
 ];
 
-pub fn csr_name(value: u32) -> Option<&'static str> {
+pub fn csr_name(value: u32) -> Option<String> {
     for &(name, v) in CSRS {
         if v == value {
-            return Some(name);
+            return Some(String::from(name));
         }
     }
 
-    None
+    hpm_csr(value).map(|(kind, n)| {
+        let &(prefix, suffix, _, _) = HPM_CSRS.iter().find(|&&(_, _, k, _)| k == kind).unwrap();
+        format!("{}{}{}", prefix, n, suffix)
+    })
 }
 
 pub fn csr_value(name: &str) -> Option<u32> {
@@ -194,9 +241,56 @@ pub fn csr_value(name: &str) -> Option<u32> {
         }
     }
 
+    for &(prefix, suffix, _, base) in HPM_CSRS {
+        if name.starts_with(prefix) && name.ends_with(suffix) && name.len() > prefix.len() + suffix.len() {
+            match name[prefix.len()..name.len() - suffix.len()].parse::<u32>() {
+                Ok(n) if n >= HPM_FIRST && n < HPM_FIRST + HPM_COUNT => return Some(base + n),
+                _ => (),
+            }
+        }
+    }
+
     None
 }
 
+// Hardware performance monitor. There are too many to name one by one, so these are numbered:
+// hpmcounter3 to 31 and their high halves, and the same for mhpmcounter and mhpmevent
+pub const HPM_FIRST: u32 = 3;
+pub const HPM_COUNT: u32 = 29;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HPM_CSR {
+    COUNTER, // User shadow
+    COUNTERH,
+    MCOUNTER,
+    MCOUNTERH,
+    MEVENT,
+}
+
+// Name prefix, suffix, and the CSR of counter 0
+const HPM_CSRS: &'static [(&'static str, &'static str, HPM_CSR, u32)] = &[
+    ("hpmcounter", "", HPM_CSR::COUNTER, 0xC00),
+    ("hpmcounter", "h", HPM_CSR::COUNTERH, 0xC80),
+    ("mhpmcounter", "", HPM_CSR::MCOUNTER, 0xB00),
+    ("mhpmcounter", "h", HPM_CSR::MCOUNTERH, 0xB80),
+    ("mhpmevent", "", HPM_CSR::MEVENT, 0x320),
+];
+
+// Which one, and the counter number
+pub fn hpm_csr(csr: u32) -> Option<(HPM_CSR, u32)> {
+    HPM_CSRS.iter()
+        .find(|&&(_, _, _, base)| csr >= base + HPM_FIRST && csr < base + HPM_FIRST + HPM_COUNT)
+        .map(|&(_, _, kind, base)| (kind, csr - base))
+}
+
+// mhpmevent values. Bus accesses are counted for one peripheral, numbered in order of attachment
+pub const HPM_EVENT_LOADS: u64 = 1; // Instructions that read memory
+pub const HPM_EVENT_STORES: u64 = 2; // Instructions that write memory
+pub const HPM_EVENT_TAKEN_BRANCHES: u64 = 3;
+pub const HPM_EVENT_TRAPS: u64 = 4; // Exceptions and interrupts
+pub const HPM_EVENT_STALLS: u64 = 5; // Cycles a timing model held the hart
+pub const HPM_EVENT_BUS_ACCESS: u64 = 0x100; // + peripheral
+
 // General value/encoding
 pub const USER: u8 = 0;
 pub const SUPERVISOR: u8 = 1;
@@ -207,9 +301,21 @@ pub const LEVELS: [u8; 3] = [0, 1, 3];
 
 // CSRs Offsets
 
-// misa
+// misa. MXL is at XLEN - 2. This is where it is in RV32
 pub const BASE: u8 = 30;
 
+pub const XLEN_32: u32 = 1;
+pub const XLEN_64: u32 = 2;
+
+// Extension bit, by letter
+pub fn extension(letter: char) -> u32 {
+    1 << (letter as u32 - 'A' as u32)
+}
+
+// mtvec mode
+pub const MTVEC_DIRECT: u32 = 0;
+pub const MTVEC_VECTORED: u32 = 1;
+
 // mstatus
 pub const IE_BASE: u8 = 0;
 
@@ -219,7 +325,19 @@ pub const SPP: u8 = 8;
 //pub const HPP: u8 = 9;
 pub const MPP: u8 = 11;
 
-pub const VM: u8 = 24;
+pub const MPRV: u8 = 17;
+pub const SUM: u8 = 18;
+pub const MXR: u8 = 19;
+
+// RV64 only. Both read as XLEN_64
+pub const UXL: u8 = 32;
+pub const SXL: u8 = 34;
+
+// sstatus is a view of these mstatus bits: UIE, SIE, UPIE, SPIE, SPP, SUM, MXR
+pub const SSTATUS_MASK: u32 = 0x000C0133;
+// The implemented mstatus bits: the sstatus ones, MIE, MPIE, MPP and MPRV
+pub const MSTATUS_MASK: u32 = 0x000E19BB;
+
 
 // mip
 pub const USIP: u8 = 0;
@@ -249,6 +367,12 @@ pub const UEIE: u8 = 8;
 pub const SEIE: u8 = 9;
 pub const MEIE: u8 = 11;
 
+// Writable bits
+pub const MIE_MASK: u32 = 0xBBB;
+pub const MIP_MASK: u32 = 0x333; // Machine level bits are read-only
+pub const MEDELEG_MASK: u32 = 0xB3FF; // Not ecall from M
+pub const MIDELEG_MASK: u32 = 0x333;
+
 // mcause
 pub const EXCEPTION_CODE: u8 = 0;
 pub const INTERRUPT: u8 = 31;
@@ -268,11 +392,31 @@ pub const LOAD_ACCESS_FAULT: u32 = 5;
 pub const STORE_ADDRESS_MISALIGNED: u32 = 6;
 pub const STORE_ACCESS_FAULT: u32 = 7;
 pub const ECALL_BASE: u32 = 8;
+pub const INSTRUCTION_PAGE_FAULT: u32 = 12;
+pub const LOAD_PAGE_FAULT: u32 = 13;
+pub const STORE_PAGE_FAULT: u32 = 15;
 
 
-// Virtualization management scheme
-pub const MBARE: u32 = 0;
-pub const MBB: u32 = 1;
-pub const MBBID: u32 = 2;
+// pmpcfg, a byte per entry
+pub const PMP_ENTRIES: usize = 16;
+
+pub const PMP_R: u8 = 1 << 0;
+pub const PMP_W: u8 = 1 << 1;
+pub const PMP_X: u8 = 1 << 2;
+pub const PMP_A: u8 = 3; // Offset of the 2-bit address matching mode
+pub const PMP_L: u8 = 1 << 7;
 
-pub const SV32: u32 = 8;
+// Address matching modes
+pub const PMP_OFF: u8 = 0;
+pub const PMP_TOR: u8 = 1;
+pub const PMP_NA4: u8 = 2;
+pub const PMP_NAPOT: u8 = 3;
+
+// satp
+pub const SATP_MODE: u8 = 31;
+pub const SATP_ASID: u8 = 22;
+pub const SATP_PPN_MASK: u32 = 0x003FFFFF;
+
+// Translation modes in satp
+pub const MBARE: u32 = 0;
+pub const SV32: u32 = 1;
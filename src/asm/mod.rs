@@ -77,6 +77,7 @@ use self::scoped_handler::*;
 use self::scoper::*;
 
 use ::arch::inst;
+use ::arch::isa::Isa;
 
 #[derive(Debug)]
 pub struct AsmError {
@@ -111,6 +112,7 @@ pub enum AsmProcessError {
     IO(std::io::Error),
     FromInclude(Box<AsmError>),
     CalcError(String),
+    NotInIsa(String),
     Unknown,
 }
 
@@ -143,7 +145,13 @@ impl Write for NullWriter {
 }
 
 // file_path better be openable.
-pub fn assemble<R: Read, W: Write, F>(mut writer: W, input_file: &str, get_reader_func: F) -> Result<(), AsmError>
+pub fn assemble<R: Read, W: Write, F>(writer: W, input_file: &str, get_reader_func: F) -> Result<(), AsmError>
+    where F: Fn(&str) -> Result<R, std::io::Error> {
+    assemble_with_isa(writer, input_file, get_reader_func, None)
+}
+
+// Instructions outside isa are errors. Without one anything goes
+pub fn assemble_with_isa<R: Read, W: Write, F>(mut writer: W, input_file: &str, get_reader_func: F, isa: Option<Isa>) -> Result<(), AsmError>
     where F: Fn(&str) -> Result<R, std::io::Error> {
 
     // Prepare symtab
@@ -161,7 +169,7 @@ pub fn assemble<R: Read, W: Write, F>(mut writer: W, input_file: &str, get_reade
         println!("File opened: {}", input_file);
 
         let mut null_writer = NullWriter;
-        let mut scoped_handler = Phase::new(&mut null_writer);
+        let mut scoped_handler = Phase::new(&mut null_writer, isa);
 
         let mut walk_handler = Scoper::new(&mut symtab, &mut scoped_handler);
 
@@ -186,7 +194,7 @@ pub fn assemble<R: Read, W: Write, F>(mut writer: W, input_file: &str, get_reade
 
         println!("File opened: {}", input_file);
 
-        let mut scoped_handler = Phase::new(&mut writer);
+        let mut scoped_handler = Phase::new(&mut writer, isa);
 
         let mut walk_handler = Scoper::new(&mut symtab, &mut scoped_handler);
 
@@ -206,14 +214,18 @@ pub fn assemble<R: Read, W: Write, F>(mut writer: W, input_file: &str, get_reade
 
 // wrapper for assemble with memory as IO
 pub fn assemble_mem(input: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_mem_with_isa(input, None)
+}
+
+pub fn assemble_mem_with_isa(input: &str, isa: Option<Isa>) -> Result<Vec<u8>, AsmError> {
     use ::lexer::mem_reader;
 
     let mut writer: Vec<u8> = Vec::new();
 
-    assemble(&mut writer, "memory", |file_path| { match file_path {
+    assemble_with_isa(&mut writer, "memory", |file_path| { match file_path {
         "memory" => Ok(Box::new(mem_reader::MemReader::new(input.as_bytes()))),
         _ => panic!("assemble_mem with include {}", file_path),
-    } })?;
+    } }, isa)?;
 
     Ok(writer)
 }
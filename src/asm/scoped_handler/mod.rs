@@ -4,22 +4,33 @@ mod test;
 
 use super::*;
 use ::arch::inst;
+use ::arch::isa::Isa;
 use ::parser::*;
 use ::translate::symtab::Symtab;
 
 use ::translate::*;
+use ::encode;
 
 use std::io::prelude::*;
 
 
 pub struct Phase<'a> {
     writer: &'a mut Write,
+    // Auto-compress instructions, set by (.option rvc)
+    rvc: bool,
+    // Which 16-bit forms exist, set by (.option rv64)
+    xlen: u32,
+    // Instructions outside it are errors. XLEN is fixed by it
+    isa: Option<Isa>,
 }
 
 impl<'a> Phase<'a> {
-    pub fn new(writer: &'a mut Write) -> Phase<'a> {
+    pub fn new(writer: &'a mut Write, isa: Option<Isa>) -> Phase<'a> {
         Phase {
             writer: writer,
+            rvc: false,
+            xlen: isa.map_or(32, |isa| isa.xlen),
+            isa: isa,
         }
     }
 }
@@ -75,20 +86,49 @@ impl<'a> ScopedHandler for Phase<'a> {
             let id = args[0].get_identifier().ok_or(AsmProcessError::DirectiveFormat)?;
             let v = args[1].get_identifier().ok_or(AsmProcessError::DirectiveFormat)?;
 
-            let reg = ::arch::inst::register::index(v).ok_or(AsmProcessError::DirectiveFormat)?;
+            let value = if let Some(reg) = ::arch::inst::register::index(v) {
+                Value::Register(reg)
+            } else if let Some(reg) = ::arch::inst::register::float_index(v) {
+                Value::FloatRegister(reg)
+            } else {
+                return Err(AsmProcessError::DirectiveFormat);
+            };
 
             let sym = Symbol {
                 ext: false,
                 mutable: false,
-                value: Value::Register(reg),
+                value: value,
             };
             symtab.insert(id, sym).map_err(|e| AsmProcessError::Symtab(e))?;
 
             return Ok(0);
         }
 
+        if op == ".option" {
+            let args = get_args(expr);
+            if args.len() != 1 {
+                return Err(AsmProcessError::DirectiveFormat);
+            }
+
+            match args[0].get_identifier() {
+                Some("rvc") => self.rvc = true,
+                Some("norvc") => self.rvc = false,
+                Some("rv32") => self.xlen = 32,
+                Some("rv64") => self.xlen = 64,
+                _ => return Err(AsmProcessError::DirectiveFormat),
+            }
+
+            if let Some(isa) = self.isa {
+                if isa.xlen != self.xlen {
+                    return Err(AsmProcessError::NotInIsa(format!("rv{}", self.xlen)));
+                }
+            }
+
+            return Ok(0);
+        }
+
         // Other unhandled directives...
-        if !inst::is_inst(op) {
+        if !inst::is_inst(op) && !inst::compressed::is_compressed_inst(op) {
 //            return Ok(increment(expr).unwrap());
             return Err(AsmProcessError::UnknownDirective(String::from(op)));
         }
@@ -106,16 +146,38 @@ impl<'a> ScopedHandler for Phase<'a> {
             AsmProcessError::Translate(s)
         })?;
 
+        // RV32 shifts by 0 to 31 only. The encodings with shamt[5] are reserved
+        if self.xlen == 32 && is_rv64_shift(code) {
+            return Err(AsmProcessError::NotInIsa(String::from(op)));
+        }
+
+        // Compress only what can't change size between the phases, so that labels stay put.
+        // Never for an ISA without C
+        let rvc = self.rvc && self.isa.map_or(true, |isa| isa.has('C'));
+        let code = if rvc && !encode::is_compressed(code) && is_literal_args(expr, symtab) {
+            encode::compress(code, self.xlen).unwrap_or(code)
+        } else {
+            code
+        };
+
+        if let Some(isa) = self.isa {
+            if !isa.accepts(code) {
+                return Err(AsmProcessError::NotInIsa(String::from(op)));
+            }
+        }
+
+        let size = if encode::is_compressed(code) { 2 } else { 4 };
+
         // write
         let buf = u32_to_array(code);
-        let written = self.writer.write(&buf).map_err(|e| AsmProcessError::IO(e))?;
-        if written != 4 {
+        let written = self.writer.write(&buf[..size]).map_err(|e| AsmProcessError::IO(e))?;
+        if written != size {
             return Err(AsmProcessError::Unknown);
         }
 
         // wrote
 
-        Ok(4)
+        Ok(size as u32)
     }
 
     fn org(&mut self, counter: u32) -> Result<(), AsmProcessError> {
@@ -137,6 +199,26 @@ fn u32_to_array(mut x: u32) -> [u8; 4] {
     a
 }
 
+// Immediate shifts and rotates, single bit operations included, with shamt[5] set
+fn is_rv64_shift(code: u32) -> bool {
+    let funct3 = inst::patch::FUNCT3.read(code);
+    inst::patch::OPCODE.read(code) == inst::opcode::OPCODE::OP_IMM as u32
+        && (funct3 == inst::opcode::FUNCT3_OP_IMM::SLLI as u32 || funct3 == inst::opcode::FUNCT3_OP_IMM::SRLI as u32)
+        && inst::arg::SHAMT6.read(code) >= 32
+}
+
+// Registers and integer literals only
+fn is_literal_args(expr: &Expr, symtab: &Symtab) -> bool {
+    get_args(expr).iter().all(|e| match e {
+        &Expr::Integer { .. } => true,
+        &Expr::Identifier { value: ref s, .. } => match symtab.get(s).map(|sym| &sym.value) {
+            Some(&Value::Register(_)) | Some(&Value::FloatRegister(_)) => true,
+            _ => false,
+        },
+        _ => false,
+    })
+}
+
 fn get_args<'a>(expr: &'a Expr) -> &'a [Expr] {
     if let &Expr::List { exprs: ref exprs, .. } = expr {
         if exprs.is_empty() {
@@ -4,4 +4,15 @@ use super::*;
 #[test]
 fn test_u32_to_array() {
     assert_eq!(u32_to_array(0x12345678), [0x78u8, 0x56, 0x34, 0x12]);
-}
\ No newline at end of file
+}
+#[test]
+fn test_rv32_shift_amount() {
+    for s in &["(slli a0 a0 32)", "(srli a0 a0 40)", "(srai a0 a0 63)", "(bseti a0 a0 32)"] {
+        match ::asm::assemble_mem(s) {
+            Err(::asm::AsmError { error: AsmProcessError::NotInIsa(_), .. }) => (),
+            other => panic!("{}: {:?}", s, other),
+        }
+    }
+    assert!(::asm::assemble_mem("(slli a0 a0 31)").is_ok());
+    assert!(::asm::assemble_mem("(.option rv64)\n(srai a0 a0 63)").is_ok());
+}
@@ -20,13 +20,15 @@ use std::str::FromStr;
 use ::riscvvm::asm;
 
 use riscvvm::machine::*;
-use riscvvm::machine::memory::Memory;
+use riscvvm::machine::memory::{Memory, Rom};
 use riscvvm::machine::peri::*;
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
 const NUM_TICKS_DEFAULT: u32 = 100;
+const MEMORY_SIZE_DEFAULT: u32 = 0x10000;
+const BOOT_ROM_START_DEFAULT: u32 = 0x80000;
 
 // Usage: riscvvm <options> <bin file>
 // Options
@@ -42,7 +44,11 @@ fn main() {
     let continuous_tick_limit: u32;
     let dies_on_exception: bool;
     let memory_probe: Option<u32>;
+    let memory_size: u32;
+    let memory_start: u32;
+    let boot_rom: Option<String>;
     let vasm_file: bool;
+    let mut config = Config::default();
     // Handle arguments
     {
         let args: Vec<String> = env::args().collect();
@@ -52,6 +58,14 @@ fn main() {
         opts.optopt("t", "continuous_tick_limit", "default 100", "0 means no limit");
         opts.optflag("d", "die_on_exception", "Machine stops when exception is encountered");
         opts.optopt("p", "memory_probe", "address", "set to print memory write to the address");
+        opts.optopt("s", "memory_size", "bytes", "RAM size, default 65536");
+        opts.optopt("", "memory_start", "address", "where RAM is, default 0");
+        opts.optopt("r", "reset_vector", "address", "where execution starts, default 0, or the boot ROM");
+        opts.optopt("b", "boot_rom", "file", "read-only code at the reset vector, run before the program");
+        opts.optopt("m", "mtvec", "value", "initial mtvec, default 16");
+        opts.optflag("", "misaligned_access", "emulate misaligned loads and stores instead of trapping");
+        opts.optflag("", "icache", "cache fetched instructions until fence.i");
+        opts.optopt("i", "isa", "ISA string", "extensions the machine implements, default rv32gc");
         opts.optflag("a", "vasm", "Input file is vasm file so assemble first");
         opts.optflag("h", "help", "print this help message");
 
@@ -85,34 +99,85 @@ fn main() {
             None => None,
         };
 
+        memory_size = match matches.opt_str("memory_size") {
+            Some(s) => match u32::from_str(&s) {
+                Ok(v) => v,
+                Err(e) => panic!(e),
+            },
+            None => MEMORY_SIZE_DEFAULT,
+        };
+
+        memory_start = match matches.opt_str("memory_start") {
+            Some(s) => match u32::from_str(&s) {
+                Ok(v) => v,
+                Err(e) => panic!(e),
+            },
+            None => 0,
+        };
+
+        config.misaligned_access = matches.opt_present("misaligned_access");
+        config.icache = matches.opt_present("icache");
+
+        if let Some(s) = matches.opt_str("isa") {
+            config.isa = match Isa::parse(&s) {
+                Ok(v) => v,
+                Err(e) => panic!("Bad ISA string {}: {:?}", s, e),
+            };
+        }
+
+        // The program is in RAM from 0, so the boot ROM goes elsewhere unless told
+        boot_rom = matches.opt_str("boot_rom");
+        if boot_rom.is_some() {
+            config.reset_vector = BOOT_ROM_START_DEFAULT;
+        }
+
+        if let Some(s) = matches.opt_str("reset_vector") {
+            config.reset_vector = match u32::from_str(&s) {
+                Ok(v) => v,
+                Err(e) => panic!(e),
+            };
+        }
+
+        if let Some(s) = matches.opt_str("mtvec") {
+            config.mtvec = match u32::from_str(&s) {
+                Ok(v) => v,
+                Err(e) => panic!(e),
+            };
+        }
+
         input = match matches.free.get(0) {
             Some(input) => input.clone(),
             None => print_usage(&program, opts),
         };
     }
 
+    // ELF executables place themselves. Anything else is an image for the start of RAM
     let mut data = Vec::<u8>::new();
+    let mut elf = None;
     if vasm_file {
         println!("VASM file: {}", &input);
 
-        asm::assemble(&mut data, &input, get_reader).expect("Failed to assemble");
+        asm::assemble_with_isa(&mut data, &input, get_reader, Some(config.isa)).expect("Failed to assemble");
     } else {
         println!("Bin file: {}", &input);
 
         let mut input_file = File::open(&input).expect("Failed to open Input file");
         input_file.read_to_end(&mut data).expect("Failed to read input file");
+
+        if riscvvm::elf::is_elf(&data) {
+            elf = Some(Elf::parse(&data).expect("Bad ELF file"));
+            data.clear();
+        }
     }
 
     // Prepare machine. Load file into memory. Start ticking
 
     // Preferred bus configuration
-    const MEMORY_START: u32 = 0;
-    const MEMORY_WIDTH: u8 = 16;
     const OUTPUT_START: u32 = 0x100000;
     const INPUT_START: u32 = 0x100004;
 
     // Memory
-    let mut memory = Memory::new(memory_probe);
+    let mut memory = Memory::new(memory_size, memory_probe);
     memory.load(&data);
 
     let output_device = OutputDevice::new(std::io::stdout());
@@ -120,11 +185,23 @@ fn main() {
     let input_device = InputDevice::new(std::io::stdin());
 
     // Machine and peripherals
-    let mut m = Machine::new();
+    let mut m = Machine::with_config(config);
+
+    m.attach("memory", memory, memory_start, memory_size).expect("Memory doesn't fit below the devices");
+    if let Some(path) = boot_rom {
+        println!("Boot ROM: {}", &path);
+        let rom = Rom::from_file(&path, true).expect("Failed to read boot ROM");
+        m.attach_boot_rom(rom).expect("Boot ROM overlaps");
+    }
+    m.attach("output", output_device, OUTPUT_START, 4).unwrap();
+    m.attach("input", input_device, INPUT_START, 4).unwrap();
 
-    m.attach("memory", memory, MEMORY_START, MEMORY_WIDTH);
-    m.attach("output", output_device, OUTPUT_START, 0);
-    m.attach("input", input_device, INPUT_START, 0);
+    if let Some(ref elf) = elf {
+        if let Err(addr) = m.load_elf(elf) {
+            panic!("Nothing to load the ELF segment into at {:08X}", addr);
+        }
+        println!("Entry: {:08X}", elf.entry);
+    }
 
     println!("Simulation starting");
 
@@ -134,6 +211,17 @@ fn main() {
         Ok(cycles) => (),
         Err(e) => {
             println!("RunError: {:?}", e);
+
+            // Where, by the ELF symbols
+            let pc = match e {
+                RunError::CyclesLimitExceeded(pc) | RunError::Exception(pc) => Some(pc as u32),
+                RunError::Terminated => None,
+            };
+            if let (Some(pc), Some(elf)) = (pc, elf.as_ref()) {
+                if let Some((name, offset)) = elf.symbol_at(pc) {
+                    println!("pc {:08X} is {}+0x{:X}", pc, name, offset);
+                }
+            }
         }
     }
 
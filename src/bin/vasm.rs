@@ -22,6 +22,7 @@ fn main() {
 
     let mut opts = Options::new();
     opts.optopt("o", "out", "output bin file", "NAME");
+    opts.optopt("i", "isa", "reject instructions outside the ISA, e.g. rv32imac_zicsr", "ISA");
     opts.optflag("h", "help", "print this help menu");
 
     let mut matches = match opts.parse(&args[1..]) {
@@ -41,6 +42,11 @@ fn main() {
 
     let input =  matches.free.remove(0);
 
+    let isa = matches.opt_str("isa").map(|s| match riscvvm::machine::Isa::parse(&s) {
+        Ok(isa) => isa,
+        Err(e) => panic!("Bad ISA string {}: {:?}", s, e),
+    });
+
     let output = matches.opt_str("o").unwrap_or({
         let mut path = PathBuf::from(&input);
         if !path.set_extension("bin") {
@@ -57,7 +63,7 @@ fn main() {
 
     println!("Start assembling");
 
-    riscvvm::asm::assemble(Box::new(writer), &input, get_reader).expect("Assembler returned error");
+    riscvvm::asm::assemble_with_isa(Box::new(writer), &input, get_reader, isa).expect("Assembler returned error");
 
     println!("Done! I guess");
 }
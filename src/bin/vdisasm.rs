@@ -11,7 +11,7 @@ use std::fs::File;
 
 /*
 USAGE
-vdisasm -o outfile infile
+vdisasm [--rv64] -o outfile infile
 */
 
 fn main() {
@@ -22,6 +22,7 @@ fn main() {
 
     let mut opts = Options::new();
     opts.optopt("o", "out", "output bin file", "NAME");
+    opts.optflag("", "rv64", "decode 16-bit instructions as RV64");
     opts.optflag("h", "help", "print this help menu");
 
     let mut matches = match opts.parse(&args[1..]) {
@@ -69,7 +70,9 @@ fn main() {
 
     println!("Start disassembling");
 
-    riscvvm::disasm::disassemble(writer, reader).expect("Disassembler returned error");
+    let xlen = if matches.opt_present("rv64") { 64 } else { 32 };
+
+    riscvvm::disasm::disassemble(writer, reader, xlen).expect("Disassembler returned error");
 
     println!("Done! I am pretty sure");
 }
@@ -2,6 +2,9 @@
 mod test;
 
 use ::arch::inst;
+use ::arch::inst::compressed;
+use ::arch::inst::compressed::Expand;
+use ::encode;
 
 use ::encode::patch::*;
 
@@ -28,3 +31,42 @@ pub fn decode(x: u32) -> Option<(&'static str, &'static inst::Inst, Vec<u32>)> {
     None
 }
 
+
+// 16-bit instructions. Same as decode, but some encodings depend on XLEN
+pub fn decode_compressed(x: u32, xlen: u32) -> Option<(&'static str, &'static inst::Inst, Vec<u32>)> {
+    decode_cinst(x, xlen).map(|(name, cinst, args)| (name, &cinst.inst, args))
+}
+
+fn decode_cinst(x: u32, xlen: u32) -> Option<(&'static str, &'static compressed::CInst, Vec<u32>)> {
+    'inst: for &(name, ref cinst) in compressed::CINSTS {
+        if !cinst.is_valid_in(xlen) {
+            continue 'inst;
+        }
+        for opcode in cinst.inst.opcodes {
+            let v = read(x, opcode.patch.offset, opcode.patch.length);
+            if opcode.value != v {
+                continue 'inst;
+            }
+        }
+
+        let args: Vec<u32> = cinst.inst.args.iter().map(|&arg| arg.read(x)).collect();
+        if cinst.nonzero.iter().any(|&i| args[i] == 0) {
+            continue 'inst;
+        }
+        return Some((name, cinst, args))
+    }
+    None
+}
+
+// The 32-bit instruction a 16-bit instruction stands for
+pub fn expand(x: u32, xlen: u32) -> Option<u32> {
+    let (_, cinst, args) = decode_cinst(x, xlen)?;
+
+    let expanded = inst::inst(cinst.expansion).expect("expansion");
+    let expanded_args: Vec<u32> = cinst.expansion_args.iter().map(|e| match e {
+        &Expand::Src(i) => args[i],
+        &Expand::Fixed(v) => v,
+    }).collect();
+
+    Some(encode::encode(expanded, &expanded_args))
+}
@@ -5,4 +5,28 @@ use super::*;
 #[test]
 fn test_decode() {
 
-}
\ No newline at end of file
+}
+#[test]
+fn test_decode_compressed() {
+    let (name, _, args) = decode_compressed(0x8082, 32).unwrap();
+    assert_eq!(name, "c.jr");
+    assert_eq!(args, vec![1]);
+
+    let (name, _, args) = decode_compressed(0x41C8, 32).unwrap();
+    assert_eq!(name, "c.lw");
+    assert_eq!(args, vec![10, 11, 4]);
+
+    // Reserved
+    assert!(decode_compressed(0x0000, 32).is_none());
+    assert!(decode_compressed(0x8002, 32).is_none());
+
+    // c.lw a0 a1 4 is lw a0 a1 4
+    assert_eq!(expand(0x41C8, 32), Some(0x0045A503));
+    // c.nop
+    assert_eq!(expand(0x0001, 32), Some(0x00000013));
+
+    // c.flw in RV32 is c.ld in RV64
+    assert_eq!(decode_compressed(0x6188, 32).unwrap().0, "c.flw");
+    assert_eq!(decode_compressed(0x6188, 64).unwrap().0, "c.ld");
+    assert_eq!(expand(0x6188, 64), Some(0x0005B503));
+}
@@ -23,44 +23,67 @@ listing
 
 */
 
-// I hope reader and writers are buffered, because I don't
-pub fn disassemble<W: Write, R: Read>(mut writer: W, mut reader: R) -> Result<(), DisasmError> {
+// I hope reader and writers are buffered, because I don't. xlen picks the meaning of 16-bit instructions
+pub fn disassemble<W: Write, R: Read>(mut writer: W, mut reader: R, xlen: u32) -> Result<(), DisasmError> {
     // Let's decode and detranslate!
 
-    let mut counter: u32 = 0;
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|e| DisasmError::Read(e))?;
 
-    let mut buf = [0u8; 4];
+    let mut counter: usize = 0;
 
-    // Read until EOF
-    while reader.read(&mut buf).map_err(|e| DisasmError::Read(e))? == 4 {
-        let x = array_to_u32(&buf);
+    // Until EOF. 16-bit instructions are told apart by their low bits
+    while counter + 2 <= bytes.len() {
+        let low = bytes[counter] as u32 | (bytes[counter + 1] as u32) << 8;
 
-        write!(&mut writer, "{:08X} : {:08X} - {:08b} {:08b} {:08b} {:08b}    ; ", counter, x, buf[3], buf[2], buf[1], buf[0]);
+        if ::encode::is_compressed(low) {
+            write!(&mut writer, "{:08X} : {:04X}     - {:08b} {:08b}                      ; ", counter, low, bytes[counter + 1], bytes[counter]).map_err(|e| DisasmError::Write(e))?;
 
-        match decode(x) {
-            Some((name, inst, args)) => {
-                // format and write
-                write!(&mut writer, "({}", name);
-                for i in 0..inst.args.len() {
-                    let arg = inst.args[i];
-                    let a = args[i];
+            write_inst(&mut writer, decode_compressed(low, xlen), low).map_err(|e| DisasmError::Write(e))?;
 
-                    write!(&mut writer, " {}", inst::format_arg(arg, a));
-                }
-                writeln!(&mut writer, ")");
+            counter += 2;
+        } else {
+            if counter + 4 > bytes.len() {
+                break;
             }
-            None => {
-                // Just print its value in hexadecimal
-                writeln!(&mut writer, "0x{:08X}", x);
-            }
-        };
 
-        counter += 4;
+            let mut buf = [0u8; 4];
+            buf.copy_from_slice(&bytes[counter..counter + 4]);
+            let x = array_to_u32(&buf);
+
+            write!(&mut writer, "{:08X} : {:08X} - {:08b} {:08b} {:08b} {:08b}    ; ", counter, x, buf[3], buf[2], buf[1], buf[0]).map_err(|e| DisasmError::Write(e))?;
+
+            write_inst(&mut writer, decode(x), x).map_err(|e| DisasmError::Write(e))?;
+
+            counter += 4;
+        }
     }
 
     Ok(())
 }
 
+fn write_inst<W: Write>(writer: &mut W, decoded: Option<(&'static str, &'static inst::Inst, Vec<u32>)>, x: u32) -> Result<(), std::io::Error> {
+    match decoded {
+        Some((name, inst, args)) => {
+            // format and write
+            write!(writer, "({}", name)?;
+            for i in 0..inst.args.len() {
+                let arg = inst.args[i];
+                let a = args[i];
+
+                write!(writer, " {}", inst::format_arg(arg, a))?;
+            }
+            writeln!(writer, ")")?;
+        }
+        None => {
+            // Just print its value in hexadecimal
+            writeln!(writer, "0x{:08X}", x)?;
+        }
+    };
+
+    Ok(())
+}
+
 fn array_to_u32(buf: &[u8; 4]) -> u32 {
     let mut x = 0u32;
     x |= buf[3] as u32;
@@ -0,0 +1,177 @@
+// ELF32 RISC-V executables. Only what it takes to run one: loadable segments, the entry point,
+// and the symbol table to name addresses in diagnostics
+
+#[cfg(test)]
+mod test;
+
+const MAGIC: &'static [u8] = b"\x7FELF";
+const ELFCLASS32: u8 = 1;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_RISCV: u16 = 243;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+
+const EHDR_SIZE: usize = 52;
+const PHDR_SIZE: usize = 32;
+const SHDR_SIZE: usize = 40;
+const SYM_SIZE: usize = 16;
+
+// Symbol types worth a name
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum ElfError {
+    NotElf,
+    NotRiscv32, // 64-bit, big-endian or another machine
+    NotExecutable,
+    Format, // Something points outside the file
+}
+
+pub struct Elf {
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+    pub symbols: Vec<Symbol>, // Sorted by value
+}
+
+// data goes at paddr. The rest up to mem_size is zero (.bss)
+pub struct Segment {
+    pub paddr: u32,
+    pub data: Vec<u8>,
+    pub mem_size: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: u32,
+    pub size: u32,
+}
+
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+impl Elf {
+    pub fn parse(bytes: &[u8]) -> Result<Elf, ElfError> {
+        if !is_elf(bytes) {
+            return Err(ElfError::NotElf);
+        }
+        if bytes.len() < EHDR_SIZE {
+            return Err(ElfError::Format);
+        }
+        if bytes[4] != ELFCLASS32 || bytes[5] != ELFDATA2LSB || u16_at(bytes, 18)? != EM_RISCV {
+            return Err(ElfError::NotRiscv32);
+        }
+        if u16_at(bytes, 16)? != ET_EXEC {
+            return Err(ElfError::NotExecutable);
+        }
+
+        let entry = u32_at(bytes, 24)?;
+        let phoff = u32_at(bytes, 28)? as usize;
+        let shoff = u32_at(bytes, 32)? as usize;
+        let phnum = u16_at(bytes, 44)? as usize;
+        let shnum = u16_at(bytes, 48)? as usize;
+
+        let mut segments = Vec::new();
+        for i in 0..phnum {
+            let ph = phoff + i * PHDR_SIZE;
+            if u32_at(bytes, ph)? != PT_LOAD {
+                continue;
+            }
+
+            let offset = u32_at(bytes, ph + 4)? as usize;
+            let paddr = u32_at(bytes, ph + 12)?;
+            let file_size = u32_at(bytes, ph + 16)? as usize;
+            let mem_size = u32_at(bytes, ph + 20)?;
+            if (mem_size as usize) < file_size {
+                return Err(ElfError::Format);
+            }
+
+            segments.push(Segment {
+                paddr: paddr,
+                data: Vec::from(slice(bytes, offset, file_size)?),
+                mem_size: mem_size,
+            });
+        }
+
+        let mut symbols = Vec::new();
+        for i in 0..shnum {
+            let sh = shoff + i * SHDR_SIZE;
+            if u32_at(bytes, sh + 4)? != SHT_SYMTAB {
+                continue;
+            }
+
+            let syms = slice(bytes, u32_at(bytes, sh + 16)? as usize, u32_at(bytes, sh + 20)? as usize)?;
+
+            // Names are in the string table the symbol table links to
+            let strtab_sh = shoff + u32_at(bytes, sh + 24)? as usize * SHDR_SIZE;
+            let strtab = slice(bytes, u32_at(bytes, strtab_sh + 16)? as usize, u32_at(bytes, strtab_sh + 20)? as usize)?;
+
+            for sym in syms.chunks(SYM_SIZE).filter(|s| s.len() == SYM_SIZE) {
+                let name = string_at(strtab, u32_at(sym, 0)? as usize)?;
+                let kind = sym[12] & 0xF;
+                let defined = u16_at(sym, 14)? != 0;
+                if name.is_empty() || !defined || (kind != STT_NOTYPE && kind != STT_OBJECT && kind != STT_FUNC) {
+                    continue;
+                }
+
+                symbols.push(Symbol {
+                    name: name,
+                    value: u32_at(sym, 4)?,
+                    size: u32_at(sym, 8)?,
+                });
+            }
+        }
+        symbols.sort_by_key(|s| s.value);
+
+        Ok(Elf {
+            entry: entry,
+            segments: segments,
+            symbols: symbols,
+        })
+    }
+
+    // The symbol addr is in, and how far into it. Symbols without a size reach the next one
+    pub fn symbol_at(&self, addr: u32) -> Option<(&str, u32)> {
+        let i = match self.symbols.binary_search_by_key(&addr, |s| s.value) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+
+        let symbol = &self.symbols[i];
+        let offset = addr - symbol.value;
+        if symbol.size != 0 && offset >= symbol.size {
+            return None;
+        }
+        Some((&symbol.name, offset))
+    }
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], ElfError> {
+    match offset.checked_add(len) {
+        Some(end) if end <= bytes.len() => Ok(&bytes[offset..end]),
+        _ => Err(ElfError::Format),
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, ElfError> {
+    let b = slice(bytes, offset, 2)?;
+    Ok(b[0] as u16 | (b[1] as u16) << 8)
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, ElfError> {
+    let b = slice(bytes, offset, 4)?;
+    Ok(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+}
+
+// NUL-terminated
+fn string_at(bytes: &[u8], offset: usize) -> Result<String, ElfError> {
+    let rest = slice(bytes, offset, bytes.len().saturating_sub(offset))?;
+    let len = rest.iter().position(|&b| b == 0).ok_or(ElfError::Format)?;
+    String::from_utf8(Vec::from(&rest[..len])).map_err(|_| ElfError::Format)
+}
@@ -0,0 +1,94 @@
+use super::*;
+
+// An executable with one segment of code followed by bss zeros, and the symbols
+fn executable(entry: u32, paddr: u32, code: &[u8], bss: u32, symbols: &[(&str, u32, u32)]) -> Vec<u8> {
+    fn push16(v: &mut Vec<u8>, x: u16) {
+        v.extend_from_slice(&[x as u8, (x >> 8) as u8]);
+    }
+    fn push32(v: &mut Vec<u8>, x: u32) {
+        push16(v, x as u16);
+        push16(v, (x >> 16) as u16);
+    }
+
+    let mut strtab = vec![0u8];
+    let mut symtab = vec![0u8; SYM_SIZE]; // The null symbol
+    for &(name, value, size) in symbols {
+        push32(&mut symtab, strtab.len() as u32);
+        push32(&mut symtab, value);
+        push32(&mut symtab, size);
+        symtab.extend_from_slice(&[STT_FUNC, 0]);
+        push16(&mut symtab, 1);
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+
+    let phoff = EHDR_SIZE;
+    let code_offset = phoff + PHDR_SIZE;
+    let symtab_offset = code_offset + code.len();
+    let strtab_offset = symtab_offset + symtab.len();
+    let shoff = strtab_offset + strtab.len();
+
+    let mut v = Vec::new();
+    v.extend_from_slice(MAGIC);
+    v.extend_from_slice(&[ELFCLASS32, ELFDATA2LSB, 1]);
+    v.resize(16, 0);
+    push16(&mut v, ET_EXEC);
+    push16(&mut v, EM_RISCV);
+    push32(&mut v, 1);
+    push32(&mut v, entry);
+    push32(&mut v, phoff as u32);
+    push32(&mut v, shoff as u32);
+    push32(&mut v, 0);
+    for &x in &[EHDR_SIZE, PHDR_SIZE, 1, SHDR_SIZE, 3, 0] {
+        push16(&mut v, x as u16);
+    }
+
+    for &x in &[PT_LOAD, code_offset as u32, paddr, paddr, code.len() as u32, code.len() as u32 + bss, 7, 4] {
+        push32(&mut v, x);
+    }
+    v.extend_from_slice(code);
+    v.extend_from_slice(&symtab);
+    v.extend_from_slice(&strtab);
+
+    // Null, the symbol table and its strings
+    v.resize(v.len() + SHDR_SIZE, 0);
+    for &x in &[0, SHT_SYMTAB, 0, 0, symtab_offset as u32, symtab.len() as u32, 2, 1, 4, SYM_SIZE as u32] {
+        push32(&mut v, x);
+    }
+    for &x in &[0, 3, 0, 0, strtab_offset as u32, strtab.len() as u32, 0, 0, 1, 0] {
+        push32(&mut v, x);
+    }
+
+    v
+}
+
+#[test]
+fn test_parse() {
+    let bytes = executable(0x80000004, 0x80000000, &[1, 2, 3, 4, 5, 6, 7, 8], 8,
+                           &[("_start", 0x80000004, 4), ("main", 0x80000000, 0), ("buf", 0x80000008, 8)]);
+    let elf = Elf::parse(&bytes).unwrap();
+
+    assert_eq!(elf.entry, 0x80000004);
+    assert_eq!(elf.segments.len(), 1);
+    assert_eq!(elf.segments[0].paddr, 0x80000000);
+    assert_eq!(&elf.segments[0].data[..], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(elf.segments[0].mem_size, 16);
+
+    assert_eq!(elf.symbols.len(), 3);
+    assert_eq!(elf.symbol_at(0x80000002), Some(("main", 2)), "no size reaches the next");
+    assert_eq!(elf.symbol_at(0x80000006), Some(("_start", 2)));
+    assert_eq!(elf.symbol_at(0x8000000F), Some(("buf", 7)));
+    assert_eq!(elf.symbol_at(0x80000010), None, "past buf");
+    assert_eq!(elf.symbol_at(0x7FFFFFFC), None, "before all");
+
+    assert_eq!(Elf::parse(b"\x7FELF").err(), Some(ElfError::Format));
+    assert_eq!(Elf::parse(&bytes[..200]).err(), Some(ElfError::Format));
+    assert_eq!(Elf::parse(b"not an elf").err(), Some(ElfError::NotElf));
+
+    let mut wide = bytes.clone();
+    wide[4] = 2;
+    assert_eq!(Elf::parse(&wide).err(), Some(ElfError::NotRiscv32));
+    let mut object = bytes.clone();
+    object[16] = 1;
+    assert_eq!(Elf::parse(&object).err(), Some(ElfError::NotExecutable));
+}
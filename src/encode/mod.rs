@@ -5,14 +5,17 @@ pub mod patch;
 // RISC-V Encoder
 
 use ::arch::inst::*;
+use ::arch::inst::compressed;
+use ::arch::inst::compressed::Expand;
+use ::decode;
 
 pub fn encode(inst: &Inst, args: &[u32]) -> u32 {
     let mut code = 0x00000003 as u32; // base
 
 //    println!("encode");
-    // opcodes
+    // opcodes. Compressed instructions overwrite the base with their quadrant
     for o in inst.opcodes.iter() {
-        code = patch::write(code, o.patch.offset, o.patch.length, o.value);
+        code = o.patch.write(code, o.value);
     }
 //    println!("encode: {:08X}", code);
 
@@ -29,3 +32,35 @@ pub fn encode(inst: &Inst, args: &[u32]) -> u32 {
 //    println!("encode result: {:08X}", code);
     code
 }
+
+// The 16-bit form of a 32-bit instruction, if there is one
+pub fn compress(x: u32, xlen: u32) -> Option<u32> {
+    'inst: for &(_, ref cinst) in compressed::CINSTS {
+        let expanded = inst(cinst.expansion).expect("expansion");
+        for o in expanded.opcodes {
+            if o.patch.read(x) != o.value {
+                continue 'inst;
+            }
+        }
+
+        // Take each arg from where it lands in the expansion
+        let expanded_args: Vec<u32> = expanded.args.iter().map(|&arg| arg.read(x)).collect();
+        let mut args = vec![0u32; cinst.inst.args.len()];
+        for (e, &v) in cinst.expansion_args.iter().zip(&expanded_args) {
+            if let &Expand::Src(i) = e {
+                args[i] = v;
+            }
+        }
+
+        // Args out of range or misaligned, fixed args that differ and reserved encodings don't survive the trip back
+        let code = encode(&cinst.inst, &args);
+        if decode::expand(code, xlen) == Some(x) {
+            return Some(code);
+        }
+    }
+    None
+}
+
+pub fn is_compressed(x: u32) -> bool {
+    x & 0x3 != 0x3
+}
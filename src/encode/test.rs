@@ -1,7 +1,37 @@
 
 #![cfg(test)]
 
+use super::*;
+
 #[test]
 fn test_encode() {
 
-}
\ No newline at end of file
+}
+#[test]
+fn test_compress() {
+    // addi a0 a0 1
+    assert_eq!(compress(0x00150513, 32), Some(0x0505));
+    // addi a0 a0 100 is out of range
+    assert_eq!(compress(0x06450513, 32), None);
+    // addi a0 a1 1 has different registers
+    assert_eq!(compress(0x00158513, 32), None);
+    // lw a0 a1 2 is misaligned for c.lw
+    assert_eq!(compress(0x0025A503, 32), None);
+
+    // ld only compresses in RV64
+    assert_eq!(compress(0x0005B503, 32), None);
+    assert_eq!(compress(0x0005B503, 64), Some(0x6188));
+
+    // Every 16-bit instruction compresses back from its expansion
+    for &xlen in &[32, 64] {
+        for x in 0..0x10000u32 {
+            if !is_compressed(x) {
+                continue;
+            }
+            if let Some(expanded) = decode::expand(x, xlen) {
+                let c = compress(expanded, xlen).expect("compress");
+                assert_eq!(decode::expand(c, xlen), Some(expanded), "{:04X}", x);
+            }
+        }
+    }
+}
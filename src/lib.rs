@@ -13,6 +13,7 @@ pub mod asm;
 pub mod disasm;
 mod encode;
 mod decode;
+pub mod elf;
 pub mod machine;
 //pub mod vpc;
 mod calc;
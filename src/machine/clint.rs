@@ -0,0 +1,103 @@
+use super::*;
+
+// Core-local interruptor. Register offsets follow the usual SiFive layout
+pub const MSIP_OFFSET: u32 = 0x0000;
+pub const MTIMECMP_OFFSET: u32 = 0x4000;
+pub const MTIMECMP_HI_OFFSET: u32 = 0x4004;
+pub const MTIME_OFFSET: u32 = 0xBFF8;
+pub const MTIME_HI_OFFSET: u32 = 0xBFFC;
+
+// Attach with this size
+pub const CLINT_SIZE: u32 = 0x10000;
+
+// Timer and software interrupts of the hart. mtime advances once every divider ticks
+pub struct Clint {
+    pub mtime: u64,
+    pub mtimecmp: u64,
+    pub msip: bool,
+    divider: u32,
+    count: u32,
+}
+
+impl Clint {
+    pub fn new(divider: u32) -> Clint {
+        assert!(divider > 0);
+
+        Clint {
+            mtime: 0,
+            mtimecmp: ::std::u64::MAX, // No timer interrupt until software sets it
+            msip: false,
+            divider: divider,
+            count: 0,
+        }
+    }
+
+    fn register(&self, addr: u32) -> u32 {
+        match addr {
+            MSIP_OFFSET => self.msip as u32,
+            MTIMECMP_OFFSET => self.mtimecmp as u32,
+            MTIMECMP_HI_OFFSET => (self.mtimecmp >> 32) as u32,
+            MTIME_OFFSET => self.mtime as u32,
+            MTIME_HI_OFFSET => (self.mtime >> 32) as u32,
+            _ => 0,
+        }
+    }
+}
+
+fn write_low(x: u64, v: u32) -> u64 {
+    (x & !0xFFFFFFFF) | v as u64
+}
+
+fn write_high(x: u64, v: u32) -> u64 {
+    (x & 0xFFFFFFFF) | (v as u64) << 32
+}
+
+impl BusEnd for Clint {
+    fn read_word(&mut self, addr: u32) -> u32 {
+        self.register(addr)
+    }
+
+    fn write_word(&mut self, addr: u32, value: u32) {
+        match addr {
+            MSIP_OFFSET => self.msip = value & 0x1 == 1,
+            MTIMECMP_OFFSET => self.mtimecmp = write_low(self.mtimecmp, value),
+            MTIMECMP_HI_OFFSET => self.mtimecmp = write_high(self.mtimecmp, value),
+            MTIME_OFFSET => self.mtime = write_low(self.mtime, value),
+            MTIME_HI_OFFSET => self.mtime = write_high(self.mtime, value),
+            _ => (),
+        }
+    }
+
+    // Byte enables over the register
+    fn write(&mut self, addr: u32, size: u32, value: u32) {
+        let word = self.register(addr & !0x3);
+        self.write_word(addr & !0x3, sized_write(word, addr, size, value));
+    }
+
+    // Not an external interrupt
+    fn is_interrupting(&self) -> bool {
+        false
+    }
+}
+
+impl Peri for Clint {
+    fn tick(&mut self) {
+        self.count += 1;
+        if self.count == self.divider {
+            self.count = 0;
+            self.mtime = self.mtime.wrapping_add(1);
+        }
+    }
+
+    fn timer_interrupt(&self) -> bool {
+        self.mtime >= self.mtimecmp
+    }
+
+    fn software_interrupt(&self) -> bool {
+        self.msip
+    }
+
+    fn time(&self) -> Option<u64> {
+        Some(self.mtime)
+    }
+}
@@ -0,0 +1,382 @@
+// F and D arithmetic on the host's f64
+// The error of each host result is used to apply the rounding modes and raise the flags
+
+#[cfg(test)]
+mod test;
+
+// fflags
+pub const NX: u32 = 0x01;
+pub const UF: u32 = 0x02;
+pub const OF: u32 = 0x04;
+pub const DZ: u32 = 0x08;
+pub const NV: u32 = 0x10;
+
+// Rounding modes
+pub const RNE: u32 = 0;
+pub const RTZ: u32 = 1;
+pub const RDN: u32 = 2;
+pub const RUP: u32 = 3;
+pub const RMM: u32 = 4;
+pub const DYN: u32 = 7;
+
+use ::arch::inst::opcode::FMT;
+
+const CANONICAL_NAN_S: u32 = 0x7FC00000;
+const CANONICAL_NAN_D: u64 = 0x7FF8000000000000;
+
+// Upper half of a boxed single
+const BOX: u64 = 0xFFFFFFFF00000000;
+
+// Register contents
+
+// A single that is not properly NaN-boxed reads as the canonical NaN
+pub fn unbox_bits(fmt: FMT, x: u64) -> u64 {
+    match fmt {
+        FMT::S => if x & BOX == BOX { x & 0xFFFFFFFF } else { CANONICAL_NAN_S as u64 },
+        FMT::D => x,
+    }
+}
+
+pub fn unbox(fmt: FMT, x: u64) -> f64 {
+    let bits = unbox_bits(fmt, x);
+    match fmt {
+        FMT::S => f32::from_bits(bits as u32) as f64,
+        FMT::D => f64::from_bits(bits),
+    }
+}
+
+pub fn is_snan(fmt: FMT, x: u64) -> bool {
+    let bits = unbox_bits(fmt, x);
+    match fmt {
+        FMT::S => bits & 0x7F800000 == 0x7F800000 && bits & 0x007FFFFF != 0 && bits & 0x00400000 == 0,
+        FMT::D => bits & 0x7FF0000000000000 == 0x7FF0000000000000 && bits & 0x000FFFFFFFFFFFFF != 0 && bits & 0x0008000000000000 == 0,
+    }
+}
+
+pub fn box_bits(fmt: FMT, bits: u64) -> u64 {
+    match fmt {
+        FMT::S => BOX | (bits & 0xFFFFFFFF),
+        FMT::D => bits,
+    }
+}
+
+// v must be representable in fmt
+pub fn box_value(fmt: FMT, v: f64) -> u64 {
+    match fmt {
+        FMT::S => box_bits(fmt, if v.is_nan() { CANONICAL_NAN_S } else { (v as f32).to_bits() } as u64),
+        FMT::D => if v.is_nan() { CANONICAL_NAN_D } else { v.to_bits() },
+    }
+}
+
+pub fn sign_bit(fmt: FMT) -> u64 {
+    match fmt {
+        FMT::S => 1u64 << 31,
+        FMT::D => 1u64 << 63,
+    }
+}
+
+// Format properties, in f64
+
+fn max_value(fmt: FMT) -> f64 {
+    match fmt {
+        FMT::S => ::std::f32::MAX as f64,
+        FMT::D => ::std::f64::MAX,
+    }
+}
+
+fn min_positive(fmt: FMT) -> f64 {
+    match fmt {
+        FMT::S => ::std::f32::MIN_POSITIVE as f64,
+        FMT::D => ::std::f64::MIN_POSITIVE,
+    }
+}
+
+fn round_nearest_even(fmt: FMT, x: f64) -> f64 {
+    match fmt {
+        FMT::S => x as f32 as f64,
+        FMT::D => x,
+    }
+}
+
+fn next_up(fmt: FMT, x: f64) -> f64 {
+    match fmt {
+        FMT::S => (x as f32).next_up() as f64,
+        FMT::D => x.next_up(),
+    }
+}
+
+fn next_down(fmt: FMT, x: f64) -> f64 {
+    match fmt {
+        FMT::S => (x as f32).next_down() as f64,
+        FMT::D => x.next_down(),
+    }
+}
+
+fn overflow(fmt: FMT, negative: bool, rm: u32) -> f64 {
+    let max = max_value(fmt);
+    let inf = ::std::f64::INFINITY;
+    let magnitude = match rm {
+        RTZ => max,
+        RDN => if negative { inf } else { max },
+        RUP => if negative { max } else { inf },
+        _ => inf,
+    };
+    if negative { -magnitude } else { magnitude }
+}
+
+// Round hi + lo to fmt. hi is the host's f64 result and lo its error.
+// When the error is not representable (div, sqrt), lo only has to have the right sign
+pub fn round(fmt: FMT, hi: f64, lo: f64, rm: u32) -> (f64, u32) {
+    let r = round_nearest_even(fmt, hi);
+
+    if r.is_infinite() {
+        return (overflow(fmt, hi < 0.0, rm), OF | NX);
+    }
+
+    // Distance from the rounded value to hi. Always exact
+    let d = hi - r;
+
+    if d == 0.0 && lo == 0.0 {
+        return (r, 0);
+    }
+
+    // Which side of r the exact value lies
+    let above = if d != 0.0 { d > 0.0 } else { lo > 0.0 };
+
+    let (down, up) = if above { (r, next_up(fmt, r)) } else { (next_down(fmt, r), r) };
+    let other = if above { up } else { down };
+
+    let half = (up - down) / 2.0;
+
+    // Exact value is exactly half way, or beyond half way to the other candidate
+    let (tie, beyond_half) = if d != 0.0 {
+        (d.abs() == half && lo == 0.0, d.abs() == half && lo != 0.0 && (lo > 0.0) == (d > 0.0))
+    } else {
+        (lo.abs() == half, lo.abs() > half)
+    };
+
+    let v = match rm {
+        RTZ => if down.abs() < up.abs() { down } else { up },
+        RDN => down,
+        RUP => up,
+        RMM => {
+            if beyond_half {
+                other
+            } else if tie {
+                if down.abs() > up.abs() { down } else { up }
+            } else {
+                r
+            }
+        }
+        RNE => if beyond_half { other } else { r },
+        _ => panic!("reserved rounding mode"),
+    };
+
+    let mut flags = NX;
+
+    if v.is_infinite() {
+        flags |= OF;
+    } else if v.abs() < min_positive(fmt) {
+        flags |= UF;
+    }
+
+    (v, flags)
+}
+
+// Exact a + b as hi + lo
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    let e = (a - (s - bb)) + (b - bb);
+    (s, e)
+}
+
+// Stands in for an error that is only known by its sign
+fn tiny() -> f64 {
+    f64::from_bits(1)
+}
+
+// Sign of an exact zero sum of x and y
+fn zero_sum(x: f64, y: f64, rm: u32) -> f64 {
+    if x == 0.0 && y == 0.0 && x.is_sign_negative() == y.is_sign_negative() {
+        x
+    } else if rm == RDN {
+        -0.0
+    } else {
+        0.0
+    }
+}
+
+// Arithmetic. Inputs are not NaN
+
+pub fn add(fmt: FMT, a: f64, b: f64, rm: u32) -> (f64, u32) {
+    if a.is_infinite() || b.is_infinite() {
+        let v = a + b;
+        return (v, if v.is_nan() { NV } else { 0 });
+    }
+
+    let (hi, lo) = two_sum(a, b);
+    if hi == 0.0 && lo == 0.0 {
+        return (zero_sum(a, b, rm), 0);
+    }
+    round(fmt, hi, lo, rm)
+}
+
+pub fn mul(fmt: FMT, a: f64, b: f64, rm: u32) -> (f64, u32) {
+    if a.is_infinite() || b.is_infinite() {
+        let v = a * b;
+        return (v, if v.is_nan() { NV } else { 0 });
+    }
+
+    // Products of singles are exact in f64. For doubles, fma gives the error
+    let hi = a * b;
+    let lo = a.mul_add(b, -hi);
+    round(fmt, hi, lo, rm)
+}
+
+pub fn div(fmt: FMT, a: f64, b: f64, rm: u32) -> (f64, u32) {
+    if b == 0.0 {
+        if a == 0.0 {
+            return (::std::f64::NAN, NV);
+        }
+        return (a / b, if a.is_infinite() { 0 } else { DZ });
+    }
+    if a.is_infinite() || b.is_infinite() {
+        let v = a / b;
+        return (v, if v.is_nan() { NV } else { 0 });
+    }
+
+    let q = a / b;
+    if q.is_infinite() {
+        return round(fmt, q, 0.0, rm);
+    }
+    // a - q*b is exact. Its sign relative to b's tells which side the true quotient is
+    let r = (-q).mul_add(b, a);
+    let lo = if r == 0.0 { 0.0 } else if (r > 0.0) == (b > 0.0) { tiny() } else { -tiny() };
+    round(fmt, q, lo, rm)
+}
+
+pub fn sqrt(fmt: FMT, a: f64, rm: u32) -> (f64, u32) {
+    if a == 0.0 || a.is_infinite() && a > 0.0 {
+        return (a, 0);
+    }
+    if a < 0.0 {
+        return (::std::f64::NAN, NV);
+    }
+
+    let s = a.sqrt();
+    let r = (-s).mul_add(s, a);
+    let lo = if r == 0.0 { 0.0 } else if r > 0.0 { tiny() } else { -tiny() };
+    round(fmt, s, lo, rm)
+}
+
+// a * b + c with a single rounding
+pub fn fma(fmt: FMT, a: f64, b: f64, c: f64, rm: u32) -> (f64, u32) {
+    if a.is_infinite() || b.is_infinite() || c.is_infinite() {
+        let v = a.mul_add(b, c);
+        return (v, if v.is_nan() { NV } else { 0 });
+    }
+
+    let hi = a.mul_add(b, c);
+
+    // Error of hi. Exact for singles, where the product is exact. Sign only for doubles
+    let p = a * b;
+    let pe = a.mul_add(b, -p);
+    let (s, se) = two_sum(p, c);
+    let lo = ((s - hi) + se) + pe;
+
+    if hi == 0.0 && lo == 0.0 {
+        return (zero_sum(p, c, rm), 0);
+    }
+    round(fmt, hi, lo, rm)
+}
+
+// fmin/fmax. -0 is less than +0. A single NaN input gives the other input
+pub fn min_max(a: f64, b: f64, max: bool) -> f64 {
+    if a.is_nan() {
+        return b;
+    }
+    if b.is_nan() {
+        return a;
+    }
+    if a == b {
+        // Only matters for zeros
+        return if max == a.is_sign_negative() { b } else { a };
+    }
+    if (a < b) != max { a } else { b }
+}
+
+// Conversions
+
+// Round to an integral value in the rounding mode
+pub fn round_to_integral(x: f64, rm: u32) -> f64 {
+    match rm {
+        RTZ => x.trunc(),
+        RDN => x.floor(),
+        RUP => x.ceil(),
+        RMM => x.round(),
+        _ => {
+            let f = x.floor();
+            let diff = x - f;
+            if diff < 0.5 {
+                f
+            } else if diff > 0.5 {
+                f + 1.0
+            } else if f % 2.0 == 0.0 {
+                f
+            } else {
+                f + 1.0
+            }
+        }
+    }
+}
+
+// fcvt.w, fcvt.wu, fcvt.l and fcvt.lu. Out of range and NaN inputs saturate and raise NV.
+// The result is in the low bits bits
+pub fn to_int(x: f64, rm: u32, signed: bool, bits: u32) -> (u64, u32) {
+    // Bounds are powers of two, so they are exact
+    let (min, limit) = if signed {
+        (-(2f64.powi(bits as i32 - 1)), 2f64.powi(bits as i32 - 1))
+    } else {
+        (0.0, 2f64.powi(bits as i32))
+    };
+    let mask = if bits == 64 { !0 } else { (1u64 << bits) - 1 };
+    let max = if signed { mask >> 1 } else { mask };
+    let min_value = if signed { (max + 1) & mask } else { 0 };
+
+    if x.is_nan() {
+        return (max, NV);
+    }
+
+    let r = round_to_integral(x, rm);
+    if r < min {
+        return (min_value, NV);
+    }
+    if r >= limit {
+        return (max, NV);
+    }
+
+    let v = if signed { r as i64 as u64 } else { r as u64 };
+    (v & mask, if r != x { NX } else { 0 })
+}
+
+// fclass result bits
+pub fn classify(fmt: FMT, x: u64) -> u32 {
+    let bits = unbox_bits(fmt, x);
+    let v = unbox(fmt, x);
+    let negative = bits & sign_bit(fmt) != 0;
+
+    let i = if v.is_nan() {
+        if is_snan(fmt, x) { 8 } else { 9 }
+    } else if v.is_infinite() {
+        if negative { 0 } else { 7 }
+    } else if v == 0.0 {
+        if negative { 3 } else { 4 }
+    } else if v.abs() < min_positive(fmt) {
+        if negative { 2 } else { 5 }
+    } else {
+        if negative { 1 } else { 6 }
+    };
+
+    1 << i
+}
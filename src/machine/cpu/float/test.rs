@@ -0,0 +1,134 @@
+use super::*;
+
+fn s(v: f64) -> f32 {
+    v as f32
+}
+
+#[test]
+fn test_round_single() {
+    let tiny = (2.0f64).powi(-30);
+
+    // 1 + 2^-30 is not representable in single
+    assert_eq!(add(FMT::S, 1.0, tiny, RNE), (1.0, NX));
+    assert_eq!(add(FMT::S, 1.0, tiny, RTZ), (1.0, NX));
+    assert_eq!(add(FMT::S, 1.0, tiny, RDN), (1.0, NX));
+    assert_eq!(add(FMT::S, 1.0, tiny, RUP), ((1.0f32).next_up() as f64, NX));
+    assert_eq!(add(FMT::S, -1.0, -tiny, RDN), ((-1.0f32).next_down() as f64, NX));
+    assert_eq!(add(FMT::S, -1.0, -tiny, RTZ), (-1.0, NX));
+
+    // Exactly half way between 1 and the next single: ties to even, or away with RMM
+    let half_ulp = (2.0f64).powi(-24);
+    assert_eq!(add(FMT::S, 1.0, half_ulp, RNE), (1.0, NX));
+    assert_eq!(add(FMT::S, 1.0, half_ulp, RMM), ((1.0f32).next_up() as f64, NX));
+
+    // Just beyond half way, where the f64 sum alone would look like a tie
+    let (v, _) = add(FMT::S, 1.0 + half_ulp, (2.0f64).powi(-80), RNE);
+    assert_eq!(v, (1.0f32).next_up() as f64);
+
+    // Exact
+    assert_eq!(add(FMT::S, 1.5, 2.25, RUP), (3.75, 0));
+}
+
+#[test]
+fn test_zero_sign() {
+    let (v, _) = add(FMT::D, 1.0, -1.0, RNE);
+    assert!(v == 0.0 && v.is_sign_positive());
+    let (v, _) = add(FMT::D, 1.0, -1.0, RDN);
+    assert!(v == 0.0 && v.is_sign_negative());
+    let (v, _) = add(FMT::D, -0.0, -0.0, RNE);
+    assert!(v == 0.0 && v.is_sign_negative());
+}
+
+#[test]
+fn test_div_sqrt() {
+    let third_down = div(FMT::S, 1.0, 3.0, RDN).0;
+    let third_up = div(FMT::S, 1.0, 3.0, RUP).0;
+    assert_eq!(s(third_down).next_up(), s(third_up));
+    assert_eq!(div(FMT::S, 1.0, 3.0, RNE), ((1.0f32 / 3.0) as f64, NX));
+
+    let d_down = div(FMT::D, 1.0, 3.0, RDN).0;
+    let d_up = div(FMT::D, 1.0, 3.0, RUP).0;
+    assert_eq!(d_down.next_up(), d_up);
+
+    assert_eq!(div(FMT::D, 1.0, 0.0, RNE), (::std::f64::INFINITY, DZ));
+    assert_eq!(div(FMT::D, 0.0, 0.0, RNE).1, NV);
+
+    assert_eq!(sqrt(FMT::S, 4.0, RNE), (2.0, 0));
+    assert_eq!(sqrt(FMT::D, -1.0, RNE).1, NV);
+    let sqrt2_down = sqrt(FMT::D, 2.0, RTZ).0;
+    let sqrt2_up = sqrt(FMT::D, 2.0, RUP).0;
+    assert_eq!(sqrt2_down.next_up(), sqrt2_up);
+}
+
+#[test]
+fn test_overflow() {
+    let max = ::std::f32::MAX as f64;
+    assert_eq!(mul(FMT::S, max, 2.0, RNE), (::std::f64::INFINITY, OF | NX));
+    assert_eq!(mul(FMT::S, max, 2.0, RTZ), (max, OF | NX));
+    assert_eq!(mul(FMT::S, -max, 2.0, RUP), (-max, OF | NX));
+    assert_eq!(mul(FMT::D, ::std::f64::MAX, 2.0, RDN), (::std::f64::MAX, OF | NX));
+}
+
+#[test]
+fn test_fma() {
+    // Fused: the product is not rounded before the add
+    let a = 1.0 + (2.0f64).powi(-12);
+    let (v, flags) = fma(FMT::S, a, a, -1.0, RNE);
+    assert_eq!(v, (2.0f64).powi(-11) + (2.0f64).powi(-24));
+    assert_eq!(flags, 0);
+
+    assert_eq!(fma(FMT::D, ::std::f64::INFINITY, 0.0, 1.0, RNE).1, NV);
+}
+
+#[test]
+fn test_to_int() {
+    assert_eq!(to_int(2.5, RNE, true, 32), (2, NX));
+    assert_eq!(to_int(3.5, RNE, true, 32), (4, NX));
+    assert_eq!(to_int(-2.5, RMM, true, 32), (-3i32 as u32 as u64, NX));
+    assert_eq!(to_int(-2.5, RTZ, true, 32), (-2i32 as u32 as u64, NX));
+    assert_eq!(to_int(7.0, RNE, true, 32), (7, 0));
+
+    assert_eq!(to_int(1e10, RNE, true, 32), (::std::i32::MAX as u64, NV));
+    assert_eq!(to_int(-1e10, RNE, true, 32), (::std::i32::MIN as u32 as u64, NV));
+    assert_eq!(to_int(::std::f64::NAN, RNE, true, 32), (::std::i32::MAX as u64, NV));
+
+    assert_eq!(to_int(-1.0, RNE, false, 32), (0, NV));
+    assert_eq!(to_int(-0.25, RTZ, false, 32), (0, NX));
+    assert_eq!(to_int(4294967295.0, RNE, false, 32), (::std::u32::MAX as u64, 0));
+
+    // 64-bit
+    assert_eq!(to_int(-2.5, RMM, true, 64), (-3i64 as u64, NX));
+    assert_eq!(to_int(1e10, RNE, true, 64), (10000000000, 0));
+    assert_eq!(to_int(9.3e18, RNE, true, 64), (::std::i64::MAX as u64, NV));
+    assert_eq!(to_int(-1e19, RNE, true, 64), (::std::i64::MIN as u64, NV));
+    assert_eq!(to_int(1.8e19, RNE, false, 64), (18000000000000000000, 0));
+    assert_eq!(to_int(1.9e19, RNE, false, 64), (::std::u64::MAX, NV));
+}
+
+#[test]
+fn test_min_max() {
+    assert_eq!(min_max(1.0, 2.0, false), 1.0);
+    assert_eq!(min_max(1.0, 2.0, true), 2.0);
+    assert_eq!(min_max(::std::f64::NAN, 2.0, false), 2.0);
+    assert!(min_max(0.0, -0.0, false).is_sign_negative());
+    assert!(min_max(-0.0, 0.0, true).is_sign_positive());
+}
+
+#[test]
+fn test_boxing() {
+    let one = box_value(FMT::S, 1.0);
+    assert_eq!(one, 0xFFFFFFFF3F800000);
+    assert_eq!(unbox(FMT::S, one), 1.0);
+
+    // Not boxed
+    assert!(unbox(FMT::S, 0x3F800000).is_nan());
+    assert_eq!(classify(FMT::S, 0x3F800000), 1 << 9);
+
+    assert_eq!(box_value(FMT::D, ::std::f64::NAN), 0x7FF8000000000000);
+    assert!(is_snan(FMT::S, 0xFFFFFFFF7F800001));
+    assert!(!is_snan(FMT::S, 0xFFFFFFFF7FC00000));
+
+    assert_eq!(classify(FMT::D, (-0.0f64).to_bits()), 1 << 3);
+    assert_eq!(classify(FMT::D, (1e-310f64).to_bits()), 1 << 5);
+    assert_eq!(classify(FMT::S, box_value(FMT::S, -1.0)), 1 << 1);
+}
@@ -0,0 +1,48 @@
+// Instruction fetch cache, by physical address. Flushed by fence.i
+
+#[derive(Clone, Copy, Debug)]
+struct Line {
+    paddr: u32,
+    word: u32,
+}
+
+// Direct mapped by word address
+#[derive(Default)]
+pub struct ICache {
+    lines: Vec<Option<Line>>,
+}
+
+impl ICache {
+    pub fn with_lines(n: usize) -> ICache {
+        ICache { lines: vec![None; n] }
+    }
+
+    fn index(&self, paddr: u32) -> usize {
+        (paddr >> 2) as usize % self.lines.len()
+    }
+
+    pub fn lookup(&self, paddr: u32) -> Option<u32> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        match self.lines[self.index(paddr)] {
+            Some(line) if line.paddr == paddr => Some(line.word),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, paddr: u32, word: u32) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let i = self.index(paddr);
+        self.lines[i] = Some(Line { paddr: paddr, word: word });
+    }
+
+    // fence.i
+    pub fn flush(&mut self) {
+        for line in self.lines.iter_mut() {
+            *line = None;
+        }
+    }
+}
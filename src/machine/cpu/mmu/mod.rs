@@ -0,0 +1,235 @@
+// Sv32 address translation, with a small TLB flushed by sfence.vma
+
+#[cfg(test)]
+mod test;
+
+use super::*;
+use super::pmp;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+    Fetch,
+    Load,
+    Store, // Also AMOs
+}
+
+impl Access {
+    pub fn page_fault(self) -> u32 {
+        match self {
+            Access::Fetch => INSTRUCTION_PAGE_FAULT,
+            Access::Load => LOAD_PAGE_FAULT,
+            Access::Store => STORE_PAGE_FAULT,
+        }
+    }
+
+    pub fn access_fault(self) -> u32 {
+        match self {
+            Access::Fetch => INSTRUCTION_ACCESS_FAULT,
+            Access::Load => LOAD_ACCESS_FAULT,
+            Access::Store => STORE_ACCESS_FAULT,
+        }
+    }
+}
+
+// PTE bits
+pub const PTE_V: u32 = 1 << 0;
+pub const PTE_R: u32 = 1 << 1;
+pub const PTE_W: u32 = 1 << 2;
+pub const PTE_X: u32 = 1 << 3;
+pub const PTE_U: u32 = 1 << 4;
+pub const PTE_A: u32 = 1 << 6;
+pub const PTE_D: u32 = 1 << 7;
+
+const PAGE_SHIFT: u32 = 12;
+const PPN_SHIFT: u32 = 10;
+const VPN_BITS: u32 = 10;
+
+const TLB_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Default, Debug)]
+struct TlbEntry {
+    valid: bool,
+    vpn: u32,
+    pte: u32,
+    superpage: bool, // 4MiB. Only vpn[1] is matched
+}
+
+impl TlbEntry {
+    fn matches(&self, vpn: u32) -> bool {
+        if !self.valid {
+            return false;
+        }
+        if self.superpage {
+            self.vpn >> VPN_BITS == vpn >> VPN_BITS
+        } else {
+            self.vpn == vpn
+        }
+    }
+}
+
+// Fully associative, replaced round robin. ASIDs are not implemented, so there is a single space
+#[derive(Default)]
+pub struct Tlb {
+    entries: [TlbEntry; TLB_SIZE],
+    next: usize,
+}
+
+impl Tlb {
+    fn lookup(&self, vpn: u32) -> Option<TlbEntry> {
+        self.entries.iter().find(|e| e.matches(vpn)).cloned()
+    }
+
+    fn insert(&mut self, entry: TlbEntry) {
+        // An entry refilled for D replaces the old one
+        let i = match self.entries.iter().position(|e| e.matches(entry.vpn)) {
+            Some(i) => i,
+            None => {
+                let i = self.next;
+                self.next = (self.next + 1) % TLB_SIZE;
+                i
+            }
+        };
+        self.entries[i] = entry;
+    }
+
+    // sfence.vma. None flushes everything
+    pub fn flush(&mut self, vaddr: Option<u32>) {
+        for e in self.entries.iter_mut() {
+            match vaddr {
+                Some(vaddr) if !e.matches(vaddr >> PAGE_SHIFT) => (),
+                _ => e.valid = false,
+            }
+        }
+    }
+}
+
+// Loads and stores in M-mode with MPRV are translated and checked as if at MPP
+pub fn effective_level(cpu: &Cpu, access: Access) -> u8 {
+    if access != Access::Fetch && cpu.level == MACHINE && (cpu.status >> MPRV) & 0x1 == 1 {
+        ((cpu.status >> MPP) & 0x3) as u8
+    } else {
+        cpu.level
+    }
+}
+
+// Virtual to physical. Err is the exception cause
+pub fn translate(cpu: &mut Cpu, bus: &mut MasterBusEnd, vaddr: u32, access: Access) -> Result<u32, u32> {
+    let level = effective_level(cpu, access);
+    if level == MACHINE || cpu.satp >> SATP_MODE == MBARE {
+        return Ok(vaddr);
+    }
+
+    let vpn = vaddr >> PAGE_SHIFT;
+    let entry = match cpu.tlb.lookup(vpn) {
+        // A store through a clean entry walks again to set D
+        Some(e) if access != Access::Store || e.pte & PTE_D != 0 => {
+            check_permission(cpu, level, e.pte, access)?;
+            e
+        }
+        _ => {
+            let e = walk(cpu, bus, vaddr, level, access)?;
+            cpu.tlb.insert(e);
+            e
+        }
+    };
+
+    physical(&entry, vaddr).ok_or(access.access_fault())
+}
+
+fn walk(cpu: &mut Cpu, bus: &mut MasterBusEnd, vaddr: u32, level: u8, access: Access) -> Result<TlbEntry, u32> {
+    let mut table = ((cpu.satp & SATP_PPN_MASK) as u64) << PAGE_SHIFT;
+
+    for i in (0..2).rev() {
+        let vpn_i = (vaddr >> (PAGE_SHIFT + VPN_BITS * i)) & ((1 << VPN_BITS) - 1);
+        let pte_addr = table + (vpn_i * 4) as u64;
+        if pte_addr > ::std::u32::MAX as u64 {
+            return Err(access.access_fault());
+        }
+        let pte_addr = pte_addr as u32;
+
+        // The walk itself is held to PMP as S-mode
+        if !pmp::check(cpu, pte_addr, Access::Load, SUPERVISOR) {
+            return Err(access.access_fault());
+        }
+        cpu.count_bus_access(bus, pte_addr);
+        let pte = bus.read_word(pte_addr).map_err(|()| access.access_fault())?;
+
+        if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) {
+            return Err(access.page_fault());
+        }
+
+        // Pointer to the next level
+        if pte & (PTE_R | PTE_X) == 0 {
+            if i == 0 {
+                return Err(access.page_fault());
+            }
+            table = ((pte >> PPN_SHIFT) as u64) << PAGE_SHIFT;
+            continue;
+        }
+
+        check_permission(cpu, level, pte, access)?;
+
+        // Superpages must be aligned
+        if i == 1 && (pte >> PPN_SHIFT) & ((1 << VPN_BITS) - 1) != 0 {
+            return Err(access.page_fault());
+        }
+
+        let updated = pte | PTE_A | if access == Access::Store { PTE_D } else { 0 };
+        if updated != pte {
+            if !pmp::check(cpu, pte_addr, Access::Store, SUPERVISOR) {
+                return Err(access.access_fault());
+            }
+            cpu.count_bus_access(bus, pte_addr);
+            bus.write_word(pte_addr, updated).map_err(|()| access.access_fault())?;
+        }
+
+        return Ok(TlbEntry {
+            valid: true,
+            vpn: vaddr >> PAGE_SHIFT,
+            pte: updated,
+            superpage: i == 1,
+        });
+    }
+
+    panic!("statically impossible")
+}
+
+fn check_permission(cpu: &Cpu, level: u8, pte: u32, access: Access) -> Result<(), u32> {
+    let user_page = pte & PTE_U != 0;
+    let sum = (cpu.status >> SUM) & 0x1 == 1;
+    let mxr = (cpu.status >> MXR) & 0x1 == 1;
+
+    let privilege = match level {
+        USER => user_page,
+        // S-mode never executes user pages, and reads or writes them only with SUM
+        _ => !user_page || (access != Access::Fetch && sum),
+    };
+
+    let allowed = match access {
+        Access::Fetch => pte & PTE_X != 0,
+        Access::Load => pte & PTE_R != 0 || (mxr && pte & PTE_X != 0),
+        Access::Store => pte & PTE_W != 0,
+    };
+
+    if privilege && allowed {
+        Ok(())
+    } else {
+        Err(access.page_fault())
+    }
+}
+
+// Physical addresses are 34 bits. Those beyond the 32-bit bus don't exist
+fn physical(entry: &TlbEntry, vaddr: u32) -> Option<u32> {
+    let ppn = (entry.pte >> PPN_SHIFT) as u64;
+    let addr = if entry.superpage {
+        ((ppn >> VPN_BITS) << (PAGE_SHIFT + VPN_BITS)) | (vaddr & ((1 << (PAGE_SHIFT + VPN_BITS)) - 1)) as u64
+    } else {
+        (ppn << PAGE_SHIFT) | (vaddr & ((1 << PAGE_SHIFT) - 1)) as u64
+    };
+
+    if addr > ::std::u32::MAX as u64 {
+        None
+    } else {
+        Some(addr as u32)
+    }
+}
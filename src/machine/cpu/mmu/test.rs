@@ -0,0 +1,154 @@
+use super::*;
+
+use std::collections::HashMap;
+
+// Sparse words. Nothing exists at and above 0x100000
+struct PageBus(HashMap<u32, u32>);
+
+impl MasterBusEnd for PageBus {
+    fn read_word(&mut self, addr: u32) -> Result<u32, ()> {
+        if addr >= 0x100000 {
+            return Err(());
+        }
+        Ok(*self.0.get(&addr).unwrap_or(&0))
+    }
+    fn write_word(&mut self, addr: u32, value: u32) -> Result<(), ()> {
+        if addr >= 0x100000 {
+            return Err(());
+        }
+        self.0.insert(addr, value);
+        Ok(())
+    }
+    fn is_interrupting(&self) -> bool {
+        false
+    }
+}
+
+const ROOT: u32 = 0x1000;
+const TABLE: u32 = 0x2000;
+
+fn pte(ppn: u32, flags: u32) -> u32 {
+    (ppn << PPN_SHIFT) | flags | PTE_V
+}
+
+// Root at 0x1000. 0x00400000 is a table at 0x2000, 0x00800000 is a superpage onto 0x00400000
+fn setup() -> (Cpu, PageBus) {
+    let mut cpu = Cpu::new();
+    cpu.satp = (SV32 << SATP_MODE) | (ROOT >> PAGE_SHIFT);
+    cpu.level = SUPERVISOR;
+
+    // PMP lets everything through
+    cpu.pmpcfg[0] = (PMP_NAPOT << PMP_A) | PMP_R | PMP_W | PMP_X;
+    cpu.pmpaddr[0] = 0xFFFFFFFF;
+
+    let mut bus = PageBus(HashMap::new());
+    bus.0.insert(ROOT + 1 * 4, pte(TABLE >> PAGE_SHIFT, 0));
+    bus.0.insert(ROOT + 2 * 4, pte(0x400, PTE_R | PTE_W));
+
+    // Pages 0x00400000.., in the table
+    bus.0.insert(TABLE + 0 * 4, pte(0x5, PTE_R | PTE_W | PTE_X));
+    bus.0.insert(TABLE + 1 * 4, pte(0x6, PTE_R | PTE_U));
+    bus.0.insert(TABLE + 2 * 4, pte(0x7, PTE_X | PTE_U));
+    bus.0.insert(TABLE + 3 * 4, pte(0x100, PTE_R));
+
+    (cpu, bus)
+}
+
+#[test]
+fn test_bare() {
+    let (mut cpu, mut bus) = setup();
+    cpu.satp = 0;
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400123, Access::Load), Ok(0x00400123));
+
+    // M-mode is never translated
+    let (mut cpu, mut bus) = setup();
+    cpu.level = MACHINE;
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400123, Access::Store), Ok(0x00400123));
+}
+
+#[test]
+fn test_walk() {
+    let (mut cpu, mut bus) = setup();
+
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400123, Access::Load), Ok(0x5123));
+    assert_eq!(bus.0[&TABLE] & (PTE_A | PTE_D), PTE_A, "accessed");
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400FFC, Access::Store), Ok(0x5FFC));
+    assert_eq!(bus.0[&TABLE] & (PTE_A | PTE_D), PTE_A | PTE_D, "dirty");
+
+    // Superpage
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00812344, Access::Load), Ok(0x00412344));
+
+    // Unmapped, and beyond the last level
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00C00000, Access::Fetch), Err(INSTRUCTION_PAGE_FAULT));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00404000, Access::Store), Err(STORE_PAGE_FAULT));
+
+    // Whether the page exists is up to the bus
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00403000, Access::Load), Ok(0x100000));
+
+    // Misaligned superpage
+    bus.0.insert(ROOT + 3 * 4, pte(0x401, PTE_R));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00C00000, Access::Load), Err(LOAD_PAGE_FAULT));
+
+    // Table outside the bus
+    cpu.satp = (SV32 << SATP_MODE) | (0x100000 >> PAGE_SHIFT);
+    cpu.tlb.flush(None);
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Load), Err(LOAD_ACCESS_FAULT));
+}
+
+#[test]
+fn test_permission() {
+    let (mut cpu, mut bus) = setup();
+
+    // Read-only
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00403000, Access::Fetch), Err(INSTRUCTION_PAGE_FAULT));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00403000, Access::Store), Err(STORE_PAGE_FAULT));
+
+    // Execute-only, readable with MXR
+    cpu.level = USER;
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00402000, Access::Fetch), Ok(0x7000));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00402000, Access::Load), Err(LOAD_PAGE_FAULT));
+    cpu.status |= 1 << MXR;
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00402000, Access::Load), Ok(0x7000));
+
+    // User pages only
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Load), Err(LOAD_PAGE_FAULT));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00401000, Access::Load), Ok(0x6000));
+
+    // Supervisor reads user pages with SUM, and never executes them
+    cpu.level = SUPERVISOR;
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00401000, Access::Load), Err(LOAD_PAGE_FAULT));
+    cpu.status |= 1 << SUM;
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00401000, Access::Load), Ok(0x6000));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00402000, Access::Fetch), Err(INSTRUCTION_PAGE_FAULT));
+
+    // M-mode loads as U with MPRV
+    cpu.level = MACHINE;
+    cpu.status = (1 << MPRV) | ((USER as u32) << MPP);
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Load), Err(LOAD_PAGE_FAULT));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Fetch), Ok(0x00400000));
+}
+
+#[test]
+fn test_tlb() {
+    let (mut cpu, mut bus) = setup();
+
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Load), Ok(0x5000));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00801000, Access::Load), Ok(0x00401000));
+
+    // Stale until flushed
+    bus.0.insert(TABLE, pte(0x9, PTE_R));
+    bus.0.insert(ROOT + 2 * 4, 0);
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Load), Ok(0x5000));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00802000, Access::Load), Ok(0x00402000));
+
+    cpu.tlb.flush(Some(0x00400000));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Load), Ok(0x9000));
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00803000, Access::Load), Ok(0x00403000));
+
+    cpu.tlb.flush(None);
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00803000, Access::Load), Err(LOAD_PAGE_FAULT));
+
+    // Permissions are checked on hits too
+    cpu.level = USER;
+    assert_eq!(translate(&mut cpu, &mut bus, 0x00400000, Access::Load), Err(LOAD_PAGE_FAULT));
+}
@@ -1,12 +1,17 @@
 
-//TODO check that the cycle is pipeline correct in regard to exceptions (write back must not succeed if exception)
+// An instruction's effects are collected in an Outcome and applied together only if it didn't trap
 
 #[cfg(test)]
 mod test;
 
 mod system;
+mod float;
+mod mmu;
+mod pmp;
+pub(super) mod icache;
 
 use self::system::*;
+use self::mmu::Access;
 
 // Cpu
 
@@ -20,8 +25,6 @@ use ::arch::system::*;
 
 use enum_primitive::FromPrimitive;
 
-const RESET_VECTOR: u32 = 0;
-
 const NUM_REGS: usize = 32;
 
 /*
@@ -45,32 +48,103 @@ macro_rules! read_opcode {
 }
 }
 
+// Effects of the current instruction, applied by commit
+#[derive(Default)]
+pub struct Outcome {
+    pub reg: Option<(u8, i64)>,
+    pub freg: Option<(u8, u64)>,
+    pub stores: Vec<Store>,
+    pub pc: Option<u64>, // None if the instruction set pc itself (xRET)
+    pub events: Vec<u64>, // Counted if it retires
+    pub fflags: u32, // Accrued
+    pub csr: Option<(u32, u64)>,
+    pub reservation: Option<Option<u32>>, // Set by lr, cleared by sc
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Store {
+    pub addr: u64, // Virtual, for mtval
+    pub paddr: u32,
+    pub size: u32,
+    pub value: u32, // In the low size bytes
+}
+
 #[derive(Default)]
 pub struct Cpu {
-    pub regs: [i32; NUM_REGS],
-    pub pc: u32,
+    // Instructions outside it are illegal
+    pub isa: Isa,
+    // 32 or 64, from isa. In RV32, registers hold sign-extended 32-bit values and addresses wrap at 32 bits
+    pub xlen: u32,
+
+    pub regs: [i64; NUM_REGS],
+    pub pc: u64,
+
+    // F and D. Singles are NaN-boxed
+    pub fregs: [u64; NUM_REGS],
+    pub fflags: u32,
+    pub frm: u32,
 
     // SYSTEM state
     // CSRs
     pub level: u8,
 
+    pub misa: u64,
+
     pub status: u32,
 
-    pub scratch: u32,
-    pub epc: u32,
-    pub cause: u32,
-    pub mtval: u32,
+    pub scratch: u64,
+    pub epc: u64,
+    pub cause: u32, // The interrupt bit is kept at 31 in either XLEN
+    pub mtval: u64,
 
     // Interrupts
     pub ip: u32,
     pub ie: u32,
 
+    pub medeleg: u32,
+    pub mideleg: u32,
+
+    pub mcounteren: u32,
+    pub scounteren: u32,
+
+    // Supervisor trap setup and handling
+    pub stvec: u64,
+    pub sscratch: u64,
+    pub sepc: u64,
+    pub scause: u32,
+    pub stval: u64,
+
+    // Translation
+    pub satp: u32,
+    pub tlb: mmu::Tlb,
+
+    // Fetched words, until fence.i
+    pub icache: icache::ICache,
+
+    // Protection
+    pub pmpcfg: [u8; PMP_ENTRIES],
+    pub pmpaddr: [u32; PMP_ENTRIES],
+
     pub wfi: bool,
 
+    // Reservation set by lr. Word address
+    pub reservation: Option<u32>,
+
     // csrs
     pub cycle: u64,
+    pub instret: u64,
+    pub time: u64, // Set by the machine from its timer
+    pub hpmcounter: [u64; HPM_COUNT as usize], // mhpmcounter3 and on
+    pub hpmevent: [u64; HPM_COUNT as usize],
+
+    // Misaligned loads and stores are split instead of trapping
+    pub misaligned_access: bool,
+
+    // Set when the current instruction traps instead of retiring
+    pub trapped: bool,
+    pub outcome: Outcome,
 
-    pub mtvec: u32,
+    pub mtvec: u64,
 
     // Debug...
     pub num_cycles: i32,
@@ -80,7 +154,7 @@ pub struct Cpu {
     // DEBUG
     pub last_word: u32,
 
-    pub pc_trail: Option<Vec<u32>>,
+    pub pc_trail: Option<Vec<u64>>,
 }
 
 impl Cpu {
@@ -92,29 +166,123 @@ impl Cpu {
     }
 
     fn debug_reset(&mut self) {
-        self.reset();
+        self.reset(Default::default(), 0, 0);
     }
 
-    fn reset(&mut self) {
+    // The ISA, where execution starts and where traps go are up to the machine
+    pub fn reset(&mut self, isa: Isa, reset_vector: u32, mtvec: u32) {
+        let xlen = isa.xlen;
+        self.isa = isa;
+        self.xlen = xlen;
+        self.pc = reset_vector as u64;
         self.level = MACHINE;
+
+        // MXL is the top two bits. S and U modes are always there
+        let mxl = if xlen == 64 { XLEN_64 } else { XLEN_32 };
+        let extensions = isa.misa_extensions() | extension('S') | extension('U');
+        self.misa = ((mxl as u64) << (xlen - 2)) | extensions as u64;
         self.status = 0;
         self.cause = 0;
 
         self.ip = 0;
         self.ie = 0;
 
-        self.mtvec = MTVEC_VALUE;
+        self.mtvec = mtvec as u64;
+    }
+
+    // Counters programmed with the event go up by one
+    pub fn count_event(&mut self, event: u64) {
+        self.count_events(event, 1);
     }
 
-    fn reg(&self, r: u8) -> i32 {
-        info!("reg: {} = 0x{:08X}", ::arch::inst::register::abi_name(r), self.regs[r as usize]);
+    // ... or by n at once
+    pub fn count_events(&mut self, event: u64, n: u64) {
+        for i in 0..self.hpmevent.len() {
+            if self.hpmevent[i] == event {
+                self.hpmcounter[i] = self.hpmcounter[i].wrapping_add(n);
+            }
+        }
+    }
+
+    // Looking up the peripheral is only worth it if something counts bus accesses.
+    // Counted with the instruction's other events
+    fn count_bus_access(&mut self, bus: &MasterBusEnd, paddr: u32) {
+        if self.hpmevent.iter().all(|&e| e < HPM_EVENT_BUS_ACCESS) {
+            return;
+        }
+        if let Some(id) = bus.peripheral_id(paddr) {
+            self.outcome.events.push(HPM_EVENT_BUS_ACCESS + id as u64);
+        }
+    }
+
+    fn has_extension(&self, letter: char) -> bool {
+        self.misa & extension(letter) as u64 != 0
+    }
+
+    fn reg(&self, r: u8) -> i64 {
+        info!("reg: {} = 0x{:016X}", ::arch::inst::register::abi_name(r), self.regs[r as usize]);
         self.regs[r as usize]
     }
 
-    fn set_reg(&mut self, r: u8, v: i32) {
-        info!("set_reg: {} <- 0x{:08X}", ::arch::inst::register::abi_name(r), v);
+    // Register writes are held until commit. RV32 keeps the low 32 bits, sign-extended
+    fn set_reg(&mut self, r: u8, v: i64) {
+        let v = if self.xlen == 32 { v as i32 as i64 } else { v };
+        info!("set_reg: {} <- 0x{:016X}", ::arch::inst::register::abi_name(r), v);
         if r != 0 {
-            self.regs[r as usize] = v;
+            self.outcome.reg = Some((r, v));
+        }
+    }
+
+    // Addresses and pc wrap around at XLEN
+    fn wrap(&self, addr: u64) -> u64 {
+        if self.xlen == 32 { addr as u32 as u64 } else { addr }
+    }
+
+    // A register value as an unsigned XLEN-bit value
+    fn unsigned(&self, v: i64) -> u64 {
+        self.wrap(v as u64)
+    }
+
+    fn pc_offset(&self, offset: i64) -> u64 {
+        self.unsigned((self.pc as i64).wrapping_add(offset))
+    }
+
+    fn rotate_left(&self, v: i64, n: u32) -> i64 {
+        if self.xlen == 32 {
+            (v as u32).rotate_left(n) as i32 as i64
+        } else {
+            (v as u64).rotate_left(n) as i64
+        }
+    }
+
+    fn set_freg(&mut self, r: u8, v: u64) {
+        info!("set_freg: {} <- 0x{:016X}", ::arch::inst::register::float_abi_name(r), v);
+        self.outcome.freg = Some((r, v));
+    }
+
+    // pc must be 4-byte aligned, or 2-byte with C
+    fn is_pc_misaligned(&self, pc: u64) -> bool {
+        let align = if self.has_extension('C') { 0x1 } else { 0x3 };
+        pc & align != 0
+    }
+
+    // Taken branches and jumps. A misaligned target traps on the jump, which doesn't link
+    fn jump(&mut self, target: u64) {
+        if self.is_pc_misaligned(target) {
+            self.exception_with_tval(INSTRUCTION_ADDRESS_MISALIGNED, target);
+            return;
+        }
+        self.outcome.pc = Some(target);
+    }
+
+    // Rounding mode of the instruction, or frm if dynamic. None if reserved
+    fn rounding_mode(&self, word: u32) -> Option<u32> {
+        let rm = patch::RM.read(word);
+        let rm = if rm == float::DYN { self.frm } else { rm };
+        if rm <= float::RMM {
+            Some(rm)
+        } else {
+            None
         }
     }
 
@@ -123,8 +291,14 @@ impl Cpu {
         self.pc_trail.as_mut().map(|t| t.push(pc));
 
         self.wfi = false;
+        self.trapped = false;
         self.tick_inner(bus);
         self.num_cycles += 1;
+
+        self.cycle = self.cycle.wrapping_add(1);
+        if !self.trapped {
+            self.instret = self.instret.wrapping_add(1);
+        }
     }
 
     fn tick_inner(&mut self, bus: &mut MasterBusEnd) {
@@ -150,22 +324,26 @@ impl Cpu {
             }
 
             // Check each of interrupt sources
-            // TODO
+            if let Some(cause) = pending_interrupt(self, level) {
+                interrupt(self, level, cause);
+                return;
+            }
         }
 
-        // Generate address misaligned exception on branch/jump target
-        if self.pc & 0x3 != 0 {
-            self.mtval = self.pc;
-            self.cause = INSTRUCTION_ADDRESS_MISALIGNED;
+        // Jumps check their targets. This catches xRET to a misaligned xepc and the reset vector
+        let pc = self.pc;
+        if self.is_pc_misaligned(pc) {
+            self.exception_with_tval(INSTRUCTION_ADDRESS_MISALIGNED, pc);
             return;
         }
 
         // Instruction fetch
-        let word = match bus.read_word(self.pc){
+        let word = match self.fetch(bus) {
             Ok(v) => v,
-            Err(e) => {
-                self.mtval = self.pc;
-                self.exception(INSTRUCTION_ACCESS_FAULT);
+            Err(cause) => {
+                self.outcome = Default::default();
+                let pc = self.pc;
+                self.memory_exception(pc, cause);
                 return;
             },
         };
@@ -175,12 +353,125 @@ impl Cpu {
         self.cycle(word, bus);
     }
 
+    // With C, pc may be halfword aligned and a 32-bit instruction may straddle two words.
+    // A 16-bit instruction comes back in the low half
+    fn fetch(&mut self, bus: &mut MasterBusEnd) -> Result<u32, u32> {
+        let addr = self.pc & !0x3;
+        let word = self.fetch_word(bus, addr)?;
+
+        if self.pc & 0x2 == 0 {
+            return Ok(word);
+        }
+
+        let low = word >> 16;
+        if ::encode::is_compressed(low) {
+            return Ok(low);
+        }
+
+        let high = self.fetch_word(bus, self.wrap(addr + 4))?;
+        Ok(low | (high << 16))
+    }
+
+    fn fetch_word(&mut self, bus: &mut MasterBusEnd, addr: u64) -> Result<u32, u32> {
+        let paddr = self.translate(bus, addr, Access::Fetch)?;
+        if let Some(word) = self.icache.lookup(paddr) {
+            return Ok(word);
+        }
+        self.count_bus_access(bus, paddr);
+        let word = bus.read_word(paddr).map_err(|()| Access::Fetch.access_fault())?;
+        self.icache.insert(paddr, word);
+        Ok(word)
+    }
+
     fn cycle(&mut self, word: u32, bus: &mut MasterBusEnd) {
+        self.trapped = false;
+
+        // Bus accesses of the fetch count with the instruction
+        let events = ::std::mem::replace(&mut self.outcome.events, Vec::new());
+        self.outcome = Outcome { events: events, ..Default::default() };
+
+        self.execute(word, bus);
+
+        if !self.trapped {
+            self.commit(bus);
+        }
+
+        // Nothing carries over to the next instruction, retired or not
+        self.outcome = Default::default();
+    }
+
+    // What may still fail goes first: the CSR write, then stores, all pieces checked before any
+    // is written. Then the rest of the state
+    fn commit(&mut self, bus: &mut MasterBusEnd) {
+        if let Some((csr, v)) = self.outcome.csr {
+            if let Err(()) = self.write_csr(csr, v) {
+                self.exception(ILLEGAL_INSTRUCTION);
+                return;
+            }
+        }
+
+        let stores = ::std::mem::replace(&mut self.outcome.stores, Vec::new());
+        if let Some(store) = stores.iter().find(|s| !bus.is_writable(s.paddr)) {
+            self.memory_exception(store.addr, STORE_ACCESS_FAULT);
+            return;
+        }
+
+        if let Some(reservation) = self.outcome.reservation {
+            self.reservation = reservation;
+        }
+
+        if !stores.is_empty() {
+            self.outcome.events.push(HPM_EVENT_STORES);
+        }
+        for store in stores {
+            // A store to the reserved word breaks the reservation
+            if self.reservation == Some(store.paddr & !0x3) {
+                self.reservation = None;
+            }
+            self.count_bus_access(bus, store.paddr);
+            bus.write(store.paddr, store.size, store.value).expect("checked writable");
+        }
+
+        if let Some((r, v)) = self.outcome.reg {
+            self.regs[r as usize] = v;
+        }
+        if let Some((r, v)) = self.outcome.freg {
+            self.fregs[r as usize] = v;
+        }
+        if let Some(pc) = self.outcome.pc {
+            self.pc = pc;
+        }
+        self.fflags |= self.outcome.fflags;
+        let events = ::std::mem::replace(&mut self.outcome.events, Vec::new());
+        for event in events {
+            self.count_event(event);
+        }
+    }
+
+    fn execute(&mut self, word: u32, bus: &mut MasterBusEnd) {
 //        println!("cpu cycle on {:X}, word: {:X}", self.pc, word);
         // NOP may be optimized here
 
-        // check
-        if word & 0x3 != 0x3 {
+        // check. 16-bit instructions run as what they expand to
+        let (word, len) = if ::encode::is_compressed(word) {
+            if !self.has_extension('C') {
+                self.exception(ILLEGAL_INSTRUCTION);
+                return;
+            }
+
+            match ::decode::expand(word & 0xFFFF, self.xlen) {
+                Some(expanded) => (expanded, 2),
+                None => {
+                    self.exception(ILLEGAL_INSTRUCTION);
+                    return;
+                }
+            }
+        } else {
+            (word, 4)
+        };
+
+        // Extensions the machine leaves out
+        if !self.isa.accepts(word) {
             self.exception(ILLEGAL_INSTRUCTION);
             return;
         }
@@ -191,6 +482,473 @@ impl Cpu {
 
         match inst_type {
 
+            INST_TYPE::R if opcode == OPCODE::AMO => {
+                let rd = arg::RD.read(word) as u8;
+                let rs1 = arg::RS1.read(word) as u8;
+                let rs2 = arg::RS2.read(word) as u8;
+
+                let width = read_opcode!(self, word, FUNCT3_AMO, FUNCT3);
+                let funct5 = read_opcode!(self, word, FUNCT5_AMO, FUNCT5);
+
+                // Doublewords are RV64 only
+                let size = match width {
+                    FUNCT3_AMO::W => 4,
+                    FUNCT3_AMO::D if self.xlen == 64 => 8,
+                    FUNCT3_AMO::D => {
+                        self.exception(ILLEGAL_INSTRUCTION);
+                        return;
+                    }
+                };
+
+                // Words are sign-extended, and compared unsigned in their low 32 bits
+                let extend = |v: i64| if size == 4 { v as i32 as i64 } else { v };
+                let unsigned = |v: i64| if size == 4 { v as u32 as u64 } else { v as u64 };
+
+                // aq/rl are accepted but there is nothing to order on a single in-order hart
+
+                let addr = self.unsigned(self.reg(rs1));
+
+                match funct5 {
+                    FUNCT5_AMO::LR => {
+                        if rs2 != 0 {
+                            self.exception(ILLEGAL_INSTRUCTION);
+                            return;
+                        }
+
+                        if addr & (size - 1) != 0 {
+                            self.memory_exception(addr, LOAD_ADDRESS_MISALIGNED);
+                            return;
+                        }
+
+                        // The reservation is on the physical word
+                        let paddr = match self.translate(bus, addr, Access::Load) {
+                            Ok(v) => v,
+                            Err(cause) => {
+                                self.memory_exception(addr, cause);
+                                return;
+                            }
+                        };
+
+                        let read = match self.load(bus, addr, size as u32, Access::Load) {
+                            Ok(v) => v,
+                            Err(cause) => {
+                                self.memory_exception(addr, cause);
+                                return;
+                            }
+                        };
+
+                        self.outcome.reservation = Some(Some(paddr));
+                        self.set_reg(rd, extend(read as i64));
+                    }
+                    FUNCT5_AMO::SC => {
+                        if addr & (size - 1) != 0 {
+                            self.memory_exception(addr, STORE_ADDRESS_MISALIGNED);
+                            return;
+                        }
+
+                        let paddr = match self.translate(bus, addr, Access::Store) {
+                            Ok(v) => v,
+                            Err(cause) => {
+                                self.memory_exception(addr, cause);
+                                return;
+                            }
+                        };
+
+                        // sc always consumes the reservation, whether it succeeds or not
+                        let success = self.reservation == Some(paddr);
+                        self.outcome.reservation = Some(None);
+
+                        if success {
+                            let value = self.reg(rs2) as u64;
+                            if let Err(cause) = self.store(bus, addr, size as u32, value) {
+                                self.memory_exception(addr, cause);
+                                return;
+                            }
+                        }
+
+                        self.set_reg(rd, if success { 0 } else { 1 });
+                    }
+                    funct5 => {
+                        // AMOs report store/AMO faults, even for the read part
+                        if addr & (size - 1) != 0 {
+                            self.memory_exception(addr, STORE_ADDRESS_MISALIGNED);
+                            return;
+                        }
+
+                        let read = match self.load(bus, addr, size as u32, Access::Store) {
+                            Ok(v) => extend(v as i64),
+                            Err(cause) => {
+                                self.memory_exception(addr, cause);
+                                return;
+                            }
+                        };
+
+                        let v_rs2 = extend(self.reg(rs2));
+
+                        let value = match funct5 {
+                            FUNCT5_AMO::AMOSWAP => v_rs2,
+                            FUNCT5_AMO::AMOADD => read.wrapping_add(v_rs2),
+                            FUNCT5_AMO::AMOXOR => read ^ v_rs2,
+                            FUNCT5_AMO::AMOAND => read & v_rs2,
+                            FUNCT5_AMO::AMOOR => read | v_rs2,
+                            FUNCT5_AMO::AMOMIN => if read < v_rs2 { read } else { v_rs2 },
+                            FUNCT5_AMO::AMOMAX => if read > v_rs2 { read } else { v_rs2 },
+                            FUNCT5_AMO::AMOMINU => if unsigned(read) < unsigned(v_rs2) { read } else { v_rs2 },
+                            FUNCT5_AMO::AMOMAXU => if unsigned(read) > unsigned(v_rs2) { read } else { v_rs2 },
+                            _ => panic!("statically impossible"),
+                        };
+
+                        match self.store(bus, addr, size as u32, value as u64) {
+                            Ok(()) => (),
+                            Err(cause) => {
+                                self.memory_exception(addr, cause);
+                                return;
+                            }
+                        }
+
+                        self.set_reg(rd, read);
+                    }
+                }
+            }
+
+            INST_TYPE::R if opcode == OPCODE::OP_FP => {
+                let rd = arg::RD.read(word) as u8;
+                let rs1 = arg::RS1.read(word) as u8;
+                let rs2 = arg::RS2.read(word) as u8;
+
+                let fmt = read_opcode!(self, word, FMT, FMT);
+                let funct5 = read_opcode!(self, word, FUNCT5_OP_FP, FUNCT5);
+
+                let x_a = self.fregs[rs1 as usize];
+                let x_b = self.fregs[rs2 as usize];
+                let a = float::unbox(fmt, x_a);
+                let b = float::unbox(fmt, x_b);
+                let snan = float::is_snan(fmt, x_a) || float::is_snan(fmt, x_b);
+
+                match funct5 {
+                    FUNCT5_OP_FP::FADD | FUNCT5_OP_FP::FSUB | FUNCT5_OP_FP::FMUL | FUNCT5_OP_FP::FDIV | FUNCT5_OP_FP::FSQRT => {
+                        let rm = match self.rounding_mode(word) {
+                            Some(rm) => rm,
+                            None => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        };
+
+                        let unary = funct5 == FUNCT5_OP_FP::FSQRT;
+                        if unary && rs2 != 0 {
+                            self.exception(ILLEGAL_INSTRUCTION);
+                            return;
+                        }
+
+                        let (v, flags) = if unary && a.is_nan() {
+                            (a, if float::is_snan(fmt, x_a) { float::NV } else { 0 })
+                        } else if !unary && (a.is_nan() || b.is_nan()) {
+                            (a + b, if snan { float::NV } else { 0 })
+                        } else {
+                            match funct5 {
+                                FUNCT5_OP_FP::FADD => float::add(fmt, a, b, rm),
+                                FUNCT5_OP_FP::FSUB => float::add(fmt, a, -b, rm),
+                                FUNCT5_OP_FP::FMUL => float::mul(fmt, a, b, rm),
+                                FUNCT5_OP_FP::FDIV => float::div(fmt, a, b, rm),
+                                _ => float::sqrt(fmt, a, rm),
+                            }
+                        };
+
+                        self.outcome.fflags |= flags;
+                        self.set_freg(rd, float::box_value(fmt, v));
+                    }
+                    FUNCT5_OP_FP::FSGNJ => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_FSGNJ, FUNCT3);
+
+                        // Bitwise, no exceptions and no NaN canonicalization
+                        let sign = float::sign_bit(fmt);
+                        let bits_a = float::unbox_bits(fmt, x_a);
+                        let bits_b = float::unbox_bits(fmt, x_b);
+                        let s = match funct3 {
+                            FUNCT3_FSGNJ::FSGNJ => bits_b & sign,
+                            FUNCT3_FSGNJ::FSGNJN => !bits_b & sign,
+                            FUNCT3_FSGNJ::FSGNJX => (bits_a ^ bits_b) & sign,
+                        };
+
+                        self.set_freg(rd, float::box_bits(fmt, (bits_a & !sign) | s));
+                    }
+                    FUNCT5_OP_FP::FMINMAX => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_FMINMAX, FUNCT3);
+
+                        if snan {
+                            self.outcome.fflags |= float::NV;
+                        }
+                        let v = float::min_max(a, b, funct3 == FUNCT3_FMINMAX::FMAX);
+
+                        self.set_freg(rd, float::box_value(fmt, v));
+                    }
+                    FUNCT5_OP_FP::FCVT_FMT => {
+                        // rs2 is the source format
+                        let src = match FMT::from_u32(rs2 as u32) {
+                            Some(src) if src != fmt => src,
+                            _ => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        };
+                        let rm = match self.rounding_mode(word) {
+                            Some(rm) => rm,
+                            None => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        };
+
+                        let x = float::unbox(src, x_a);
+                        let (v, flags) = if x.is_nan() {
+                            (x, if float::is_snan(src, x_a) { float::NV } else { 0 })
+                        } else if x.is_infinite() {
+                            (x, 0)
+                        } else {
+                            float::round(fmt, x, 0.0, rm)
+                        };
+
+                        self.outcome.fflags |= flags;
+                        self.set_freg(rd, float::box_value(fmt, v));
+                    }
+                    FUNCT5_OP_FP::FCMP => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_FCMP, FUNCT3);
+
+                        // feq is quiet, flt and fle signal on any NaN
+                        if snan || (funct3 != FUNCT3_FCMP::FEQ && (a.is_nan() || b.is_nan())) {
+                            self.outcome.fflags |= float::NV;
+                        }
+                        let r = match funct3 {
+                            FUNCT3_FCMP::FEQ => a == b,
+                            FUNCT3_FCMP::FLT => a < b,
+                            FUNCT3_FCMP::FLE => a <= b,
+                        };
+
+                        self.set_reg(rd, if r { 1 } else { 0 });
+                    }
+                    FUNCT5_OP_FP::FCVT_W => {
+                        let kind = read_opcode!(self, word, RS2_FCVT_W, RS2);
+                        let rm = match self.rounding_mode(word) {
+                            Some(rm) => rm,
+                            None => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        };
+
+                        // Longs are RV64 only. Words, even unsigned ones, are sign-extended
+                        let signed = kind == RS2_FCVT_W::W || kind == RS2_FCVT_W::L;
+                        let v = match kind {
+                            RS2_FCVT_W::W | RS2_FCVT_W::WU => {
+                                let (v, flags) = float::to_int(a, rm, signed, 32);
+                                self.outcome.fflags |= flags;
+                                v as i32 as i64
+                            }
+                            _ if self.xlen == 64 => {
+                                let (v, flags) = float::to_int(a, rm, signed, 64);
+                                self.outcome.fflags |= flags;
+                                v as i64
+                            }
+                            _ => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        };
+
+                        self.set_reg(rd, v);
+                    }
+                    FUNCT5_OP_FP::FCVT_FROM_W => {
+                        let kind = read_opcode!(self, word, RS2_FCVT_W, RS2);
+                        let rm = match self.rounding_mode(word) {
+                            Some(rm) => rm,
+                            None => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        };
+
+                        // 64-bit integers don't fit in a double. lo is what converting lost
+                        let x = self.reg(rs1);
+                        let (hi, lo) = match kind {
+                            RS2_FCVT_W::W => (x as i32 as f64, 0.0),
+                            RS2_FCVT_W::WU => (x as u32 as f64, 0.0),
+                            _ if self.xlen != 64 => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                            RS2_FCVT_W::L => {
+                                let hi = x as f64;
+                                (hi, (x as i128 - hi as i128) as f64)
+                            }
+                            RS2_FCVT_W::LU => {
+                                let hi = x as u64 as f64;
+                                (hi, (x as u64 as i128 - hi as i128) as f64)
+                            }
+                        };
+                        let (v, flags) = float::round(fmt, hi, lo, rm);
+
+                        self.outcome.fflags |= flags;
+                        self.set_freg(rd, float::box_value(fmt, v));
+                    }
+                    FUNCT5_OP_FP::FMV_X_CLASS => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_FMV_X_CLASS, FUNCT3);
+                        if rs2 != 0 {
+                            self.exception(ILLEGAL_INSTRUCTION);
+                            return;
+                        }
+
+                        let v = match funct3 {
+                            // fmv.x.w sign-extends. No fmv.x.d on RV32
+                            FUNCT3_FMV_X_CLASS::FMV_X if fmt == FMT::S => x_a as u32 as i32 as i64,
+                            FUNCT3_FMV_X_CLASS::FMV_X if self.xlen == 64 => x_a as i64,
+                            FUNCT3_FMV_X_CLASS::FMV_X => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                            FUNCT3_FMV_X_CLASS::FCLASS => float::classify(fmt, x_a) as i64,
+                        };
+
+                        self.set_reg(rd, v);
+                    }
+                    FUNCT5_OP_FP::FMV_FROM_X => {
+                        let valid_fmt = fmt == FMT::S || self.xlen == 64;
+                        if rs2 != 0 || !valid_fmt || patch::FUNCT3.read(word) != 0 {
+                            self.exception(ILLEGAL_INSTRUCTION);
+                            return;
+                        }
+
+                        let v = self.reg(rs1) as u64;
+                        let v = if fmt == FMT::S { v as u32 as u64 } else { v };
+                        self.set_freg(rd, float::box_bits(fmt, v));
+                    }
+                }
+            }
+
+            INST_TYPE::R4 => {
+                let rd = arg::RD.read(word) as u8;
+                let rs1 = arg::RS1.read(word) as u8;
+                let rs2 = arg::RS2.read(word) as u8;
+                let rs3 = patch::RS3.read(word) as u8;
+
+                let fmt = read_opcode!(self, word, FMT, FMT);
+                let rm = match self.rounding_mode(word) {
+                    Some(rm) => rm,
+                    None => {
+                        self.exception(ILLEGAL_INSTRUCTION);
+                        return;
+                    }
+                };
+
+                let x_a = self.fregs[rs1 as usize];
+                let x_b = self.fregs[rs2 as usize];
+                let x_c = self.fregs[rs3 as usize];
+                let a = float::unbox(fmt, x_a);
+                let b = float::unbox(fmt, x_b);
+                let c = float::unbox(fmt, x_c);
+
+                // (-(a * b)) or (a * b), then +c or -c
+                let (negate_product, negate_addend) = match opcode {
+                    OPCODE::MADD => (false, false),
+                    OPCODE::MSUB => (false, true),
+                    OPCODE::NMSUB => (true, false),
+                    OPCODE::NMADD => (true, true),
+                    _ => panic!("statically impossible"),
+                };
+
+                let (v, flags) = if a.is_nan() || b.is_nan() || c.is_nan() {
+                    // inf * 0 is invalid even when the addend is a quiet NaN
+                    let invalid = float::is_snan(fmt, x_a) || float::is_snan(fmt, x_b) || float::is_snan(fmt, x_c)
+                        || (a.is_infinite() && b == 0.0) || (a == 0.0 && b.is_infinite());
+                    (a + b + c, if invalid { float::NV } else { 0 })
+                } else {
+                    let a = if negate_product { -a } else { a };
+                    let c = if negate_addend { -c } else { c };
+                    float::fma(fmt, a, b, c, rm)
+                };
+
+                self.outcome.fflags |= flags;
+                self.set_freg(rd, float::box_value(fmt, v));
+            }
+
+            INST_TYPE::R if opcode == OPCODE::OP_32 => {
+                let rd = arg::RD.read(word) as u8;
+                let rs1 = arg::RS1.read(word) as u8;
+                let rs2 = arg::RS2.read(word) as u8;
+
+                // Word instructions are RV64 only. They work on the low 32 bits and sign-extend
+                if self.xlen != 64 {
+                    self.exception(ILLEGAL_INSTRUCTION);
+                    return;
+                }
+
+                let a = self.reg(rs1) as i32;
+                let b = self.reg(rs2) as i32;
+
+                let funct7 = read_opcode!(self, word, FUNCT7_OP, FUNCT7);
+                let res = match funct7 {
+                    FUNCT7_OP::BASE => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_32, FUNCT3);
+                        (match funct3 {
+                            FUNCT3_OP_32::ADDW => a.wrapping_add(b),
+                            FUNCT3_OP_32::SLLW => a << (b & 0x1F),
+                            FUNCT3_OP_32::SRLW => ((a as u32) >> (b & 0x1F)) as i32,
+                        }) as i64
+                    }
+                    FUNCT7_OP::ALT => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_32_ALT, FUNCT3);
+                        (match funct3 {
+                            FUNCT3_OP_32_ALT::SUBW => a.wrapping_sub(b),
+                            FUNCT3_OP_32_ALT::SRAW => a >> (b & 0x1F),
+                        }) as i64
+                    }
+                    FUNCT7_OP::MULDIV => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_32_MULDIV, FUNCT3);
+                        (match funct3 {
+                            FUNCT3_OP_32_MULDIV::MULW => a.wrapping_mul(b),
+                            FUNCT3_OP_32_MULDIV::DIVW => if b == 0 { -1 } else { a.wrapping_div(b) },
+                            FUNCT3_OP_32_MULDIV::DIVUW => if b == 0 { -1 } else { ((a as u32) / (b as u32)) as i32 },
+                            FUNCT3_OP_32_MULDIV::REMW => if b == 0 { a } else { a.wrapping_rem(b) },
+                            FUNCT3_OP_32_MULDIV::REMUW => if b == 0 { a } else { ((a as u32) % (b as u32)) as i32 },
+                        }) as i64
+                    }
+                    // Zba works on the zero-extended low word of rs1, with a full width result
+                    FUNCT7_OP::ZEXT => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_ZEXT, FUNCT3);
+                        match funct3 {
+                            FUNCT3_OP_ZEXT::ADD_UW => (a as u32 as i64).wrapping_add(self.reg(rs2)),
+                            FUNCT3_OP_ZEXT::ZEXT_H if rs2 == 0 => a as u16 as i64,
+                            _ => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        }
+                    }
+                    FUNCT7_OP::SHADD => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_SHADD, FUNCT3);
+                        let n = match funct3 {
+                            FUNCT3_OP_SHADD::SH1ADD => 1,
+                            FUNCT3_OP_SHADD::SH2ADD => 2,
+                            FUNCT3_OP_SHADD::SH3ADD => 3,
+                        };
+                        ((a as u32 as i64) << n).wrapping_add(self.reg(rs2))
+                    }
+                    FUNCT7_OP::ROT => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_ROT, FUNCT3);
+                        (match funct3 {
+                            FUNCT3_OP_ROT::ROL => (a as u32).rotate_left(b as u32 & 0x1F),
+                            FUNCT3_OP_ROT::ROR => (a as u32).rotate_right(b as u32 & 0x1F),
+                        }) as i32 as i64
+                    }
+                    _ => {
+                        self.exception(ILLEGAL_INSTRUCTION);
+                        return;
+                    }
+                };
+
+                self.set_reg(rd, res);
+            }
+
             INST_TYPE::R => {
                 let rd = arg::RD.read(word) as u8;
                 let rs1 = arg::RS1.read(word) as u8;
@@ -199,26 +957,99 @@ impl Cpu {
                 // OPCODE OP
                 assert_eq!(opcode, OPCODE::OP);
 
+                let a = self.reg(rs1);
+                let b = self.reg(rs2);
+                let shamt = b & (self.xlen as i64 - 1);
+
                 let funct7 = read_opcode!(self, word, FUNCT7_OP, FUNCT7);
                 let res = match funct7 {
                     FUNCT7_OP::BASE => {
                         let funct3 = read_opcode!(self, word, FUNCT3_OP, FUNCT3);
                         match funct3 {
-                            FUNCT3_OP::ADD => self.reg(rs1).wrapping_add(self.reg(rs2)),
-                            FUNCT3_OP::SLL => self.reg(rs1) << (self.reg(rs2) & 0x1F),
-                            FUNCT3_OP::SLT => if self.reg(rs1) < self.reg(rs2) { 1 } else { 0 },
-                            FUNCT3_OP::SLTU => if (self.reg(rs1) as u32) < (self.reg(rs2) as u32) { 1 } else { 0 },
-                            FUNCT3_OP::XOR => self.reg(rs1) ^ self.reg(rs2),
-                            FUNCT3_OP::SRL => self.reg(rs1) >> self.reg(rs2) & 0x1F,
-                            FUNCT3_OP::OR => self.reg(rs1) | self.reg(rs2),
-                            FUNCT3_OP::AND => self.reg(rs1) & self.reg(rs2),
+                            FUNCT3_OP::ADD => a.wrapping_add(b),
+                            FUNCT3_OP::SLL => a << shamt,
+                            FUNCT3_OP::SLT => if a < b { 1 } else { 0 },
+                            FUNCT3_OP::SLTU => if self.unsigned(a) < self.unsigned(b) { 1 } else { 0 },
+                            FUNCT3_OP::XOR => a ^ b,
+                            FUNCT3_OP::SRL => (self.unsigned(a) >> shamt) as i64,
+                            FUNCT3_OP::OR => a | b,
+                            FUNCT3_OP::AND => a & b,
                         }
                     }
                     FUNCT7_OP::ALT => {
                         let funct3 = read_opcode!(self, word, FUNCT3_OP_ALT, FUNCT3);
                         match funct3 {
-                            FUNCT3_OP_ALT::SUB => self.reg(rs1).wrapping_sub(self.reg(rs2)),
-                            FUNCT3_OP_ALT::SRA => (self.reg(rs1) as u32 >> self.reg(rs2) & 0x1F) as i32,
+                            FUNCT3_OP_ALT::SUB => a.wrapping_sub(b),
+                            FUNCT3_OP_ALT::SRA => a >> shamt,
+                            FUNCT3_OP_ALT::XNOR => !(a ^ b),
+                            FUNCT3_OP_ALT::ORN => a | !b,
+                            FUNCT3_OP_ALT::ANDN => a & !b,
+                        }
+                    }
+                    FUNCT7_OP::MULDIV => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_MULDIV, FUNCT3);
+                        let xlen = self.xlen;
+                        let (ua, ub) = (self.unsigned(a), self.unsigned(b));
+                        // Division by zero and overflow don't trap. See the spec for the values.
+                        // In RV32 the overflow of a 64-bit division is truncated to the right value
+                        match funct3 {
+                            FUNCT3_OP_MULDIV::MUL => a.wrapping_mul(b),
+                            FUNCT3_OP_MULDIV::MULH => ((a as i128 * b as i128) >> xlen) as i64,
+                            FUNCT3_OP_MULDIV::MULHSU => ((a as i128 * ub as i128) >> xlen) as i64,
+                            FUNCT3_OP_MULDIV::MULHU => ((ua as u128 * ub as u128) >> xlen) as i64,
+                            FUNCT3_OP_MULDIV::DIV => if b == 0 { -1 } else { a.wrapping_div(b) },
+                            FUNCT3_OP_MULDIV::DIVU => if b == 0 { -1 } else { (ua / ub) as i64 },
+                            FUNCT3_OP_MULDIV::REM => if b == 0 { a } else { a.wrapping_rem(b) },
+                            FUNCT3_OP_MULDIV::REMU => if b == 0 { a } else { (ua % ub) as i64 },
+                        }
+                    }
+                    // zext.h. RV64 has it in OP_32
+                    FUNCT7_OP::ZEXT => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_ZEXT, FUNCT3);
+                        if funct3 != FUNCT3_OP_ZEXT::ZEXT_H || rs2 != 0 || self.xlen != 32 {
+                            self.exception(ILLEGAL_INSTRUCTION);
+                            return;
+                        }
+                        a as u16 as i64
+                    }
+                    FUNCT7_OP::MINMAX => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_MINMAX, FUNCT3);
+                        let (ua, ub) = (self.unsigned(a), self.unsigned(b));
+                        match funct3 {
+                            FUNCT3_OP_MINMAX::MIN => a.min(b),
+                            FUNCT3_OP_MINMAX::MINU => if ua < ub { a } else { b },
+                            FUNCT3_OP_MINMAX::MAX => a.max(b),
+                            FUNCT3_OP_MINMAX::MAXU => if ua > ub { a } else { b },
+                        }
+                    }
+                    FUNCT7_OP::SHADD => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_SHADD, FUNCT3);
+                        match funct3 {
+                            FUNCT3_OP_SHADD::SH1ADD => (a << 1).wrapping_add(b),
+                            FUNCT3_OP_SHADD::SH2ADD => (a << 2).wrapping_add(b),
+                            FUNCT3_OP_SHADD::SH3ADD => (a << 3).wrapping_add(b),
+                        }
+                    }
+                    FUNCT7_OP::ROT => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_ROT, FUNCT3);
+                        match funct3 {
+                            FUNCT3_OP_ROT::ROL => self.rotate_left(a, shamt as u32),
+                            FUNCT3_OP_ROT::ROR => self.rotate_left(a, self.xlen - shamt as u32),
+                        }
+                    }
+                    // Single bit instructions. bext shares funct7 with bclr
+                    FUNCT7_OP::BSET | FUNCT7_OP::BCLR | FUNCT7_OP::BINV => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_BIT, FUNCT3);
+                        let bit = 1i64 << shamt;
+                        match (funct7, funct3) {
+                            (FUNCT7_OP::BSET, FUNCT3_OP_BIT::BIT) => a | bit,
+                            (FUNCT7_OP::BCLR, FUNCT3_OP_BIT::BIT) => a & !bit,
+                            (FUNCT7_OP::BINV, FUNCT3_OP_BIT::BIT) => a ^ bit,
+                            (FUNCT7_OP::BCLR, FUNCT3_OP_BIT::BEXT) => (a >> shamt) & 0x1,
+                            _ => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
                         }
                     }
                 };
@@ -229,89 +1060,217 @@ impl Cpu {
             INST_TYPE::I => {
                 let rd = arg::RD.read(word) as u8;
                 let rs1 = arg::RS1.read(word) as u8;
-                let imm = arg::IMM12.read(word) as i32;
+                let imm = arg::IMM12.read(word) as i32 as i64;
 
                 match opcode {
                     OPCODE::LOAD => {
-                        // Completly faulting at misaligned load for now
-
                         let funct3 = read_opcode!(self, word, FUNCT3_LOAD, FUNCT3);
 
-                        let addr = self.reg(rs1).wrapping_add(imm) as u32;
-                        let byte_offset = addr & 0x3;
-
-                        // Catch misaligned access early
-                        if funct3 == FUNCT3_LOAD::LH || funct3 == FUNCT3_LOAD::LHU {
-                            if !(byte_offset == 0 || byte_offset == 2) {
-                                self.exception(LOAD_ADDRESS_MISALIGNED);
+                        let addr = self.unsigned(self.reg(rs1).wrapping_add(imm));
+
+                        let size = match funct3 {
+                            FUNCT3_LOAD::LB | FUNCT3_LOAD::LBU => 1,
+                            FUNCT3_LOAD::LH | FUNCT3_LOAD::LHU => 2,
+                            FUNCT3_LOAD::LW => 4,
+                            // RV64 only
+                            FUNCT3_LOAD::LWU if self.xlen == 64 => 4,
+                            FUNCT3_LOAD::LD if self.xlen == 64 => 8,
+                            _ => {
+                                self.exception(ILLEGAL_INSTRUCTION);
                                 return;
                             }
-                        } else if funct3 == FUNCT3_LOAD::LW {
-                            if byte_offset != 0 {
-                                self.exception(LOAD_ADDRESS_MISALIGNED);
+                        };
+
+                        // Misaligned access faults unless the machine emulates it
+                        if addr & (size as u64 - 1) != 0 && !self.misaligned_access {
+                            self.memory_exception(addr, LOAD_ADDRESS_MISALIGNED);
+                            return;
+                        }
+
+                        let read = match self.load(bus, addr, size, Access::Load) {
+                            Ok(v) => v,
+                            Err(cause) => {
+                                self.memory_exception(addr, cause);
                                 return;
                             }
-                        }
+                        };
+
+                        let value = match funct3 {
+                            FUNCT3_LOAD::LB => read as i8 as i64,
+                            FUNCT3_LOAD::LH => read as i16 as i64,
+                            FUNCT3_LOAD::LW => read as i32 as i64,
+                            FUNCT3_LOAD::LD => read as i64,
+                            FUNCT3_LOAD::LBU => read as u8 as i64,
+                            FUNCT3_LOAD::LHU => read as u16 as i64,
+                            FUNCT3_LOAD::LWU => read as u32 as i64,
+                        };
 
-                        let addr_word_aligned = addr & !0x3;
+                        self.set_reg(rd, value);
+                    }
+
+                    OPCODE::LOAD_FP => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_LOAD_FP, FUNCT3);
+
+                        let addr = self.unsigned(self.reg(rs1).wrapping_add(imm));
 
-                        let read = match bus.read_word(addr_word_aligned) {
+                        // Naturally aligned only. fld is two word reads
+                        let size = if funct3 == FUNCT3_LOAD_FP::FLW { 4 } else { 8 };
+                        if addr & (size as u64 - 1) != 0 {
+                            self.memory_exception(addr, LOAD_ADDRESS_MISALIGNED);
+                            return;
+                        }
+
+                        let value = match self.load(bus, addr, size, Access::Load) {
                             Ok(v) => v,
-                            Err(()) => {
-                                self.exception(LOAD_ACCESS_FAULT);
+                            Err(cause) => {
+                                self.memory_exception(addr, cause);
                                 return;
                             }
                         };
 
                         let value = match funct3 {
-                            FUNCT3_LOAD::LB => (read >> byte_offset*8) as i8 as i32,
-                            FUNCT3_LOAD::LH => (read >> byte_offset*8) as i16 as i32,
-                            FUNCT3_LOAD::LW => read as i32,
-                            FUNCT3_LOAD::LBU => (read >> byte_offset*8) as u8 as i32,
-                            FUNCT3_LOAD::LHU => (read >> byte_offset*8) as u16 as i32,
+                            FUNCT3_LOAD_FP::FLW => float::box_bits(FMT::S, value),
+                            FUNCT3_LOAD_FP::FLD => value,
                         };
 
-                        self.set_reg(rd, value);
+                        self.set_freg(rd, value);
                     }
 
                     OPCODE::OP_IMM => {
                         let funct3 = read_opcode!(self, word, FUNCT3_OP_IMM, FUNCT3);
 
-                        let funct7 = patch::FUNCT7.read(word);
+                        // shamt[5] only exists in RV64
+                        let shamt = arg::SHAMT6.read(word);
+                        let is_shift = funct3 == FUNCT3_OP_IMM::SLLI || funct3 == FUNCT3_OP_IMM::SRLI;
+                        if is_shift && shamt >= self.xlen {
+                            self.exception(ILLEGAL_INSTRUCTION);
+                            return;
+                        }
 
+                        let a = self.reg(rs1);
+                        let ua = self.unsigned(a);
                         let value = match funct3 {
-                            FUNCT3_OP_IMM::ADDI => self.reg(rs1).wrapping_add(imm),
-                            FUNCT3_OP_IMM::SLLI => self.reg(rs1) << (imm & 0x1F),
-                            FUNCT3_OP_IMM::SLTI => if self.reg(rs1) < imm { 1 } else { 0 },
-                            FUNCT3_OP_IMM::SLTIU => if (self.reg(rs1) as u32) < (imm as u32) { 1 } else { 0 },
-                            FUNCT3_OP_IMM::XORI => self.reg(rs1) ^ imm,
-                            FUNCT3_OP_IMM::SRLI => match funct7 {
-                                // SRLI
-                                0 => (self.reg(rs1) as u32 >> (imm & 0x1F)) as i32,
-                                // SRAI
-                                0x20 => self.reg(rs1) >> (imm & 0x1F),
+                            FUNCT3_OP_IMM::ADDI => a.wrapping_add(imm),
+                            FUNCT3_OP_IMM::SLLI => match read_opcode!(self, word, FUNCT6_OP_IMM_SLL, FUNCT6) {
+                                FUNCT6_OP_IMM_SLL::SLLI => a << shamt,
+                                FUNCT6_OP_IMM_SLL::BSETI => a | (1 << shamt),
+                                FUNCT6_OP_IMM_SLL::BCLRI => a & !(1 << shamt),
+                                FUNCT6_OP_IMM_SLL::BINVI => a ^ (1 << shamt),
+                                // The shift amount selects the operation
+                                FUNCT6_OP_IMM_SLL::UNARY => {
+                                    match RS2_OP_IMM_UNARY::from_u32(shamt) {
+                                        Some(RS2_OP_IMM_UNARY::CLZ) => (ua.leading_zeros() - (64 - self.xlen)) as i64,
+                                        Some(RS2_OP_IMM_UNARY::CTZ) => ua.trailing_zeros().min(self.xlen) as i64,
+                                        Some(RS2_OP_IMM_UNARY::CPOP) => ua.count_ones() as i64,
+                                        Some(RS2_OP_IMM_UNARY::SEXT_B) => a as i8 as i64,
+                                        Some(RS2_OP_IMM_UNARY::SEXT_H) => a as i16 as i64,
+                                        None => {
+                                            self.exception(ILLEGAL_INSTRUCTION);
+                                            return;
+                                        }
+                                    }
+                                }
+                            },
+                            FUNCT3_OP_IMM::SLTI => if a < imm { 1 } else { 0 },
+                            FUNCT3_OP_IMM::SLTIU => if ua < self.unsigned(imm) { 1 } else { 0 },
+                            FUNCT3_OP_IMM::XORI => a ^ imm,
+                            FUNCT3_OP_IMM::SRLI => match read_opcode!(self, word, FUNCT6_OP_IMM_SRL, FUNCT6) {
+                                FUNCT6_OP_IMM_SRL::SRLI => (ua >> shamt) as i64,
+                                FUNCT6_OP_IMM_SRL::SRAI => a >> shamt,
+                                FUNCT6_OP_IMM_SRL::BEXTI => (a >> shamt) & 0x1,
+                                FUNCT6_OP_IMM_SRL::RORI => self.rotate_left(a, self.xlen - shamt),
+                                // Both have a fixed shift amount
+                                FUNCT6_OP_IMM_SRL::ORC_B if shamt == SHAMT_ORC_B => {
+                                    (0..8).fold(0, |v, i| if (ua >> (i * 8)) & 0xFF != 0 { v | (0xFF << (i * 8)) } else { v })
+                                }
+                                FUNCT6_OP_IMM_SRL::REV8 if shamt == self.xlen - 8 => {
+                                    if self.xlen == 32 { (a as u32).swap_bytes() as i32 as i64 } else { a.swap_bytes() }
+                                }
                                 _ => {
                                     self.exception(ILLEGAL_INSTRUCTION);
                                     return;
                                 }
                             },
-                            FUNCT3_OP_IMM::ORI => self.reg(rs1) | imm,
-                            FUNCT3_OP_IMM::ANDI => self.reg(rs1) & imm,
+                            FUNCT3_OP_IMM::ORI => a | imm,
+                            FUNCT3_OP_IMM::ANDI => a & imm,
                         };
 
                         self.set_reg(rd, value);
                     }
 
+                    OPCODE::OP_IMM_32 => {
+                        if self.xlen != 64 {
+                            self.exception(ILLEGAL_INSTRUCTION);
+                            return;
+                        }
+
+                        let funct3 = read_opcode!(self, word, FUNCT3_OP_IMM_32, FUNCT3);
+
+                        let shamt = arg::SHAMT.read(word);
+                        let funct7 = patch::FUNCT7.read(word);
+
+                        let a = self.reg(rs1) as i32;
+                        let value = match (funct3, funct7) {
+                            (FUNCT3_OP_IMM_32::ADDIW, _) => a.wrapping_add(imm as i32),
+                            (FUNCT3_OP_IMM_32::SLLIW, 0) => a << shamt,
+                            (FUNCT3_OP_IMM_32::SRLIW, 0) => ((a as u32) >> shamt) as i32,
+                            (FUNCT3_OP_IMM_32::SRLIW, 0x20) => a >> shamt,
+                            // slli.uw has a 6-bit shift amount and a full width result
+                            (FUNCT3_OP_IMM_32::SLLIW, 0x04) | (FUNCT3_OP_IMM_32::SLLIW, 0x05) => {
+                                self.set_reg(rd, (a as u32 as i64) << arg::SHAMT6.read(word));
+                                return;
+                            }
+                            (FUNCT3_OP_IMM_32::SLLIW, 0x30) => {
+                                match RS2_OP_IMM_UNARY::from_u32(shamt) {
+                                    Some(RS2_OP_IMM_UNARY::CLZ) => (a as u32).leading_zeros() as i32,
+                                    Some(RS2_OP_IMM_UNARY::CTZ) => (a as u32).trailing_zeros() as i32,
+                                    Some(RS2_OP_IMM_UNARY::CPOP) => (a as u32).count_ones() as i32,
+                                    _ => {
+                                        self.exception(ILLEGAL_INSTRUCTION);
+                                        return;
+                                    }
+                                }
+                            }
+                            (FUNCT3_OP_IMM_32::SRLIW, 0x30) => (a as u32).rotate_right(shamt) as i32,
+                            _ => {
+                                self.exception(ILLEGAL_INSTRUCTION);
+                                return;
+                            }
+                        };
+
+                        self.set_reg(rd, value as i64);
+                    }
+
+                    // A single hart sees its own memory accesses in order, so fence has nothing to wait for
+                    OPCODE::MISC_MEM => {
+                        let funct3 = read_opcode!(self, word, FUNCT3_MISC_MEM, FUNCT3);
+                        match funct3 {
+                            FUNCT3_MISC_MEM::FENCE => (),
+                            FUNCT3_MISC_MEM::FENCE_I => self.icache.flush(),
+                        }
+                    }
+
                     OPCODE::JALR => {
-                        let link = self.pc.wrapping_add(4);
-                        self.set_reg(rd, link as i32);
-                        self.pc = (self.reg(rs1).wrapping_add(imm) as u32) & !0x1;
+                        let link = self.pc_offset(len);
+                        let target = self.unsigned(self.reg(rs1).wrapping_add(imm)) & !0x1;
+                        self.set_reg(rd, link as i64);
+                        self.jump(target);
                         return;
                     }
                     OPCODE::SYSTEM => {
                         let funct3 = read_opcode!(self, word, FUNCT3_SYSTEM, FUNCT3);
 
                         match funct3 {
+                            FUNCT3_SYSTEM::PRIV if patch::FUNCT7.read(word) == FUNCT7_SFENCE_VMA => {
+                                if rd != 0 || self.level < SUPERVISOR {
+                                    self.exception(ILLEGAL_INSTRUCTION);
+                                    return;
+                                }
+
+                                // Only the page of rs1 when given. ASIDs are not implemented
+                                let vaddr = if rs1 != 0 { Some(self.reg(rs1) as u32) } else { None };
+                                self.tlb.flush(vaddr);
+                            }
                             FUNCT3_SYSTEM::PRIV => {
                                 let funct12 = read_opcode!(self, word, FUNCT12_PRIV, FUNCT12);
 
@@ -322,7 +1281,9 @@ impl Cpu {
                                             return;
                                         }
 
-                                        panic!("Implement ECALL")
+                                        // mepc is the ecall itself. The handler skips it
+                                        self.exception(ECALL_BASE + self.level as u32);
+                                        return;
                                     }
                                     FUNCT12_PRIV::EBREAK => {
                                         if rs1 != 0 || rd != 0 {
@@ -330,7 +1291,9 @@ impl Cpu {
                                             return;
                                         }
 
-                                        panic!("Implement EBREAK, whatever it is")
+                                        let pc = self.pc;
+                                        self.exception_with_tval(BREAKPOINT, pc);
+                                        return;
                                     }
 
                                     // RET
@@ -355,7 +1318,6 @@ impl Cpu {
                                         return;
                                     }
                                     FUNCT12_PRIV::WFI => self.wfi = true,
-                                    FUNCT12_PRIV::SFENCEVM => panic!("implement please"),
                                 }
                             }
                             // CSR instructions
@@ -369,8 +1331,6 @@ impl Cpu {
                                     return;
                                 }
 
-                                //NOTE: Write accessibility is check is deferred. I am not sure about the right behavior
-
                                 // x0 special:
                                 // If rs1=x0, Rs and Rc will not write to the CSR at all.
                                 // If rd=x0, Rw will not read the CSR.
@@ -388,28 +1348,31 @@ impl Cpu {
                                     0 // don't matter
                                 };
 
-                                let v_rs1 = self.reg(rs1) as u32;
-                                let uimm = rs1 as u32; // Zero-extending
-
-                                // write_csr, .. helper functions affect system
-                                let result = match funct3 {
-                                    FUNCT3_SYSTEM::CSRRW => self.write_csr(csr, v_rs1),
-                                    FUNCT3_SYSTEM::CSRRS => if rs1 != 0 { self.write_csr(csr, r | v_rs1) } else { Ok(()) },
-                                    FUNCT3_SYSTEM::CSRRC => if rs1 != 0 { self.write_csr(csr, r & !v_rs1) } else { Ok(()) },
-                                    FUNCT3_SYSTEM::CSRRWI => self.write_csr(csr, uimm),
-                                    FUNCT3_SYSTEM::CSRRSI => if uimm != 0 { self.write_csr(csr, r | uimm) } else { Ok(()) },
-                                    FUNCT3_SYSTEM::CSRRCI => if uimm != 0 { self.write_csr(csr, r & !uimm) } else { Ok(()) },
-                                    _ =>  panic!("Should be statically impossible... I wish compiler were smarter about enums"),
+                                let v_rs1 = self.unsigned(self.reg(rs1));
+                                let uimm = rs1 as u64; // Zero-extending
+
+                                // Rs and Rc with x0 don't write, so they can read read-only CSRs
+                                let writes = match funct3 {
+                                    FUNCT3_SYSTEM::CSRRS | FUNCT3_SYSTEM::CSRRC | FUNCT3_SYSTEM::CSRRSI | FUNCT3_SYSTEM::CSRRCI => rs1 != 0,
+                                    _ => true,
                                 };
-                                match result {
-                                    Err(()) => {
-                                        self.exception(ILLEGAL_INSTRUCTION);
-                                        return;
-                                    }
-                                    _ => (),
+                                if writes && is_csr_readonly(csr) {
+                                    self.exception(ILLEGAL_INSTRUCTION);
+                                    return;
                                 }
 
-                                self.set_reg(rd, r as i32);
+                                // Written on commit, which raises illegal instruction if the CSR refuses it
+                                self.outcome.csr = match funct3 {
+                                    FUNCT3_SYSTEM::CSRRW => Some((csr, v_rs1)),
+                                    FUNCT3_SYSTEM::CSRRS => if rs1 != 0 { Some((csr, r | v_rs1)) } else { None },
+                                    FUNCT3_SYSTEM::CSRRC => if rs1 != 0 { Some((csr, r & !v_rs1)) } else { None },
+                                    FUNCT3_SYSTEM::CSRRWI => Some((csr, uimm)),
+                                    FUNCT3_SYSTEM::CSRRSI => if uimm != 0 { Some((csr, r | uimm)) } else { None },
+                                    FUNCT3_SYSTEM::CSRRCI => if uimm != 0 { Some((csr, r & !uimm)) } else { None },
+                                    _ =>  panic!("Should be statically impossible... I wish compiler were smarter about enums"),
+                                };
+
+                                self.set_reg(rd, r as i64);
                             }
                         }
                     }
@@ -417,88 +1380,68 @@ impl Cpu {
                 }
             }
 
-            INST_TYPE::S => {
-                assert_eq!(opcode, OPCODE::STORE);
+            INST_TYPE::S if opcode == OPCODE::STORE_FP => {
+                let funct3 = read_opcode!(self, word, FUNCT3_STORE_FP, FUNCT3);
 
-                let funct3 = read_opcode!(self, word, FUNCT3_STORE, FUNCT3);
-
-                // no misaligned store for now
                 let rs1 = arg::RS1.read(word) as u8;
                 let rs2 = arg::RS2.read(word) as u8;
-                let imm = arg::SIMM12.read(word);
+                let imm = arg::SIMM12.read(word) as i32 as i64;
 
-                let addr = (self.reg(rs1) as u32).wrapping_add(imm);
-                let addr_word_aligned = addr & !0x3;
-                let byte_offset = (addr & 0x3) as u8;
+                let addr = self.unsigned(self.reg(rs1).wrapping_add(imm));
 
-                // fault on misaligned store
-                if funct3 == FUNCT3_STORE::SH {
-                    if byte_offset != 0 && byte_offset != 2 {
-                        self.exception(STORE_ADDRESS_MISALIGNED);
-                        return;
-                    }
-                } else if funct3 == FUNCT3_STORE::SW {
-                    if byte_offset != 0 {
-                        self.exception(STORE_ADDRESS_MISALIGNED);
+                let size = if funct3 == FUNCT3_STORE_FP::FSW { 4 } else { 8 };
+                if addr & (size as u64 - 1) != 0 {
+                    self.memory_exception(addr, STORE_ADDRESS_MISALIGNED);
+                    return;
+                }
+
+                // fsw stores the low bits as they are, boxed or not
+                let value = self.fregs[rs2 as usize];
+                match self.store(bus, addr, size, value) {
+                    Ok(()) => (),
+                    Err(cause) => {
+                        self.memory_exception(addr, cause);
                         return;
                     }
                 }
+            }
 
-                let value = self.reg(rs2) as u32;
+            INST_TYPE::S => {
+                assert_eq!(opcode, OPCODE::STORE);
 
-                // CPU driven read and write back on smaller than byte write
-                match funct3 {
-                    FUNCT3_STORE::SB => {
-                        let read = match bus.read_word(addr_word_aligned) {
-                            Ok(v) => v,
-                            Err(()) => {
-                                self.exception(LOAD_ACCESS_FAULT); // ????????????????????? should it load or store access fault?
-                                return;
-                            }
-                        };
+                let funct3 = read_opcode!(self, word, FUNCT3_STORE, FUNCT3);
 
-                        let mask: u32 = 0xFF;
-                        let updated = (read & !(mask << byte_offset*8)) | ((value & mask) << byte_offset*8);
-                        match bus.write_word(addr_word_aligned, updated) {
-                            Ok(()) => (),
-                            Err(()) => {
-                                self.exception(STORE_ACCESS_FAULT);
-                                return;
-                            }
-                        }
-                    }
-                    FUNCT3_STORE::SH => {
-                        let read = match bus.read_word(addr_word_aligned) {
-                            Ok(v) => v,
-                            Err(()) => {
-                                self.exception(LOAD_ACCESS_FAULT); // ????????????????????? should it load or store access fault?
-                                return;
-                            }
-                        };
+                let rs1 = arg::RS1.read(word) as u8;
+                let rs2 = arg::RS2.read(word) as u8;
+                let imm = arg::SIMM12.read(word) as i32 as i64;
 
-                        let mask: u32 = 0xFFFF;
-                        let updated = (read & !(mask << byte_offset*8)) | ((value & mask) << byte_offset*8);
-                        match bus.write_word(addr_word_aligned, updated) {
-                            Ok(()) => (),
-                            Err(()) => {
-                                self.exception(STORE_ACCESS_FAULT);
-                                return;
-                            }
-                        }
-                    }
-                    FUNCT3_STORE::SW => {
-                        match bus.write_word(addr_word_aligned, value) {
-                            Ok(()) => (),
-                            Err(()) => {
-                                println!("STORE error with addr={:08X} addr_word_aligned={:08X}", addr, addr_word_aligned);
-                                self.exception(STORE_ACCESS_FAULT);
-                                return;
-                            }
-                        }
+                let addr = self.unsigned(self.reg(rs1).wrapping_add(imm));
+
+                let size = match funct3 {
+                    FUNCT3_STORE::SB => 1,
+                    FUNCT3_STORE::SH => 2,
+                    FUNCT3_STORE::SW => 4,
+                    FUNCT3_STORE::SD if self.xlen == 64 => 8,
+                    FUNCT3_STORE::SD => {
+                        self.exception(ILLEGAL_INSTRUCTION);
+                        return;
                     }
+                };
+
+                if addr & (size as u64 - 1) != 0 && !self.misaligned_access {
+                    self.memory_exception(addr, STORE_ADDRESS_MISALIGNED);
+                    return;
                 }
 
+                let value = self.reg(rs2) as u64;
 
+                match self.store(bus, addr, size, value) {
+                    Ok(()) => (),
+                    Err(cause) => {
+                        self.memory_exception(addr, cause);
+                        return;
+                    }
+                }
             }
 
             INST_TYPE::SB => {
@@ -506,7 +1449,7 @@ impl Cpu {
 
                 let rs1 = arg::RS1.read(word) as u8;
                 let rs2 = arg::RS2.read(word) as u8;
-                let imm = arg::BIMM12.read(word);
+                let imm = arg::BIMM12.read(word) as i32 as i64;
 
                 let v_rs1 = self.reg(rs1);
                 let v_rs2 = self.reg(rs2);
@@ -517,25 +1460,28 @@ impl Cpu {
                     FUNCT3_BRANCH::BNE => v_rs1 != v_rs2,
                     FUNCT3_BRANCH::BLT => v_rs1 < v_rs2,
                     FUNCT3_BRANCH::BGE => v_rs1 >= v_rs2,
-                    FUNCT3_BRANCH::BLTU => (v_rs1 as u32) < (v_rs2 as u32),
-                    FUNCT3_BRANCH::BGEU => (v_rs1 as u32) >= (v_rs2 as u32),
+                    FUNCT3_BRANCH::BLTU => self.unsigned(v_rs1) < self.unsigned(v_rs2),
+                    FUNCT3_BRANCH::BGEU => self.unsigned(v_rs1) >= self.unsigned(v_rs2),
                 };
 
                 if branch {
-                    self.pc = self.pc.wrapping_add(imm);
+                    self.outcome.events.push(HPM_EVENT_TAKEN_BRANCHES);
+                    let target = self.pc_offset(imm);
+                    self.jump(target);
                     return;
                 }
             }
 
             INST_TYPE::U => {
                 let rd = arg::RD.read(word) as u8;
-                let imm = arg::IMM20.read(word);
+                // Sign-extended in RV64
+                let imm = arg::IMM20.read(word) as i32 as i64;
 
                 let value = match opcode {
                     OPCODE::LUI => imm,
-                    OPCODE::AUIPC => imm.wrapping_add(self.pc),
+                    OPCODE::AUIPC => self.pc_offset(imm) as i64,
                     _ => panic!("Statically impossible"),
-                } as i32;
+                };
 
                 self.set_reg(rd, value);
             }
@@ -543,11 +1489,12 @@ impl Cpu {
             INST_TYPE::UJ => {
                 assert_eq!(opcode, OPCODE::JAL);
                 let rd = arg::RD.read(word) as u8;
-                let imm = arg::JIMM20.read(word);
+                let imm = arg::JIMM20.read(word) as i32 as i64;
 
-                let link =self.pc.wrapping_add(4);
-                self.set_reg(rd, link as i32);
-                self.pc = self.pc.wrapping_add(imm);
+                let link = self.pc_offset(len);
+                let target = self.pc_offset(imm);
+                self.set_reg(rd, link as i64);
+                self.jump(target);
                 return;
             }
         }
@@ -555,15 +1502,87 @@ impl Cpu {
         // No jump/branch path come here
 
         // Advance pc
-        self.pc = self.pc.wrapping_add(4);
+        self.outcome.pc = Some(self.pc_offset(len));
+    }
+
+
+    // Memory accesses from the hart go through these. addr is virtual. Err is the exception cause.
+    // RV64 has no translation, and nothing exists beyond the 32-bit bus
+    fn translate(&mut self, bus: &mut MasterBusEnd, addr: u64, access: Access) -> Result<u32, u32> {
+        if addr > ::std::u32::MAX as u64 {
+            return Err(access.access_fault());
+        }
+        let paddr = mmu::translate(self, bus, addr as u32, access)?;
+
+        let level = mmu::effective_level(self, access);
+        if !pmp::check(self, paddr & !0x3, access, level) {
+            return Err(access.access_fault());
+        }
+
+        Ok(paddr)
+    }
+
+    // One bus transaction of size bytes, naturally aligned
+    fn read(&mut self, bus: &mut MasterBusEnd, addr: u64, size: u32, access: Access) -> Result<u32, u32> {
+        let paddr = self.translate(bus, addr, access)?;
+        self.count_bus_access(bus, paddr);
+        bus.read(paddr, size).map_err(|()| access.access_fault())
     }
 
+    // size bytes at addr, in the low bits. An aligned access up to a word is one transaction of
+    // its size. Others are split into the largest aligned pieces, each translated on its own, so
+    // a misaligned access may cross pages and peripherals
+    fn load(&mut self, bus: &mut MasterBusEnd, addr: u64, size: u32, access: Access) -> Result<u64, u32> {
+        self.outcome.events.push(HPM_EVENT_LOADS);
+        let mut read = 0u64;
+        let mut offset = 0;
+        while offset < size {
+            let piece_addr = self.wrap(addr + offset as u64);
+            let piece = piece_size(piece_addr, size - offset);
+            read |= (self.read(bus, piece_addr, piece, access)? as u64) << (offset * 8);
+            offset += piece;
+        }
+
+        Ok(read)
+    }
+
+    // In the same pieces as load. Nothing is read back, the bus only changes the bytes written
+    fn store(&mut self, bus: &mut MasterBusEnd, addr: u64, size: u32, value: u64) -> Result<(), u32> {
+        let mut offset = 0;
+        while offset < size {
+            let piece_addr = self.wrap(addr + offset as u64);
+            let piece = piece_size(piece_addr, size - offset);
+            self.write(bus, piece_addr, piece, (value >> (offset * 8)) as u32)?;
+            offset += piece;
+        }
+
+        Ok(())
+    }
+
+    // Translated and checked now, written on commit
+    fn write(&mut self, bus: &mut MasterBusEnd, addr: u64, size: u32, value: u32) -> Result<(), u32> {
+        let paddr = self.translate(bus, addr, Access::Store)?;
+        if !bus.is_writable(paddr) {
+            return Err(STORE_ACCESS_FAULT);
+        }
+        let value = value & mask(size) as u32;
+        self.outcome.stores.push(Store { addr: addr, paddr: paddr, size: size, value: value });
+        Ok(())
+    }
+
+    // Faults on memory accesses report the address
+    fn memory_exception(&mut self, addr: u64, cause: u32) {
+        self.exception_with_tval(cause, addr);
+    }
 
     // Call to SYSTEM
     // This has to look like this because I don't know how to impl a struct across multiple files
 
-    //TODO mtval should be cleared for exceptions that don't set it
     fn exception(&mut self, cause: u32) {
+        self.exception_with_tval(cause, 0);
+    }
+
+    fn exception_with_tval(&mut self, cause: u32, tval: u64) {
         // Convenience debug measure
         if self.die_on_exception {
             // Print pc trail
@@ -578,7 +1597,7 @@ impl Cpu {
             panic!("dying on exception with pc={:08X}, cause={}, word={:08X}, num_cycles={}", self.pc, cause, self.last_word, self.num_cycles);
         }
 
-        exception(self, cause);
+        exception(self, cause, tval);
     }
 
     fn is_interrupt_possible(&self, level: u8) -> bool {
@@ -589,18 +1608,18 @@ impl Cpu {
         ret(self, from);
     }
 
-    fn trap(&mut self, to: u8) {
-        trap(self, to);
+    fn trap(&mut self, to: u8, cause: u32, tval: u64) {
+        trap(self, to, cause, tval);
     }
 
     // CSR instructions helpers
     // They handle exceptional themselves
-    fn read_csr(&mut self, csr: u32) -> Result<u32, ()> {
+    fn read_csr(&mut self, csr: u32) -> Result<u64, ()> {
         read_csr(self, csr)
     }
 
     // I wish ? operator were more general...
-    fn write_csr(&mut self, csr: u32, v: u32) -> Result<(), ()> {
+    fn write_csr(&mut self, csr: u32, v: u64) -> Result<(), ()> {
         if is_csr_readonly(csr) {
             return Err(());
         }
@@ -608,6 +1627,16 @@ impl Cpu {
     }
 }
 
-fn shamtify(v: i32) -> u8 {
-    (v & 0x1F) as u8
+// The largest transaction that starts at addr, is aligned to its size, and doesn't go past the end
+fn piece_size(addr: u64, remaining: u32) -> u32 {
+    let mut size = 4;
+    while addr & (size as u64 - 1) != 0 || size > remaining {
+        size /= 2;
+    }
+    size
+}
+
+// The low size bytes
+fn mask(size: u32) -> u64 {
+    if size == 8 { !0 } else { (1u64 << (size * 8)) - 1 }
 }
\ No newline at end of file
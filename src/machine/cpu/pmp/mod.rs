@@ -0,0 +1,106 @@
+// Physical memory protection. The first matching entry decides
+
+#[cfg(test)]
+mod test;
+
+use super::*;
+use super::mmu::Access;
+
+fn mode(cfg: u8) -> u8 {
+    (cfg >> PMP_A) & 0x3
+}
+
+fn is_locked(cpu: &Cpu, i: usize) -> bool {
+    cpu.pmpcfg[i] & PMP_L != 0
+}
+
+// Byte range [start, end) of the entry. Addresses are 34 bits
+fn range(cpu: &Cpu, i: usize) -> Option<(u64, u64)> {
+    let addr = (cpu.pmpaddr[i] as u64) << 2;
+    match mode(cpu.pmpcfg[i]) {
+        PMP_TOR => {
+            let start = if i == 0 { 0 } else { (cpu.pmpaddr[i - 1] as u64) << 2 };
+            Some((start, addr))
+        }
+        PMP_NA4 => Some((addr, addr + 4)),
+        PMP_NAPOT => {
+            // Trailing ones give the size, 8 bytes and up
+            let ones = cpu.pmpaddr[i].trailing_ones();
+            let size = 8u64 << ones;
+            let start = addr & !(size - 1);
+            Some((start, start + size))
+        }
+        _ => None,
+    }
+}
+
+// Whether a word access at addr is allowed. level is the effective privilege
+pub fn check(cpu: &Cpu, addr: u32, access: Access, level: u8) -> bool {
+    let start = addr as u64;
+    let end = start + 4;
+
+    for i in 0..PMP_ENTRIES {
+        let (lo, hi) = match range(cpu, i) {
+            Some(r) => r,
+            None => continue,
+        };
+
+        // Not touching this entry
+        if end <= lo || start >= hi {
+            continue;
+        }
+
+        // Partially matching accesses fail
+        if start < lo || end > hi {
+            return false;
+        }
+
+        if level == MACHINE && !is_locked(cpu, i) {
+            return true;
+        }
+
+        let cfg = cpu.pmpcfg[i];
+        return match access {
+            Access::Fetch => cfg & PMP_X != 0,
+            Access::Load => cfg & PMP_R != 0,
+            Access::Store => cfg & PMP_W != 0,
+        };
+    }
+
+    level == MACHINE
+}
+
+// CSRs. n is the index of pmpcfgn or pmpaddrn. In RV64 only the even pmpcfgs exist, with 8 entries each
+
+fn cfg_entries(cpu: &Cpu) -> usize {
+    cpu.xlen as usize / 8
+}
+
+pub fn read_cfg(cpu: &Cpu, n: usize) -> u64 {
+    (0..cfg_entries(cpu)).fold(0, |v, j| v | (cpu.pmpcfg[n * 4 + j] as u64) << (j * 8))
+}
+
+pub fn write_cfg(cpu: &mut Cpu, n: usize, v: u64) {
+    for j in 0..cfg_entries(cpu) {
+        let i = n * 4 + j;
+        if is_locked(cpu, i) {
+            continue;
+        }
+
+        // W without R is reserved
+        let mut cfg = (v >> (j * 8)) as u8 & (PMP_L | 0x3 << PMP_A | PMP_X | PMP_W | PMP_R);
+        if cfg & PMP_R == 0 {
+            cfg &= !PMP_W;
+        }
+        cpu.pmpcfg[i] = cfg;
+    }
+}
+
+pub fn write_addr(cpu: &mut Cpu, n: usize, v: u32) {
+    // A locked TOR entry also locks the address below it
+    let top_locked = n + 1 < PMP_ENTRIES && is_locked(cpu, n + 1) && mode(cpu.pmpcfg[n + 1]) == PMP_TOR;
+    if is_locked(cpu, n) || top_locked {
+        return;
+    }
+    cpu.pmpaddr[n] = v;
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+fn napot(base: u32, size: u32) -> u32 {
+    (base >> 2) | ((size >> 3) - 1)
+}
+
+#[test]
+fn test_match() {
+    let mut cpu = Cpu::new();
+
+    // Nothing matches
+    assert!(!check(&cpu, 0x0, Access::Load, USER));
+    assert!(!check(&cpu, 0x0, Access::Load, SUPERVISOR));
+    assert!(check(&cpu, 0x0, Access::Store, MACHINE));
+
+    // [0, 0x1000) read and execute
+    cpu.pmpcfg[0] = (PMP_TOR << PMP_A) | PMP_R | PMP_X;
+    cpu.pmpaddr[0] = 0x1000 >> 2;
+    assert!(check(&cpu, 0xFFC, Access::Load, USER));
+    assert!(check(&cpu, 0xFFC, Access::Fetch, USER));
+    assert!(!check(&cpu, 0xFFC, Access::Store, USER));
+    assert!(!check(&cpu, 0x1000, Access::Load, USER));
+
+    // [0x1000, 0x1800) from the entry below
+    cpu.pmpcfg[1] = (PMP_TOR << PMP_A) | PMP_R | PMP_W;
+    cpu.pmpaddr[1] = 0x1800 >> 2;
+    assert!(check(&cpu, 0x1000, Access::Store, USER));
+    assert!(!check(&cpu, 0x1800, Access::Store, USER));
+
+    // The lower entry wins
+    cpu.pmpcfg[2] = PMP_NA4 << PMP_A;
+    cpu.pmpaddr[2] = 0x2000 >> 2;
+    cpu.pmpcfg[3] = (PMP_NAPOT << PMP_A) | PMP_R | PMP_W;
+    cpu.pmpaddr[3] = napot(0x2000, 0x100);
+    assert!(!check(&cpu, 0x2000, Access::Load, SUPERVISOR));
+    assert!(check(&cpu, 0x2004, Access::Load, SUPERVISOR));
+    assert!(check(&cpu, 0x20FC, Access::Store, SUPERVISOR));
+    assert!(!check(&cpu, 0x2100, Access::Load, SUPERVISOR));
+
+    // M-mode ignores unlocked entries
+    assert!(check(&cpu, 0x2000, Access::Load, MACHINE));
+    cpu.pmpcfg[2] |= PMP_L;
+    assert!(!check(&cpu, 0x2000, Access::Load, MACHINE));
+}
+
+#[test]
+fn test_csr() {
+    let mut cpu = Cpu::new();
+
+    // W without R is reserved
+    write_cfg(&mut cpu, 0, 0x9D1A0E03);
+    assert_eq!(read_cfg(&cpu, 0), 0x9D180C03);
+    assert_eq!(cpu.pmpcfg[3], PMP_L | (PMP_NAPOT << PMP_A) | PMP_X | PMP_R);
+
+    // Entry 3 is locked
+    write_cfg(&mut cpu, 0, 0);
+    assert_eq!(read_cfg(&cpu, 0), 0x9D000000);
+    write_addr(&mut cpu, 3, 0x1234);
+    assert_eq!(cpu.pmpaddr[3], 0);
+
+    // A locked TOR entry locks the address below it
+    write_addr(&mut cpu, 4, 0x100);
+    write_cfg(&mut cpu, 1, (PMP_L | PMP_TOR << PMP_A) as u64);
+    write_addr(&mut cpu, 4, 0x200);
+    write_addr(&mut cpu, 3, 0x200);
+    assert_eq!(cpu.pmpaddr[4], 0x100);
+    assert_eq!(cpu.pmpaddr[3], 0);
+    write_addr(&mut cpu, 5, 0x300);
+    assert_eq!(cpu.pmpaddr[5], 0x300);
+}
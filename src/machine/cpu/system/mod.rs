@@ -1,11 +1,10 @@
 
 use super::*;
+use super::pmp;
 use ::arch::system::*;
 
 // Interrupt, exception
 
-pub const MTVEC_VALUE: u32 = 0x00000010;
-
 pub fn is_interrupt_possible(cpu: &Cpu, to: u8) -> bool {
     if to > cpu.level {
         return true;
@@ -16,70 +15,112 @@ pub fn is_interrupt_possible(cpu: &Cpu, to: u8) -> bool {
     return (cpu.status >> (IE_BASE + cpu.level)) & 0x1 == 1
 }
 
-pub fn interrupt(cpu: &mut Cpu, to: u8, cause: u32) {
-    cpu.cause = cause | (1u32 << INTERRUPT);
+// Standard priority. Interrupts to a higher level still come first
+const INTERRUPT_PRIORITY: [u8; 9] = [MEIP, MSIP, MTIP, SEIP, SSIP, STIP, UEIP, USIP, UTIP];
+
+// Level that takes the interrupt of the given mip bit
+fn interrupt_level(cpu: &Cpu, bit: u8) -> u8 {
+    if (cpu.mideleg >> bit) & 0x1 == 1 {
+        SUPERVISOR
+    } else {
+        MACHINE
+    }
+}
 
-    cpu.trap(to);
+// Highest priority interrupt that is pending, enabled and taken by the level.
+// mip bits are the exception codes
+pub fn pending_interrupt(cpu: &Cpu, to: u8) -> Option<u32> {
+    let pending = cpu.ip & cpu.ie;
+    INTERRUPT_PRIORITY.iter()
+        .find(|&&bit| (pending >> bit) & 0x1 == 1 && interrupt_level(cpu, bit) == to)
+        .map(|&bit| bit as u32)
 }
 
-// Currently, to is Machine
-pub fn trap(cpu: &mut Cpu, to: u8) {
+pub fn interrupt(cpu: &mut Cpu, to: u8, cause: u32) {
+    cpu.trap(to, cause | (1u32 << INTERRUPT), 0);
+}
+
+// to is Machine, or Supervisor for delegated traps
+pub fn trap(cpu: &mut Cpu, to: u8, cause: u32, tval: u64) {
     assert!(to >= cpu.level);
 
     /*
+    mstatus[toPIE] <- mstatus[toIE]
     mstatus[toIE] <- 0
-
     mstatus[toPP] <- prev level
-    mstatus[toPIE] <- mstatus[prevIE]
     level <- to level
 
-    mepc <- pc + 4
+    xepc <- pc
+    xcause, xtval <- cause, tval
     pc <- trap vector
     */
 
+    // Whatever was running loses its reservation, so an sc after a context switch fails
+    cpu.reservation = None;
+
+    let ie = (cpu.status >> (IE_BASE + to)) & 0x1;
+    cpu.status &= !(1u32 << (PIE_BASE + to));
+    cpu.status |= ie << (PIE_BASE + to);
     cpu.status &= !(1u32 << (IE_BASE + to));
 
-    if to == USER {
-        // nop
-    } else if to == SUPERVISOR {
+    if to == SUPERVISOR {
         cpu.status &= !(1u32 << SPP);
         cpu.status |= (cpu.level as u32) << SPP;
+
+        cpu.sepc = cpu.pc;
+        cpu.scause = cause;
+        cpu.stval = tval;
+        cpu.pc = trap_vector(cpu.stvec, cause);
     } else if to == MACHINE {
         cpu.status &= !(3u32 << MPP);
         cpu.status |= (cpu.level as u32) << MPP;
-    }
 
-    let prev_ie = (cpu.status >> (IE_BASE + cpu.level)) & 0x1;
-
-    cpu.status &= !(1u32 << (PIE_BASE + cpu.level));
-    cpu.status |= prev_ie << (PIE_BASE + cpu.level);
+        cpu.epc = cpu.pc;
+        cpu.cause = cause;
+        cpu.mtval = tval;
+        cpu.pc = trap_vector(cpu.mtvec, cause);
+    } else {
+        panic!("to: {}", to)
+    }
 
     cpu.level = to;
+    cpu.trapped = true;
+    cpu.count_event(HPM_EVENT_TRAPS);
+}
 
-    cpu.epc = cpu.pc;
-
-    cpu.pc = cpu.mtvec & !0x3;
+// Vectored mode sends interrupts to BASE + 4 * code. Exceptions always go to BASE
+fn trap_vector(tvec: u64, cause: u32) -> u64 {
+    let base = tvec & !0x3;
+    if tvec & 0x3 == MTVEC_VECTORED as u64 && (cause >> INTERRUPT) & 0x1 == 1 {
+        base.wrapping_add(((cause & !(1u32 << INTERRUPT)) << 2) as u64)
+    } else {
+        base
+    }
 }
 
-pub fn exception(cpu: &mut Cpu, cause: u32) {
+// Exceptions below M go to S when medeleg says so
+pub fn exception(cpu: &mut Cpu, cause: u32, tval: u64) {
     debug!("exception: cause={:08X} pc={:08X}", cause, cpu.pc);
 
-    cpu.cause = cause;
-    cpu.trap(MACHINE);
+    let to = if cpu.level < MACHINE && (cpu.medeleg >> cause) & 0x1 == 1 {
+        SUPERVISOR
+    } else {
+        MACHINE
+    };
+    cpu.trap(to, cause, tval);
 }
 
 // from can't be higher than the current level
 pub fn ret(cpu: &mut Cpu, from: u8) {
     /*
     prev <- mstatus[from's PP]
-    prevIE <- mstatus[from's PIE]
-    mstatus[prev's IE] <- prevIE
+    mstatus[from's IE] <- mstatus[from's PIE]
     Level <- prev
 
     mstatus[from's PIE] <- 1
     mstatus[from's PP] <- User, or Machine if User is not supported. Note WLRL
 
-    pc <- mepc
+    pc <- xepc
     */
 
     // Get prev fromPP and replace with USER
@@ -98,14 +139,10 @@ pub fn ret(cpu: &mut Cpu, from: u8) {
         panic!("from: {}", from)
     }
 
-    assert!(prev == USER || prev == SUPERVISOR || prev == MACHINE);
-
-    // prevIE <- mstatus[from's PIE]
+    // mstatus[from's IE] <- mstatus[from's PIE]
     let prev_ie = (cpu.status >> (PIE_BASE + from)) & 0x1;
-
-    // mstatus[prev's IE] <- prevIE
-    let prev_ie_patch = patch::Patch { offset: IE_BASE + prev, length: 1 };
-    prev_ie_patch.write_on_ref(&mut cpu.status, prev_ie);
+    let ie_patch = patch::Patch { offset: IE_BASE + from, length: 1 };
+    ie_patch.write_on_ref(&mut cpu.status, prev_ie);
 
     // Level <- prev
     cpu.level = prev;
@@ -114,59 +151,298 @@ pub fn ret(cpu: &mut Cpu, from: u8) {
     let from_pie_patch = patch::Patch { offset: PIE_BASE + from, length: 1 };
     from_pie_patch.write_on_ref(&mut cpu.status, 1);
 
-    // pc <- mepc
-    cpu.pc = cpu.epc;
+    // pc <- xepc
+    cpu.pc = if from == SUPERVISOR { cpu.sepc } else { cpu.epc };
 }
 
 // CSRs
 
 // Accessibility are checked from cycle
 
-pub fn read_csr(cpu: &mut Cpu, csr: u32) -> Result<u32, ()> {
+// Values are XLEN bits. Most state is 32 bits either way
+pub fn read_csr(cpu: &mut Cpu, csr: u32) -> Result<u64, ()> {
+    if let Some((kind, n)) = hpm_csr(csr) {
+        return read_hpm(cpu, csr, kind, n);
+    }
+
     let csr = match CSR::from_u32(csr) {
         Some(csr) => csr,
         None => return Err(()),
     };
 
+    // The high halves of counters and the odd pmpcfgs are RV32 only
+    if cpu.xlen == 64 && is_rv32_only(csr) {
+        return Err(());
+    }
+    if is_float(csr) && !cpu.isa.has('F') {
+        return Err(());
+    }
+
     let value = match csr {
-        CSR::MCYCLE => cpu.cycle as u32,
-        CSR::MCYCLEH => (cpu.cycle >> 32) as u32,
+        CSR::FFLAGS => cpu.fflags as u64,
+        CSR::FRM => cpu.frm as u64,
+        CSR::FCSR => ((cpu.frm << 5) | cpu.fflags) as u64,
+
+        // User shadows of the machine counters
+        CSR::CYCLE | CSR::TIME | CSR::INSTRET | CSR::CYCLEH | CSR::TIMEH | CSR::INSTRETH => {
+            if !is_counter_enabled(cpu, csr as u32) {
+                return Err(());
+            }
+
+            match csr {
+                CSR::CYCLE => cpu.cycle,
+                CSR::CYCLEH => cpu.cycle >> 32,
+                CSR::TIME => cpu.time,
+                CSR::TIMEH => cpu.time >> 32,
+                CSR::INSTRET => cpu.instret,
+                _ => cpu.instret >> 32,
+            }
+        }
+
+        CSR::MCYCLE => cpu.cycle,
+        CSR::MCYCLEH => cpu.cycle >> 32,
+        CSR::MINSTRET => cpu.instret,
+        CSR::MINSTRETH => cpu.instret >> 32,
 
         // Machine specs
-        //TODO
-
-        CSR::MSTATUS => cpu.status,
+        CSR::MVENDORID => 0, // Non-commercial
+        CSR::MARCHID => 0,
+        CSR::MIMPID => 0,
+        CSR::MHARTID => 0,
+
+        // Supervisor views of the machine registers
+        CSR::SSTATUS => status_with_xl(cpu, cpu.status & SSTATUS_MASK, false),
+        CSR::SIE => (cpu.ie & cpu.mideleg) as u64,
+        CSR::SIP => (cpu.ip & cpu.mideleg) as u64,
+
+        CSR::STVEC => cpu.stvec,
+        CSR::SCOUNTEREN => cpu.scounteren as u64,
+        CSR::SSCRATCH => cpu.sscratch,
+        CSR::SEPC => if cpu.has_extension('C') { cpu.sepc } else { cpu.sepc & !0x3 },
+        CSR::SCAUSE => cause_to_xlen(cpu, cpu.scause),
+        CSR::STVAL => cpu.stval,
+        CSR::SATP => cpu.satp as u64,
+
+        CSR::MSTATUS => status_with_xl(cpu, cpu.status, true),
+        CSR::MISA => cpu.misa,
+        CSR::MEDELEG => cpu.medeleg as u64,
+        CSR::MIDELEG => cpu.mideleg as u64,
+        CSR::MTVEC => cpu.mtvec,
+        CSR::MCOUNTEREN => cpu.mcounteren as u64,
+
+        // Trap handling
+        CSR::MSCRATCH => cpu.scratch,
+        // Bit 1 reads as zero while there are no 16-bit instructions
+        CSR::MEPC => if cpu.has_extension('C') { cpu.epc } else { cpu.epc & !0x3 },
+        CSR::MCAUSE => cause_to_xlen(cpu, cpu.cause),
+        CSR::MTVAL => cpu.mtval,
 
         // Exceptions
-        CSR::MIP => cpu.ip,
-        CSR::MIE => cpu.ie,
+        CSR::MIP => cpu.ip as u64,
+        CSR::MIE => cpu.ie as u64,
+
+        // Protection
+        CSR::PMPCFG0 | CSR::PMPCFG1 | CSR::PMPCFG2 | CSR::PMPCFG3 => pmp::read_cfg(cpu, pmpcfg_index(csr)),
+        _ if is_pmpaddr(csr) => cpu.pmpaddr[pmpaddr_index(csr)] as u64,
 
-        _ => panic!("implement me"),
+        _ => return Err(()),
     };
+
+    // The 32-bit views of counters
+    let value = if cpu.xlen == 32 { value as u32 as u64 } else { value };
     Ok(value)
 }
 
-pub fn write_csr(cpu: &mut Cpu, csr: u32, v: u32) -> Result<(), ()> {
+pub fn write_csr(cpu: &mut Cpu, csr: u32, v: u64) -> Result<(), ()> {
+    if let Some((kind, n)) = hpm_csr(csr) {
+        return write_hpm(cpu, kind, n, v);
+    }
+
     let csr = match CSR::from_u32(csr) {
         Some(csr) => csr,
         None => return Err(()),
     };
 
+    if cpu.xlen == 64 && is_rv32_only(csr) {
+        return Err(());
+    }
+    if is_float(csr) && !cpu.isa.has('F') {
+        return Err(());
+    }
+
+    // Full width for the counters and the registers that hold addresses
+    let wide = v;
+    let v = v as u32;
+
     match csr {
-        CSR::MSTATUS => cpu.status = v,
-        CSR::MIP => panic!("implement me!"),
-        CSR::MIE => cpu.ie = v,
-        _ => panic!("implement me!"),
+        CSR::FFLAGS => cpu.fflags = v & 0x1F,
+        CSR::FRM => cpu.frm = v & 0x7,
+        CSR::FCSR => {
+            cpu.fflags = v & 0x1F;
+            cpu.frm = (v >> 5) & 0x7;
+        }
+
+        CSR::MCYCLE if cpu.xlen == 64 => cpu.cycle = wide,
+        CSR::MCYCLE => cpu.cycle = (cpu.cycle & !0xFFFFFFFF) | v as u64,
+        CSR::MCYCLEH => cpu.cycle = (cpu.cycle & 0xFFFFFFFF) | (v as u64) << 32,
+        CSR::MINSTRET if cpu.xlen == 64 => cpu.instret = wide,
+        CSR::MINSTRET => cpu.instret = (cpu.instret & !0xFFFFFFFF) | v as u64,
+        CSR::MINSTRETH => cpu.instret = (cpu.instret & 0xFFFFFFFF) | (v as u64) << 32,
+
+        CSR::SSTATUS => cpu.status = (cpu.status & !SSTATUS_MASK) | (v & SSTATUS_MASK),
+        // Only delegated interrupts are visible. Of the pending bits, software can raise SSIP
+        CSR::SIE => cpu.ie = (cpu.ie & !cpu.mideleg) | (v & cpu.mideleg),
+        CSR::SIP => {
+            let mask = cpu.mideleg & (1 << SSIP);
+            cpu.ip = (cpu.ip & !mask) | (v & mask);
+        }
+
+        CSR::STVEC => cpu.stvec = tvec(wide, cpu.stvec),
+        CSR::SCOUNTEREN => cpu.scounteren = v,
+        CSR::SSCRATCH => cpu.sscratch = wide,
+        CSR::SEPC => cpu.sepc = wide & !0x1,
+        CSR::SCAUSE => cpu.scause = cause_from_xlen(cpu, wide),
+        CSR::STVAL => cpu.stval = wide,
+        // RV64 is Bare only. Writing an unsupported mode has no effect
+        CSR::SATP if cpu.xlen == 64 => if wide >> 60 == MBARE as u64 { cpu.satp = 0 },
+        // No ASID bits. Changing the mode or table needs sfence.vma as on hardware
+        CSR::SATP => cpu.satp = v & !(0x1FF << SATP_ASID),
+
+        CSR::MSTATUS => {
+            // MPP is WARL. The reserved level 2 leaves it as it was
+            let mpp = if (v >> MPP) & 0x3 == 2 { cpu.status } else { v };
+            let mpp_mask = 0x3 << MPP;
+            cpu.status = (v & MSTATUS_MASK & !mpp_mask) | (mpp & mpp_mask);
+        }
+        CSR::MISA => {
+            // Only C can be switched, if the machine has it, and not off while the next instruction
+            // is misaligned
+            let c = extension('C') as u64;
+            if cpu.isa.has('C') && (wide & c != 0 || cpu.pc & 0x3 == 0) {
+                cpu.misa = (cpu.misa & !c) | (wide & c);
+            }
+        }
+        CSR::MEDELEG => cpu.medeleg = v & MEDELEG_MASK,
+        CSR::MIDELEG => cpu.mideleg = v & MIDELEG_MASK,
+        CSR::MTVEC => cpu.mtvec = tvec(wide, cpu.mtvec),
+        CSR::MCOUNTEREN => cpu.mcounteren = v,
+
+        CSR::MSCRATCH => cpu.scratch = wide,
+        CSR::MEPC => cpu.epc = wide & !0x1,
+        CSR::MCAUSE => cpu.cause = cause_from_xlen(cpu, wide),
+        CSR::MTVAL => cpu.mtval = wide,
+
+        CSR::MIP => cpu.ip = (cpu.ip & !MIP_MASK) | (v & MIP_MASK),
+        CSR::MIE => cpu.ie = v & MIE_MASK,
+
+        CSR::PMPCFG0 | CSR::PMPCFG1 | CSR::PMPCFG2 | CSR::PMPCFG3 => pmp::write_cfg(cpu, pmpcfg_index(csr), wide),
+        _ if is_pmpaddr(csr) => pmp::write_addr(cpu, pmpaddr_index(csr), v),
+
+        _ => return Err(()),
     }
 
     Ok(())
 }
 
+// Counters 3 to 31, and what they count. High halves are RV32 only
+fn read_hpm(cpu: &Cpu, csr: u32, kind: HPM_CSR, n: u32) -> Result<u64, ()> {
+    let i = (n - HPM_FIRST) as usize;
+    let value = match kind {
+        HPM_CSR::COUNTERH | HPM_CSR::MCOUNTERH if cpu.xlen == 64 => return Err(()),
+        HPM_CSR::COUNTER | HPM_CSR::COUNTERH if !is_counter_enabled(cpu, csr) => return Err(()),
+        HPM_CSR::COUNTER | HPM_CSR::MCOUNTER => cpu.hpmcounter[i],
+        HPM_CSR::COUNTERH | HPM_CSR::MCOUNTERH => cpu.hpmcounter[i] >> 32,
+        HPM_CSR::MEVENT => cpu.hpmevent[i],
+    };
+
+    let value = if cpu.xlen == 32 { value as u32 as u64 } else { value };
+    Ok(value)
+}
+
+fn write_hpm(cpu: &mut Cpu, kind: HPM_CSR, n: u32, v: u64) -> Result<(), ()> {
+    let i = (n - HPM_FIRST) as usize;
+    let counter = cpu.hpmcounter[i];
+    match kind {
+        HPM_CSR::COUNTERH | HPM_CSR::MCOUNTERH if cpu.xlen == 64 => return Err(()),
+        HPM_CSR::MCOUNTER if cpu.xlen == 64 => cpu.hpmcounter[i] = v,
+        HPM_CSR::MCOUNTER => cpu.hpmcounter[i] = (counter & !0xFFFFFFFF) | v as u32 as u64,
+        HPM_CSR::MCOUNTERH => cpu.hpmcounter[i] = (counter & 0xFFFFFFFF) | (v as u32 as u64) << 32,
+        HPM_CSR::MEVENT => cpu.hpmevent[i] = if cpu.xlen == 32 { v as u32 as u64 } else { v },
+        // The user shadows are read-only
+        _ => return Err(()),
+    }
+    Ok(())
+}
+
+// Direct or vectored. Reserved modes leave the mode as it was
+fn tvec(v: u64, old: u64) -> u64 {
+    let mode = if v & 0x3 <= MTVEC_VECTORED as u64 { v & 0x3 } else { old & 0x3 };
+    (v & !0x3) | mode
+}
+
+// RV64 has UXL and SXL, fixed to 64. sstatus only shows UXL
+fn status_with_xl(cpu: &Cpu, status: u32, machine: bool) -> u64 {
+    if cpu.xlen == 32 {
+        return status as u64;
+    }
+    let xl = XLEN_64 as u64;
+    let sxl = if machine { xl << SXL } else { 0 };
+    status as u64 | (xl << UXL) | sxl
+}
+
+// The interrupt bit is the top bit of xcause
+fn cause_to_xlen(cpu: &Cpu, cause: u32) -> u64 {
+    let interrupt = (cause >> INTERRUPT) as u64 & 0x1;
+    (cause & !(1u32 << INTERRUPT)) as u64 | (interrupt << (cpu.xlen - 1))
+}
+
+fn cause_from_xlen(cpu: &Cpu, v: u64) -> u32 {
+    let interrupt = (v >> (cpu.xlen - 1)) as u32 & 0x1;
+    (v as u32 & !(1u32 << INTERRUPT)) | (interrupt << INTERRUPT)
+}
+
+fn is_rv32_only(csr: CSR) -> bool {
+    match csr {
+        CSR::CYCLEH | CSR::TIMEH | CSR::INSTRETH | CSR::MCYCLEH | CSR::MINSTRETH => true,
+        CSR::PMPCFG1 | CSR::PMPCFG3 => true,
+        _ => false,
+    }
+}
+
+fn is_float(csr: CSR) -> bool {
+    match csr {
+        CSR::FFLAGS | CSR::FRM | CSR::FCSR => true,
+        _ => false,
+    }
+}
+
+fn pmpcfg_index(csr: CSR) -> usize {
+    (csr as u32 - CSR::PMPCFG0 as u32) as usize
+}
+
+fn is_pmpaddr(csr: CSR) -> bool {
+    csr as u32 >= CSR::PMPADDR0 as u32 && csr as u32 <= CSR::PMPADDR15 as u32
+}
+
+fn pmpaddr_index(csr: CSR) -> usize {
+    (csr as u32 - CSR::PMPADDR0 as u32) as usize
+}
+
+// Counters below M are readable only as mcounteren allows, and in U also scounteren
+fn is_counter_enabled(cpu: &Cpu, csr: u32) -> bool {
+    let bit = csr & 0x1F;
+    match cpu.level {
+        MACHINE => true,
+        SUPERVISOR => (cpu.mcounteren >> bit) & 0x1 == 1,
+        _ => (cpu.mcounteren >> bit) & (cpu.scounteren >> bit) & 0x1 == 1,
+    }
+}
+
 pub fn csr_level(csr: u32) -> u8 {
     ((csr >> 8) & 0x3) as u8
 }
 
 pub fn is_csr_readonly(csr: u32) -> bool {
     let access_bits = (csr >> 10) & 0x3;
-    access_bits == 11
+    access_bits == 0b11
 }
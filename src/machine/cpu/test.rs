@@ -106,6 +106,884 @@ fn test_cpu() {
     assert_eq!(m.reg_from_name("t0"), 0x123, "lw");
 }
 
+#[test]
+fn test_muldiv() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    m.exec("(li t0 -7)");
+    m.exec("(li t1 3)");
+
+    m.exec("(mul t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), -21, "mul");
+
+    m.exec("(div t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), -2, "div rounds toward zero");
+
+    m.exec("(rem t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), -1, "rem takes sign of dividend");
+
+    m.exec("(divu t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), 0x55555553, "divu");
+
+    m.exec("(remu t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), 0, "remu");
+
+    // Upper halves
+    m.exec("(mulh t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), -1, "mulh");
+
+    m.exec("(mulhu t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), 2, "mulhu");
+
+    m.exec("(mulhsu t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), -1, "mulhsu");
+
+    m.exec("(mulhsu t2 t1 t0)");
+    assert_eq!(m.reg_from_name("t2"), 2, "mulhsu unsigned rs2");
+
+    // Division by zero
+    m.exec("(div t2 t0 zero)");
+    assert_eq!(m.reg_from_name("t2"), -1, "div by zero");
+    m.exec("(divu t2 t0 zero)");
+    assert_eq!(m.reg_from_name("t2"), -1, "divu by zero");
+    m.exec("(rem t2 t0 zero)");
+    assert_eq!(m.reg_from_name("t2"), -7, "rem by zero");
+    m.exec("(remu t2 t0 zero)");
+    assert_eq!(m.reg_from_name("t2"), -7, "remu by zero");
+
+    // Overflow
+    m.exec("(lui t0 0x80000000)");
+    m.exec("(li t1 -1)");
+    m.exec("(div t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), i32::min_value() as i64, "div overflow");
+    m.exec("(rem t2 t0 t1)");
+    assert_eq!(m.reg_from_name("t2"), 0, "rem overflow");
+}
+
+#[test]
+fn test_atomic() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    m.exec("(li a0 0x40)");
+    m.exec("(li t0 5)");
+    m.exec("(sw a0 t0 0)");
+
+    m.exec("(amoadd.w t1 a0 t0)");
+    assert_eq!(m.reg_from_name("t1"), 5, "amoadd old value");
+    assert_eq!(m.bus.value, 10, "amoadd memory");
+
+    m.exec("(li t0 -3)");
+    m.exec("(amomax.w t1 a0 t0)");
+    assert_eq!(m.bus.value, 10, "amomax");
+    m.exec("(amomaxu.w.aqrl t1 a0 t0)");
+    assert_eq!(m.bus.value, -3i32 as u32, "amomaxu");
+    m.exec("(li t0 7)");
+    m.exec("(amoswap.w.aq t1 a0 t0)");
+    assert_eq!(m.reg_from_name("t1"), -3, "amoswap old value");
+    assert_eq!(m.bus.value, 7, "amoswap");
+
+    // Successful lr/sc pair
+    m.exec("(lr.w t1 a0)");
+    assert_eq!(m.reg_from_name("t1"), 7, "lr");
+    m.exec("(addi t1 t1 1)");
+    m.exec("(sc.w t2 a0 t1)");
+    assert_eq!(m.reg_from_name("t2"), 0, "sc success");
+    assert_eq!(m.bus.value, 8, "sc memory");
+
+    // Reservation is consumed
+    m.exec("(sc.w t2 a0 zero)");
+    assert_eq!(m.reg_from_name("t2"), 1, "sc without reservation");
+    assert_eq!(m.bus.value, 8, "failed sc does not write");
+
+    // Intervening store breaks the reservation
+    m.exec("(lr.w t1 a0)");
+    m.exec("(sw a0 t1 0)");
+    m.exec("(sc.w t2 a0 zero)");
+    assert_eq!(m.reg_from_name("t2"), 1, "sc after store");
+    assert_eq!(m.bus.value, 8, "sc after store does not write");
+
+    // Misaligned addresses are reported in mtval
+    m.exec("(li a1 0x42)");
+    for &(s, cause) in &[("(lr.w t1 a1)", LOAD_ADDRESS_MISALIGNED), ("(sc.w t2 a1 t0)", STORE_ADDRESS_MISALIGNED),
+                         ("(amoadd.w t1 a1 t0)", STORE_ADDRESS_MISALIGNED)] {
+        let word = inst_to_word(s, &m.symtab, m.cpu.pc);
+        m.cpu.cycle(word, &mut m.bus);
+        assert_eq!(m.cpu.cause, cause, "{}", s);
+        assert_eq!(m.cpu.mtval, 0x42, "{} mtval", s);
+        m.cpu.pc = m.cpu.epc;
+        m.cpu.epc = 0;
+        m.cpu.cause = 0;
+        m.cpu.mtval = 0;
+    }
+}
+
+#[test]
+fn test_float() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    // 1.5 and 2.25
+    m.exec("(lui t0 0x3FC00000)");
+    m.exec("(fmv.w.x ft0 t0)");
+    assert_eq!(m.cpu.fregs[0], 0xFFFFFFFF3FC00000, "fmv.w.x boxes");
+    m.exec("(lui t0 0x40100000)");
+    m.exec("(fmv.w.x ft1 t0)");
+
+    m.exec("(fadd.s ft2 ft0 ft1)");
+    m.exec("(fmv.x.w t1 ft2)");
+    assert_eq!(m.reg_from_name("t1"), 0x40700000, "fadd.s");
+    assert_eq!(m.cpu.fflags, 0, "exact");
+
+    m.exec("(fmadd.s ft3 ft0 ft1 ft0)");
+    m.exec("(fcvt.w.s t1 ft3)");
+    assert_eq!(m.reg_from_name("t1"), 5, "fmadd.s and fcvt.w.s");
+    assert_eq!(m.cpu.fflags, float::NX, "inexact conversion");
+
+    m.exec("(fcvt.w.s t1 ft3 rdn)");
+    assert_eq!(m.reg_from_name("t1"), 4, "static rounding mode");
+
+    // Dynamic rounding mode from frm
+    m.exec("(li t0 1)");
+    m.exec("(csrrw zero t0 frm)");
+    m.exec("(fcvt.w.s t1 ft3)");
+    assert_eq!(m.reg_from_name("t1"), 4, "rtz");
+    m.exec("(csrrs t1 zero fcsr)");
+    assert_eq!(m.reg_from_name("t1"), (1 << 5) | float::NX as i64, "fcsr");
+    m.exec("(csrrw zero zero fflags)");
+
+    m.exec("(flt.s t1 ft0 ft1)");
+    assert_eq!(m.reg_from_name("t1"), 1, "flt.s");
+    m.exec("(fsgnjn.s ft4 ft0 ft0)");
+    m.exec("(fle.s t1 ft0 ft4)");
+    assert_eq!(m.reg_from_name("t1"), 0, "fsgnjn.s");
+    m.exec("(fclass.s t1 ft4)");
+    assert_eq!(m.reg_from_name("t1"), 1 << 1, "fclass.s");
+
+    // Division by zero
+    m.exec("(fcvt.s.w ft5 zero)");
+    m.exec("(fdiv.s ft6 ft0 ft5)");
+    assert_eq!(m.cpu.fregs[6], 0xFFFFFFFF7F800000, "fdiv.s by zero");
+    assert_eq!(m.cpu.fflags, float::DZ, "dz");
+
+    // Widen and back
+    m.exec("(fcvt.d.s fa0 ft0)");
+    assert_eq!(m.cpu.fregs[10], (1.5f64).to_bits(), "fcvt.d.s");
+    m.exec("(fcvt.s.d fa1 fa0)");
+    assert_eq!(m.cpu.fregs[11], m.cpu.fregs[0], "fcvt.s.d");
+
+    // Improperly boxed single reads as canonical NaN
+    m.cpu.fregs[7] = 0x3FC00000;
+    m.exec("(feq.s t1 ft7 ft7)");
+    assert_eq!(m.reg_from_name("t1"), 0, "unboxed");
+    m.exec("(fadd.s ft8 ft7 ft0)");
+    assert_eq!(m.cpu.fregs[28], 0xFFFFFFFF7FC00000, "canonical NaN");
+
+    m.exec("(li a0 0x40)");
+    m.exec("(fsw a0 ft0 0)");
+    assert_eq!(m.bus.value, 0x3FC00000, "fsw");
+    m.exec("(flw ft9 a0 0)");
+    assert_eq!(m.cpu.fregs[29], 0xFFFFFFFF3FC00000, "flw");
+
+    // Misaligned addresses are reported in mtval
+    m.exec("(li a1 0x44)");
+    for &(s, cause, addr) in &[("(flw ft9 a0 2)", LOAD_ADDRESS_MISALIGNED, 0x42), ("(fld ft9 a1 0)", LOAD_ADDRESS_MISALIGNED, 0x44),
+                               ("(fsw a0 ft0 1)", STORE_ADDRESS_MISALIGNED, 0x41), ("(fsd a1 ft0 0)", STORE_ADDRESS_MISALIGNED, 0x44)] {
+        let word = inst_to_word(s, &m.symtab, m.cpu.pc);
+        m.cpu.cycle(word, &mut m.bus);
+        assert_eq!(m.cpu.cause, cause, "{}", s);
+        assert_eq!(m.cpu.mtval, addr, "{} mtval", s);
+        m.cpu.pc = m.cpu.epc;
+        m.cpu.epc = 0;
+        m.cpu.cause = 0;
+        m.cpu.mtval = 0;
+    }
+}
+
+#[test]
+fn test_compressed() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    m.exec("(c.li a0 5)");
+    assert_eq!(m.reg_from_name("a0"), 5, "c.li");
+    assert_eq!(m.cpu.pc, 2, "pc advances by 2");
+
+    m.exec("(c.addi a0 -1)");
+    assert_eq!(m.reg_from_name("a0"), 4, "c.addi");
+    m.exec("(c.mv a1 a0)");
+    m.exec("(c.add a1 a0)");
+    assert_eq!(m.reg_from_name("a1"), 8, "c.mv, c.add");
+    m.exec("(c.slli a1 2)");
+    assert_eq!(m.reg_from_name("a1"), 32, "c.slli");
+    m.exec("(c.sub a1 a0)");
+    assert_eq!(m.reg_from_name("a1"), 28, "c.sub");
+    m.exec("(c.lui a2 0xFFFFF000)");
+    assert_eq!(m.reg_from_name("a2"), -4096, "c.lui");
+
+    m.exec("(c.li a1 0x10)");
+    m.exec("(c.sw a1 a0 4)");
+    assert_eq!(m.bus.addr, 0x14, "c.sw address");
+    assert_eq!(m.bus.value, 4, "c.sw value");
+    m.exec("(c.lw a2 a1 4)");
+    assert_eq!(m.reg_from_name("a2"), 4, "c.lw");
+
+    // Links past the 16-bit instruction
+    m.cpu.pc = 0x100;
+    m.exec("(c.jal 0x20)");
+    assert_eq!(m.reg_from_name("ra"), 0x102, "c.jal link");
+    assert_eq!(m.cpu.pc, 0x120, "c.jal");
+    m.exec("(c.jr ra)");
+    assert_eq!(m.cpu.pc, 0x102, "c.jr");
+
+    m.exec("(c.beqz a0 -2)");
+    assert_eq!(m.cpu.pc, 0x104, "c.beqz not taken");
+    m.exec("(c.bnez a0 -2)");
+    assert_eq!(m.cpu.pc, 0x102, "c.bnez taken");
+
+    // Reserved encoding
+    m.cpu.cycle(0x0000, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "all zero");
+
+    // Without C in misa
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+    m.cpu.misa &= !(extension('C') as u64);
+    let word = inst_to_word("(c.li a0 5)", &m.symtab, 0);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "C disabled");
+}
+
+#[test]
+fn test_env_call() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    m.cpu.pc = 0x200;
+    let word = inst_to_word("(ecall)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ECALL_BASE + MACHINE as u32, "ecall from M");
+    assert_eq!(m.cpu.epc, 0x200, "epc is the ecall");
+    assert_eq!(m.cpu.pc, m.cpu.mtvec, "trap vector");
+
+    m.cpu.level = USER;
+    m.cpu.pc = 0x300;
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ECALL_BASE + USER as u32, "ecall from U");
+    assert_eq!(m.cpu.level, MACHINE, "trap to M");
+
+    m.cpu.pc = 0x400;
+    let word = inst_to_word("(ebreak)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, BREAKPOINT, "ebreak");
+    assert_eq!(m.cpu.epc, 0x400, "epc is the ebreak");
+    assert_eq!(m.cpu.mtval, 0x400, "mtval");
+}
+
+#[test]
+fn test_csr() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    m.exec("(li t0 0x123)");
+    m.exec("(csrrw zero t0 mscratch)");
+    m.exec("(csrrs t1 zero mscratch)");
+    assert_eq!(m.reg_from_name("t1"), 0x123, "mscratch");
+
+    // WARL
+    m.exec("(li t0 0x203)");
+    m.exec("(csrrw zero t0 mtvec)");
+    assert_eq!(m.cpu.mtvec, 0x200, "reserved mtvec mode");
+    m.exec("(li t0 0x201)");
+    m.exec("(csrrw zero t0 mtvec)");
+    assert_eq!(m.cpu.mtvec, 0x201, "vectored mtvec");
+
+    m.exec("(li t0 -1)");
+    m.exec("(csrrw zero t0 medeleg)");
+    assert_eq!(m.cpu.medeleg, MEDELEG_MASK, "medeleg");
+    m.exec("(csrrw zero zero medeleg)");
+    m.exec("(csrrw zero t0 mie)");
+    assert_eq!(m.cpu.ie, MIE_MASK, "mie");
+    m.exec("(csrrw zero zero mie)");
+
+    m.exec("(csrrw zero t0 mstatus)");
+    assert_eq!(m.cpu.status, MSTATUS_MASK, "mstatus");
+    // MPP of 2 is reserved, so mret has somewhere legal to go
+    m.exec("(lui t1 0x1000)");
+    m.exec("(csrrw zero t1 mstatus)");
+    assert_eq!(m.cpu.status, 3 << MPP, "mstatus.MPP");
+    m.exec("(mret)");
+    assert_eq!(m.cpu.level, MACHINE, "mret to MPP");
+    m.cpu.status = 0;
+
+    m.exec("(csrrs t1 zero mhartid)");
+    assert_eq!(m.reg_from_name("t1"), 0, "mhartid");
+
+    m.cpu.instret = 0x1_0000_0007;
+    m.exec("(csrrs t1 zero minstret)");
+    m.exec("(csrrs t2 zero minstreth)");
+    assert_eq!(m.reg_from_name("t1"), 7, "minstret");
+    assert_eq!(m.reg_from_name("t2"), 1, "minstreth");
+
+    m.cpu.pc = 0x100;
+    let word = inst_to_word("(csrrw zero t0 mepc)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.epc, 0xFFFFFFFE, "mepc alignment");
+
+    // Read-only
+    m.cpu.pc = 0x100;
+    let word = inst_to_word("(csrrw zero t0 mhartid)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "write to mhartid");
+
+    // Not implemented
+    m.cpu.cause = 0;
+    m.cpu.pc = 0x100;
+    let word = inst_to_word("(csrrs t1 zero 0x7C0)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "unknown csr");
+
+    // Counter enables
+    m.cpu.cause = 0;
+    m.cpu.level = USER;
+    m.cpu.pc = 0x100;
+    let word = inst_to_word("(csrrs t1 zero cycle)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "cycle without mcounteren");
+
+    m.cpu.cause = 0;
+    m.cpu.level = USER;
+    m.cpu.mcounteren = 0x1;
+    m.cpu.scounteren = 0x1;
+    m.cpu.cycle = 42;
+    m.cpu.pc = 0x100;
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, 0, "cycle with mcounteren");
+    assert_eq!(m.reg_from_name("t1"), 42, "cycle");
+}
+
+#[test]
+fn test_interrupt() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    let nop = inst_to_word("(nop)", &m.symtab, 0);
+    m.bus.value = nop;
+
+    m.cpu.pc = 0x200;
+    m.bus.addr = m.cpu.pc as u32;
+    m.cpu.ie = (1 << MEIE) | (1 << MSIE) | (1 << MTIE) | (1 << SSIE);
+    m.cpu.ip = 1 << MEIP;
+    m.cpu.tick(&mut m.bus);
+    assert_eq!(m.cpu.pc, 0x204, "mstatus.MIE clear");
+
+    // Enabled
+    m.cpu.status |= 1 << (IE_BASE + MACHINE);
+    m.bus.addr = m.cpu.pc as u32;
+    m.cpu.tick(&mut m.bus);
+    assert_eq!(m.cpu.cause, (1 << INTERRUPT) | EXTERNAL_INTERRUPT_BASE + MACHINE as u32, "MEI");
+    assert_eq!(m.cpu.epc, 0x204, "epc is the interrupted instruction");
+    assert_eq!(m.cpu.pc, m.cpu.mtvec, "trap vector");
+    assert_eq!(m.cpu.instret, 1, "not retired");
+
+    // Priority
+    m.cpu.status |= 1 << (IE_BASE + MACHINE);
+    m.cpu.ip = (1 << MTIP) | (1 << MSIP);
+    m.cpu.tick(&mut m.bus);
+    assert_eq!(m.cpu.cause, (1 << INTERRUPT) | SOFTWARE_INTERRUPT_BASE + MACHINE as u32, "MSI over MTI");
+
+    // Always enabled from a lower level
+    m.cpu.level = USER;
+    m.cpu.status = 0;
+    m.cpu.ip = 1 << MTIP;
+    m.cpu.tick(&mut m.bus);
+    assert_eq!(m.cpu.cause, (1 << INTERRUPT) | TIMER_INTERRUPT_BASE + MACHINE as u32, "MTI from U");
+    assert_eq!(m.cpu.level, MACHINE, "trap to M");
+
+    // Delegated interrupts don't interrupt M
+    m.cpu.mideleg = 1 << SSIP;
+    m.cpu.ip = 1 << SSIP;
+    m.cpu.status = 1 << (IE_BASE + MACHINE);
+    m.cpu.cause = 0;
+    m.cpu.pc = 0x300;
+    m.bus.addr = m.cpu.pc as u32;
+    m.cpu.tick(&mut m.bus);
+    assert_eq!(m.cpu.pc, 0x304, "delegated in M");
+
+    m.cpu.level = USER;
+    m.cpu.tick(&mut m.bus);
+    assert_eq!(m.cpu.scause, (1 << INTERRUPT) | SOFTWARE_INTERRUPT_BASE + SUPERVISOR as u32, "SSI from U");
+    assert_eq!(m.cpu.level, SUPERVISOR, "trap to S");
+}
+
+#[test]
+fn test_supervisor() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    m.cpu.level = SUPERVISOR;
+
+    // sstatus only shows the supervisor bits of mstatus
+    m.cpu.status = 0x1888; // MIE, MPIE, MPP
+    m.exec("(li t0 -1)");
+    m.exec("(csrrw t1 t0 sstatus)");
+    assert_eq!(m.reg_from_name("t1"), 0, "sstatus");
+    assert_eq!(m.cpu.status, 0x1888 | SSTATUS_MASK, "mstatus");
+
+    // sie and sip only show delegated interrupts
+    m.cpu.mideleg = (1 << SSIP) | (1 << STIP);
+    m.exec("(csrrw zero t0 sie)");
+    assert_eq!(m.cpu.ie, (1 << SSIE) | (1 << STIE), "sie");
+    m.exec("(csrrw zero t0 sip)");
+    assert_eq!(m.cpu.ip, 1 << SSIP, "sip");
+
+    m.exec("(csrrw zero t0 satp)");
+    assert_eq!(m.cpu.satp, 0x803FFFFF, "no asid");
+    m.cpu.satp = 0;
+
+    m.exec("(sfence.vma zero zero)");
+    m.exec("(sfence.vma t0 zero)");
+
+    m.cpu.level = USER;
+    m.cpu.pc = 0x100;
+    let word = inst_to_word("(sfence.vma zero zero)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "sfence.vma from U");
+}
+
+#[test]
+fn test_delegation() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    m.cpu.mtvec = 0x10;
+    m.cpu.stvec = 0x20;
+    m.cpu.medeleg = (1 << (ECALL_BASE + USER as u32)) | (1 << BREAKPOINT);
+
+    // Delegated ecall from U
+    m.cpu.level = USER;
+    m.cpu.status = 1 << (IE_BASE + SUPERVISOR);
+    m.cpu.pc = 0x200;
+    let ecall = inst_to_word("(ecall)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(ecall, &mut m.bus);
+    assert_eq!(m.cpu.level, SUPERVISOR, "trap to S");
+    assert_eq!(m.cpu.scause, ECALL_BASE + USER as u32, "scause");
+    assert_eq!(m.cpu.sepc, 0x200, "sepc");
+    assert_eq!(m.cpu.pc, 0x20, "stvec");
+    assert_eq!(m.cpu.cause, 0, "mcause untouched");
+    assert_eq!(m.cpu.status, 1 << (PIE_BASE + SUPERVISOR), "SPIE, SPP");
+
+    // Not delegated from S
+    m.cpu.cycle(ecall, &mut m.bus);
+    assert_eq!(m.cpu.level, MACHINE, "trap to M");
+    assert_eq!(m.cpu.cause, ECALL_BASE + SUPERVISOR as u32, "mcause");
+    assert_eq!(m.cpu.epc, 0x20, "mepc");
+    assert_eq!((m.cpu.status >> MPP) & 0x3, SUPERVISOR as u32, "MPP");
+
+    // Delegated traps report tval to stval
+    m.cpu.level = SUPERVISOR;
+    m.cpu.pc = 0x300;
+    let ebreak = inst_to_word("(ebreak)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(ebreak, &mut m.bus);
+    assert_eq!(m.cpu.scause, BREAKPOINT, "scause");
+    assert_eq!(m.cpu.stval, 0x300, "stval");
+
+    // Never delegated from M
+    m.cpu.level = MACHINE;
+    m.cpu.pc = 0x400;
+    m.cpu.cycle(ebreak, &mut m.bus);
+    assert_eq!(m.cpu.cause, BREAKPOINT, "mcause");
+    assert_eq!(m.cpu.mtval, 0x400, "mtval");
+
+    // sret back to U
+    m.cpu.cause = 0;
+    m.cpu.epc = 0;
+    m.cpu.level = SUPERVISOR;
+    m.cpu.status = 1 << (PIE_BASE + SUPERVISOR);
+    m.cpu.sepc = 0x204;
+    m.cpu.pc = 0x30;
+    m.exec("(sret)");
+    assert_eq!(m.cpu.level, USER, "SPP");
+    assert_eq!(m.cpu.pc, 0x204, "sepc");
+    assert_eq!(m.cpu.status, (1 << (IE_BASE + SUPERVISOR)) | (1 << (PIE_BASE + SUPERVISOR)), "SIE");
+}
+
+#[test]
+fn test_precise_exception() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    // jalr reads rs1 before linking
+    m.cpu.regs[register::index("t0").unwrap() as usize] = 0x100;
+    m.cpu.pc = 0x40;
+    m.exec("(jalr t0 t0 0)");
+    assert_eq!(m.cpu.pc, 0x100, "jalr rd == rs1");
+    assert_eq!(m.reg_from_name("t0"), 0x44, "jalr link");
+
+    // Without C, a misaligned target traps on the jump and doesn't link
+    m.cpu.misa &= !(extension('C') as u64);
+    m.cpu.mtvec = 0x10;
+    let word = inst_to_word("(jal ra 0x22)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, INSTRUCTION_ADDRESS_MISALIGNED, "jal misaligned");
+    assert_eq!(m.cpu.epc, 0x100, "mepc is the jump");
+    assert_eq!(m.cpu.mtval, 0x122, "mtval is the target");
+    assert_eq!(m.reg_from_name("ra"), 0, "no link");
+
+    // A misaligned pc traps before fetch
+    m.cpu.pc = 0x102;
+    m.cpu.tick(&mut m.bus);
+    assert_eq!(m.cpu.cause, INSTRUCTION_ADDRESS_MISALIGNED, "misaligned pc");
+    assert_eq!(m.cpu.epc, 0x102, "mepc");
+    assert_eq!(m.cpu.mtval, 0x102, "mtval");
+    assert_eq!(m.cpu.pc, 0x10, "trap vector");
+
+    // U may access [0, 0x104). fsd at 0x100 faults on its second word and stores nothing
+    m.cpu.level = USER;
+    m.cpu.pmpaddr[0] = 0x104 >> 2;
+    m.cpu.pmpcfg[0] = (PMP_TOR << PMP_A) | PMP_R | PMP_W;
+    m.cpu.regs[register::index("a0").unwrap() as usize] = 0x100;
+    m.cpu.pc = 0x20;
+    let word = inst_to_word("(fsd a0 fa0 0)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, STORE_ACCESS_FAULT, "fsd second word");
+    assert_eq!(m.cpu.mtval, 0x100, "mtval");
+    assert_eq!((m.bus.addr, m.bus.value), (0, 0), "nothing stored");
+
+    // A faulting load leaves rd as it was
+    m.cpu.level = USER;
+    m.cpu.regs[register::index("a1").unwrap() as usize] = 7;
+    let word = inst_to_word("(lw a1 a0 4)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, LOAD_ACCESS_FAULT, "lw");
+    assert_eq!(m.cpu.mtval, 0x104, "mtval");
+    assert_eq!(m.reg_from_name("a1"), 7, "rd untouched");
+}
+
+#[test]
+fn test_rv64() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+    m.cpu.reset(Isa::parse("rv64gc").unwrap(), 0, 0);
+
+    m.exec("(li t0 -1)");
+    assert_eq!(m.reg_from_name("t0"), -1, "li");
+    m.exec("(srli t1 t0 32)");
+    assert_eq!(m.reg_from_name("t1"), 0xFFFFFFFF, "srli 32");
+    m.exec("(lui t2 0x80000000)");
+    assert_eq!(m.reg_from_name("t2"), 0xFFFFFFFF80000000u64 as i64, "lui sign-extends");
+    m.exec("(slli t2 t1 63)");
+    assert_eq!(m.reg_from_name("t2"), ::std::i64::MIN, "slli 63");
+    m.exec("(sltu t3 t1 t2)");
+    assert_eq!(m.reg_from_name("t3"), 1, "sltu");
+    m.exec("(mulhu t3 t0 t0)");
+    assert_eq!(m.reg_from_name("t3"), -2, "mulhu");
+
+    // Word instructions work on the low 32 bits and sign-extend
+    m.exec("(addiw t3 t1 1)");
+    assert_eq!(m.reg_from_name("t3"), 0, "addiw");
+    m.exec("(addw t3 t1 t1)");
+    assert_eq!(m.reg_from_name("t3"), -2, "addw");
+    m.exec("(srliw t3 t0 4)");
+    assert_eq!(m.reg_from_name("t3"), 0x0FFFFFFF, "srliw");
+    m.exec("(sraiw t3 t1 4)");
+    assert_eq!(m.reg_from_name("t3"), -1, "sraiw");
+    m.exec("(divuw t3 t1 zero)");
+    assert_eq!(m.reg_from_name("t3"), -1, "divuw by zero");
+
+    // 64-bit conversions
+    m.exec("(fcvt.d.l ft0 t0)");
+    m.exec("(fmv.x.d t3 ft0)");
+    assert_eq!(m.reg_from_name("t3"), (-1.0f64).to_bits() as i64, "fcvt.d.l");
+    m.exec("(fcvt.lu.d t3 ft0)");
+    assert_eq!(m.reg_from_name("t3"), 0, "fcvt.lu.d saturates");
+
+    // misa and mstatus report XLEN
+    m.exec("(csrrs t3 zero misa)");
+    assert_eq!(m.reg_from_name("t3") as u64 >> 62, XLEN_64 as u64, "misa.MXL");
+    m.exec("(csrrs t3 zero mstatus)");
+    assert_eq!((m.reg_from_name("t3") >> UXL) & 0x3, XLEN_64 as i64, "mstatus.UXL");
+
+    // Nothing exists beyond the 32-bit bus
+    m.cpu.regs[register::index("t1").unwrap() as usize] = 0x100000000;
+    let word = inst_to_word("(ld t2 t1 0)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, LOAD_ACCESS_FAULT, "ld beyond 4GiB");
+    assert_eq!(m.cpu.mtval, 0x100000000, "mtval");
+
+    // RV32 has none of it
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+    for s in &["(addiw t0 t0 1)", "(subw t0 t0 t0)", "(ld t0 zero 0)", "(sd zero t0 0)", "(slli t0 t0 32)", "(fmv.x.d t0 ft0)"] {
+        m.cpu.cause = 0;
+        let word = inst_to_word(s, &m.symtab, m.cpu.pc);
+        m.cpu.cycle(word, &mut m.bus);
+        assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "{}", s);
+    }
+}
+
+#[test]
+fn test_fence() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    assert_eq!(inst_to_word("(fence)", &m.symtab, 0), 0x0FF0000F, "fence");
+    assert_eq!(inst_to_word("(fence rw w)", &m.symtab, 0), 0x0310000F, "fence rw w");
+    assert_eq!(inst_to_word("(fence.i)", &m.symtab, 0), 0x0000100F, "fence.i");
+    assert_eq!(::arch::inst::fence_set_value("wr"), None, "out of order");
+
+    m.exec("(fence)");
+    m.exec("(fence io rw)");
+    assert_eq!(m.cpu.pc, 8, "fence");
+    m.exec("(fence.i)");
+    assert_eq!(m.cpu.pc, 12, "fence.i");
+}
+
+#[test]
+fn test_isa() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+    m.cpu.reset(Isa::parse("rv32i").unwrap(), 0, 0);
+
+    m.exec("(add t0 t0 t0)");
+    m.exec("(fence)");
+    for s in &["(mul t0 t0 t0)", "(amoadd.w t0 t0 t0)", "(flw ft0 t0 0)", "(fadd.s ft0 ft0 ft0)",
+               "(csrrs t0 zero misa)", "(fence.i)", "(c.addi t0 1)"] {
+        m.cpu.cause = 0;
+        let word = inst_to_word(s, &m.symtab, m.cpu.pc);
+        m.cpu.cycle(word, &mut m.bus);
+        assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "{}", s);
+    }
+
+    // S without D
+    m.cpu.reset(Isa::parse("rv32imf_zicsr").unwrap(), 0, 0);
+    m.cpu.epc = 0;
+    m.exec("(fadd.s ft0 ft0 ft0)");
+    m.exec("(csrrs t0 zero fcsr)");
+    let word = inst_to_word("(fcvt.d.s ft0 ft0)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "fcvt.d.s");
+
+    // misa can't turn on C
+    m.cpu.cause = 0;
+    m.cpu.epc = 0;
+    m.exec("(csrrs t0 zero misa)");
+    m.exec("(li t1 4)");
+    m.exec("(csrrs zero t1 misa)");
+    m.exec("(csrrs t1 zero misa)");
+    assert_eq!(m.reg_from_name("t1"), m.reg_from_name("t0"), "misa.C");
+}
+
+#[test]
+fn test_bitmanip() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    // Strict RV32I rejects them
+    let all = ["(sh1add t0 t1 t2)", "(clz t0 t1)", "(andn t0 t1 t2)", "(rori t0 t1 3)", "(rev8 t0 t1)", "(bseti t0 t1 3)", "(bext t0 t1 t2)"];
+    for s in &all {
+        m.cpu.cause = 0;
+        let word = inst_to_word(s, &m.symtab, m.cpu.pc);
+        m.cpu.cycle(word, &mut m.bus);
+        assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "{}", s);
+    }
+
+    m.cpu.reset(Isa::parse("rv32gcb").unwrap(), 0, 0);
+    m.cpu.regs[register::index("t1").unwrap() as usize] = 0x00F0_8001;
+    m.cpu.regs[register::index("t2").unwrap() as usize] = -16;
+
+    // Zba
+    m.exec("(sh3add t0 t1 t2)");
+    assert_eq!(m.reg_from_name("t0"), 0x0784_0008 - 16, "sh3add");
+
+    // Zbb
+    m.exec("(clz t0 t1)");
+    assert_eq!(m.reg_from_name("t0"), 8, "clz");
+    m.exec("(ctz t0 t2)");
+    assert_eq!(m.reg_from_name("t0"), 4, "ctz");
+    m.exec("(ctz t0 zero)");
+    assert_eq!(m.reg_from_name("t0"), 32, "ctz 0");
+    m.exec("(cpop t0 t1)");
+    assert_eq!(m.reg_from_name("t0"), 6, "cpop");
+    m.exec("(sext.b t0 t1)");
+    assert_eq!(m.reg_from_name("t0"), 1, "sext.b");
+    m.exec("(sext.h t0 t1)");
+    assert_eq!(m.reg_from_name("t0"), 0xFFFF8001u32 as i32 as i64, "sext.h");
+    m.exec("(zext.h t0 t2)");
+    assert_eq!(m.reg_from_name("t0"), 0xFFF0, "zext.h");
+    m.exec("(min t0 t1 t2)");
+    assert_eq!(m.reg_from_name("t0"), -16, "min");
+    m.exec("(minu t0 t1 t2)");
+    assert_eq!(m.reg_from_name("t0"), 0x00F0_8001, "minu");
+    m.exec("(andn t0 t1 t2)");
+    assert_eq!(m.reg_from_name("t0"), 0x1, "andn");
+    m.exec("(xnor t0 t1 t1)");
+    assert_eq!(m.reg_from_name("t0"), -1, "xnor");
+    m.exec("(rori t0 t1 4)");
+    assert_eq!(m.reg_from_name("t0"), 0x100F_0800, "rori");
+    m.exec("(rol t0 t1 t2)");
+    assert_eq!(m.reg_from_name("t0"), 0x8001_00F0u32 as i32 as i64, "rol by 16");
+    m.exec("(orc.b t0 t1)");
+    assert_eq!(m.reg_from_name("t0"), 0x00FF_FFFF, "orc.b");
+    m.exec("(rev8 t0 t1)");
+    assert_eq!(m.reg_from_name("t0"), 0x0180_F000, "rev8");
+
+    // Zbs
+    m.exec("(bseti t0 t1 31)");
+    assert_eq!(m.reg_from_name("t0"), 0x80F0_8001u32 as i32 as i64, "bseti");
+    m.exec("(bclri t0 t1 0)");
+    assert_eq!(m.reg_from_name("t0"), 0x00F0_8000, "bclri");
+    m.exec("(binv t0 t1 t2)");
+    assert_eq!(m.reg_from_name("t0"), 0x00F1_8001, "binv");
+    m.exec("(bexti t0 t1 15)");
+    assert_eq!(m.reg_from_name("t0"), 1, "bexti");
+
+    // RV64 forms
+    m.cpu.reset(Isa::parse("rv64gc_zba_zbb_zbs").unwrap(), 0, 0);
+    m.cpu.regs[register::index("t1").unwrap() as usize] = -2;
+    m.exec("(add.uw t0 t1 t1)");
+    assert_eq!(m.reg_from_name("t0"), 0xFFFFFFFE - 2, "add.uw");
+    m.exec("(slli.uw t0 t1 32)");
+    assert_eq!(m.reg_from_name("t0"), 0xFFFFFFFE00000000u64 as i64, "slli.uw");
+    m.exec("(clz t0 t1)");
+    assert_eq!(m.reg_from_name("t0"), 0, "clz");
+    m.exec("(clzw t0 t2)");
+    assert_eq!(m.reg_from_name("t0"), 0, "clzw");
+    m.exec("(roriw t0 t1 1)");
+    assert_eq!(m.reg_from_name("t0"), 0x7FFFFFFF, "roriw");
+    m.exec("(bseti t0 zero 63)");
+    assert_eq!(m.reg_from_name("t0"), ::std::i64::MIN, "bseti 63");
+}
+
+#[test]
+fn test_hpm() {
+    let mut m = MockMachine {
+        cpu: Cpu::new(),
+        bus: MockBus { addr: 0, value: 0 },
+        symtab: symtab::Symtab::prepopulated(),
+    };
+
+    assert_eq!(csr_value("mhpmcounter3"), Some(0xB03), "mhpmcounter3");
+    assert_eq!(csr_value("hpmcounter31h"), Some(0xC9F), "hpmcounter31h");
+    assert_eq!(csr_value("mhpmevent32"), None, "mhpmevent32");
+    assert_eq!(csr_name(0x323).as_ref().map(|s| &s[..]), Some("mhpmevent3"), "mhpmevent3");
+
+    m.exec("(li t0 1)");
+    m.exec("(csrrw zero t0 mhpmevent3)");
+    m.exec("(li t0 2)");
+    m.exec("(csrrw zero t0 mhpmevent4)");
+    m.exec("(li t0 3)");
+    m.exec("(csrrw zero t0 mhpmevent31)");
+    assert_eq!(m.cpu.hpmevent[0], HPM_EVENT_LOADS, "mhpmevent3");
+
+    m.exec("(lw t1 zero 0)");
+    m.exec("(lw t1 zero 0)");
+    m.exec("(sw zero t1 0)");
+    m.cpu.pc = 0x100;
+    m.exec("(beq zero zero 8)");
+    m.exec("(bne zero zero 8)");
+    m.exec("(jal zero 8)");
+
+    m.exec("(csrrs t1 zero mhpmcounter3)");
+    assert_eq!(m.reg_from_name("t1"), 2, "loads");
+    m.exec("(csrrs t1 zero mhpmcounter4)");
+    assert_eq!(m.reg_from_name("t1"), 1, "stores");
+    m.exec("(csrrs t1 zero mhpmcounter31)");
+    assert_eq!(m.reg_from_name("t1"), 1, "taken branches");
+
+    // A trapping load doesn't count as one
+    m.cpu.hpmevent[3] = HPM_EVENT_TRAPS;
+    let word = inst_to_word("(lw t1 zero 1)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, LOAD_ADDRESS_MISALIGNED, "misaligned");
+    assert_eq!(m.cpu.hpmcounter[0], 2, "trapped load");
+    assert_eq!(m.cpu.hpmcounter[3], 1, "traps");
+    m.cpu.epc = 0;
+    m.cpu.cause = 0;
+
+    m.exec("(li t0 0x678)");
+    m.exec("(csrrw zero t0 mhpmcounter5)");
+    m.exec("(li t0 1)");
+    m.exec("(csrrw zero t0 mhpmcounter5h)");
+    assert_eq!(m.cpu.hpmcounter[2], 0x1_0000_0678, "mhpmcounter5");
+
+    // The user shadows only read, and only as mcounteren allows
+    let word = inst_to_word("(csrrw zero t0 hpmcounter5)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "write hpmcounter5");
+    m.cpu.epc = 0;
+    m.cpu.cause = 0;
+
+    m.cpu.level = SUPERVISOR;
+    let word = inst_to_word("(csrrs t1 zero hpmcounter5h)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "hpmcounter5h without mcounteren");
+    m.cpu.epc = 0;
+    m.cpu.cause = 0;
+
+    m.cpu.level = SUPERVISOR;
+    m.cpu.mcounteren = 1 << 5;
+    m.exec("(csrrs t1 zero hpmcounter5h)");
+    assert_eq!(m.reg_from_name("t1"), 1, "hpmcounter5h");
+
+    // No high halves on RV64
+    m.cpu.reset(Isa::parse("rv64gc").unwrap(), 0, 0);
+    m.exec("(csrrs t1 zero mhpmcounter5)");
+    assert_eq!(m.reg_from_name("t1"), 0x1_0000_0678, "mhpmcounter5 on RV64");
+    let word = inst_to_word("(csrrs t1 zero mhpmcounter5h)", &m.symtab, m.cpu.pc);
+    m.cpu.cycle(word, &mut m.bus);
+    assert_eq!(m.cpu.cause, ILLEGAL_INSTRUCTION, "mhpmcounter5h on RV64");
+}
+
 struct MockBus {
     addr: u32,
     value: u32,
@@ -146,13 +1024,13 @@ impl MockMachine {
         assert_eq!(self.cpu.epc, 0, "cpu.epc");
     }
 
-    fn reg_from_name(&self, name: &str) -> i32 {
+    fn reg_from_name(&self, name: &str) -> i64 {
         self.cpu.regs[register::index(name).unwrap() as usize]
     }
 }
 
-fn inst_to_word(s: &str, symtab: &symtab::Symtab, pc: u32) -> u32 {
+fn inst_to_word(s: &str, symtab: &symtab::Symtab, pc: u64) -> u32 {
     let expr = parser::parse(s);
-    let word = translate::translate(&expr, symtab, pc).expect("translate");
+    let word = translate::translate(&expr, symtab, pc as u32).expect("translate");
     word
 }
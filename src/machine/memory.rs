@@ -1,47 +1,43 @@
 use super::*;
 
-pub const MEMORY_SIZE: usize = 0x10000;
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use std::fs::File;
 
-// 64kB memory
+// RAM of size bytes, all there from the start
 pub struct Memory {
-    pub data: Box<[u32; MEMORY_SIZE]>,
+    pub data: Vec<u32>,
     write_probe: Option<u32>,
 }
 
 impl Memory {
-    pub fn new(write_probe: Option<u32>) -> Memory {
+    pub fn new(size: u32, write_probe: Option<u32>) -> Memory {
+        assert_eq!(size % 4, 0, "size: {}", size);
+
         Memory {
-            data: Box::new([0u32; MEMORY_SIZE]),
+            data: vec![0u32; (size / 4) as usize],
             write_probe: write_probe,
         }
     }
 
     pub fn load(&mut self, data: &[u8]) {
-        assert_eq!(data.len() % 4, 0);
+        self.load_at(0, data);
+    }
 
-        for i in 0..data.len() / 4 {
-            let there = i*4;
-            let v = slice_to_u32(&data[there..there + 4]);
-//            println!("slice: {:?} v: {:08X}", &data[i..i+4], v);
-            self.data[i] = v;
-        }
+    // Bytes at offset, of any length and alignment
+    pub fn load_at(&mut self, offset: u32, data: &[u8]) {
+        assert!(offset as u64 + data.len() as u64 <= self.data.len() as u64 * 4,
+                "{} bytes at {:X} don't fit in {} bytes", data.len(), offset, self.data.len() * 4);
 
+        for (i, &b) in data.iter().enumerate() {
+            let addr = offset + i as u32;
+            let word = &mut self.data[(addr / 4) as usize];
+            *word = sized_write(*word, addr, 1, b as u32);
+        }
     }
 }
 
-fn slice_to_u32(buf: &[u8]) -> u32 {
-    let mut x = 0u32;
-    x |= buf[3] as u32;
-    x <<= 8;
-    x |= buf[2] as u32;
-    x <<= 8;
-    x |= buf[1] as u32;
-    x <<= 8;
-    x |= buf[0] as u32;
-
-    x
-}
-
 impl BusEnd for Memory {
     fn read_word(&mut self, addr: u32) -> u32 {
         self.data[(addr / 4) as usize]
@@ -57,6 +53,11 @@ impl BusEnd for Memory {
         self.data[(addr / 4) as usize] = value;
     }
 
+    fn write(&mut self, addr: u32, size: u32, value: u32) {
+        let word = self.data[(addr / 4) as usize];
+        self.write_word(addr & !0x3, sized_write(word, addr, size, value));
+    }
+
     fn is_interrupting(&self) -> bool {
         false
     }
@@ -64,4 +65,123 @@ impl BusEnd for Memory {
 
 impl Peri for Memory {
 
-}
\ No newline at end of file
+}
+
+pub const PAGE_SIZE: u32 = 0x1000;
+const PAGE_WORDS: usize = (PAGE_SIZE / 4) as usize;
+
+// RAM for large, mostly empty regions. Pages are allocated on the first write to them.
+// The rest reads as zero
+pub struct SparseMemory {
+    pages: HashMap<u32, Box<[u32; PAGE_WORDS]>>,
+}
+
+impl SparseMemory {
+    pub fn new() -> SparseMemory {
+        SparseMemory {
+            pages: HashMap::new(),
+        }
+    }
+
+    pub fn load_at(&mut self, offset: u32, data: &[u8]) {
+        assert!(offset as u64 + data.len() as u64 <= 1 << 32,
+                "{} bytes at {:X} run past the end of the address space", data.len(), offset);
+
+        for (i, &b) in data.iter().enumerate() {
+            let addr = offset + i as u32;
+            let word = self.word_mut(addr);
+            *word = sized_write(*word, addr, 1, b as u32);
+        }
+    }
+
+    // Pages written so far
+    pub fn num_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn word_mut(&mut self, addr: u32) -> &mut u32 {
+        let page = self.pages.entry(addr / PAGE_SIZE).or_insert_with(|| Box::new([0u32; PAGE_WORDS]));
+        &mut page[((addr % PAGE_SIZE) / 4) as usize]
+    }
+}
+
+impl BusEnd for SparseMemory {
+    fn read_word(&mut self, addr: u32) -> u32 {
+        match self.pages.get(&(addr / PAGE_SIZE)) {
+            Some(page) => page[((addr % PAGE_SIZE) / 4) as usize],
+            None => 0,
+        }
+    }
+
+    fn write_word(&mut self, addr: u32, value: u32) {
+        *self.word_mut(addr) = value;
+    }
+
+    fn write(&mut self, addr: u32, size: u32, value: u32) {
+        let word = self.word_mut(addr);
+        *word = sized_write(*word, addr, size, value);
+    }
+
+    fn is_interrupting(&self) -> bool {
+        false
+    }
+}
+
+impl Peri for SparseMemory {
+
+}
+
+// Read-only memory, sized to its contents. Writes are ignored, or refused as access faults
+pub struct Rom {
+    pub data: Vec<u32>,
+    fault_on_write: bool,
+}
+
+impl Rom {
+    pub fn new(data: &[u8], fault_on_write: bool) -> Rom {
+        let mut memory = Memory::new(((data.len() + 3) & !0x3) as u32, None);
+        memory.load(data);
+
+        Rom {
+            data: memory.data,
+            fault_on_write: fault_on_write,
+        }
+    }
+
+    pub fn from_file(path: &str, fault_on_write: bool) -> io::Result<Rom> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        Ok(Rom::new(&data, fault_on_write))
+    }
+
+    // Bytes, for attaching
+    pub fn size(&self) -> u32 {
+        (self.data.len() * 4) as u32
+    }
+}
+
+impl BusEnd for Rom {
+    fn read_word(&mut self, addr: u32) -> u32 {
+        self.data[(addr / 4) as usize]
+    }
+
+    fn write_word(&mut self, _addr: u32, _value: u32) {
+        // ignoring...
+    }
+
+    fn write(&mut self, _addr: u32, _size: u32, _value: u32) {
+        // ignoring...
+    }
+
+    fn is_interrupting(&self) -> bool {
+        false
+    }
+
+    fn is_writable(&self, _addr: u32) -> bool {
+        !self.fault_on_write
+    }
+}
+
+impl Peri for Rom {
+
+}
@@ -5,11 +5,12 @@
 pub mod peri;
 mod cpu;
 pub mod memory;
+pub mod clint;
+pub mod plic;
 #[cfg(test)]
 mod test;
 
 use std::io::prelude::*;
-use std::collections::HashMap;
 use std::ops::Deref;
 
 use self::cpu::*;
@@ -17,50 +18,241 @@ use self::memory::*;
 
 use ::arch::system::*;
 
+pub use ::arch::isa::{Isa, IsaError};
+pub use ::elf::Elf;
+pub use ::arch::system::{HPM_EVENT_LOADS, HPM_EVENT_STORES, HPM_EVENT_TAKEN_BRANCHES, HPM_EVENT_TRAPS, HPM_EVENT_STALLS};
+
 // I don't know. Something unmistakable.
-const TERMINATION_PC: u32 = 0x10000000;
+const TERMINATION_PC: u64 = 0x10000000;
+
+const ICACHE_LINES: usize = 256;
+
+const BOOT_ROM: &'static str = "boot_rom";
 
 // Machine is CPU and the bus
 pub struct Machine {
     cpu: Cpu,
-    peripherals: HashMap<String, PeriConnection>,
+    peripherals: Vec<PeriConnection>, // Sorted by address
+    // For the next peripheral attached
+    next_peripheral_id: u32,
+    reset_vector: u32,
+}
+
+// Where the machine starts, and where traps go until software sets mtvec
+pub struct Config {
+    // Extensions, and XLEN
+    pub isa: Isa,
+    pub reset_vector: u32,
+    pub mtvec: u32,
+    // Misaligned loads and stores are done as several bus transactions instead of trapping
+    pub misaligned_access: bool,
+    // Fetch through an instruction cache, so code written by the program runs only after fence.i
+    pub icache: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            isa: Default::default(),
+            reset_vector: 0,
+            mtvec: 0x00000010,
+            misaligned_access: false,
+            icache: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AttachError {
+    Empty,
+    Overlap(String), // The peripheral already there
+    NameTaken,
 }
 
 #[derive(Debug)]
 pub enum RunError {
-    CyclesLimitExceeded(u32), // last pc
-    Exception(u32),
+    CyclesLimitExceeded(u64), // last pc
+    Exception(u64),
     Terminated,
 }
 
 impl Machine {
     /// Configure and return new machine
     pub fn new() -> Machine {
+        Machine::with_config(Default::default())
+    }
+
+    pub fn with_config(config: Config) -> Machine {
+        let mut cpu = Cpu::new();
+        cpu.reset(config.isa, config.reset_vector, config.mtvec);
+        cpu.misaligned_access = config.misaligned_access;
+        if config.icache {
+            cpu.icache = cpu::icache::ICache::with_lines(ICACHE_LINES);
+        }
+
         Machine {
-            cpu: Cpu::new(),
-            peripherals: HashMap::new(),
+            cpu: cpu,
+            peripherals: Vec::new(),
+            next_peripheral_id: 0,
+            reset_vector: config.reset_vector,
+        }
+    }
+
+    // The device answers at [addr_start, addr_start + size), and sees offsets from addr_start
+    pub fn attach<T: Peri + 'static>(&mut self, name: &str, peri: T, addr_start: u32, size: u32) -> Result<(), AttachError> {
+        self.connect(name, Box::new(peri), addr_start, size, None)
+    }
+
+    // The device interrupts through the interrupt controller, on line irq
+    pub fn attach_irq<T: Peri + 'static>(&mut self, name: &str, peri: T, addr_start: u32, size: u32, irq: u32) -> Result<(), AttachError> {
+        assert!(irq > 0 && irq < 32, "irq: {}", irq);
+        self.connect(name, Box::new(peri), addr_start, size, Some(irq))
+    }
+
+    // Bytes written through the bus to wherever they land. Err is the first address nothing
+    // writable answers at
+    pub fn load(&mut self, addr: u32, data: &[u8]) -> Result<(), u32> {
+        for (i, &b) in data.iter().enumerate() {
+            self.load_byte(addr.wrapping_add(i as u32), b)?;
         }
+        Ok(())
     }
 
-    pub fn attach<T: Peri + 'static>(&mut self, name: &str, peri: T, addr_start: u32, addr_width: u8) {
+    fn load_byte(&mut self, addr: u32, b: u8) -> Result<(), u32> {
+        self.peripherals.write(addr, 1, b as u32).map_err(|()| addr)?;
+
+        // The hart didn't see this write, but it still breaks its reservation
+        if self.cpu.reservation == Some(addr & !0x3) {
+            self.cpu.reservation = None;
+        }
+        Ok(())
+    }
+
+    // Each segment at its physical address with its .bss cleared. Execution starts at the entry,
+    // unless there is a boot ROM to run first
+    pub fn load_elf(&mut self, elf: &Elf) -> Result<(), u32> {
+        for segment in &elf.segments {
+            self.load(segment.paddr, &segment.data)?;
+
+            // Zeroed in place. mem_size comes from the file and may be large
+            let bss = segment.paddr.wrapping_add(segment.data.len() as u32);
+            for i in 0..segment.mem_size - segment.data.len() as u32 {
+                self.load_byte(bss.wrapping_add(i), 0)?;
+            }
+        }
+        if self.peripherals.iter().all(|p| p.name != BOOT_ROM) {
+            self.cpu.pc = elf.entry as u64;
+        }
+        Ok(())
+    }
+
+    // Where the hart starts, so boot code there runs first
+    pub fn attach_boot_rom(&mut self, rom: Rom) -> Result<(), AttachError> {
+        let (start, size) = (self.reset_vector, rom.size());
+        self.attach(BOOT_ROM, rom, start, size)
+    }
+
+    fn connect(&mut self, name: &str, device: Box<Peri>, addr_start: u32, size: u32, irq: Option<u32>) -> Result<(), AttachError> {
+        if size == 0 {
+            return Err(AttachError::Empty);
+        }
+        if self.peripherals.iter().any(|p| p.name == name) {
+            return Err(AttachError::NameTaken);
+        }
+
         let peripheral = PeriConnection {
+            id: self.next_peripheral_id,
+            name: String::from(name),
             addr_start: addr_start,
-            addr_width: addr_width,
-            device: Box::new(peri),
+            addr_end: addr_start as u64 + size as u64,
+            irq: irq,
+            device: device,
+        };
+
+        // Only the neighbors can overlap it
+        let index = match self.peripherals.binary_search_by_key(&addr_start, |p| p.addr_start) {
+            Ok(i) => return Err(AttachError::Overlap(self.peripherals[i].name.clone())),
+            Err(i) => i,
         };
-        self.peripherals.insert(String::from(name), peripheral);
+        if index > 0 && self.peripherals[index - 1].addr_end > addr_start as u64 {
+            return Err(AttachError::Overlap(self.peripherals[index - 1].name.clone()));
+        }
+        if index < self.peripherals.len() && (self.peripherals[index].addr_start as u64) < peripheral.addr_end {
+            return Err(AttachError::Overlap(self.peripherals[index].name.clone()));
+        }
+
+        self.next_peripheral_id += 1;
+        self.peripherals.insert(index, peripheral);
+        Ok(())
+    }
+
+    // Performance counters, as the program would read them from mhpmcounter3 and on
+
+    pub fn instret(&self) -> u64 {
+        self.cpu.instret
+    }
+
+    pub fn hpm_counter(&self, n: u32) -> u64 {
+        assert!(n >= HPM_FIRST && n < HPM_FIRST + HPM_COUNT, "n: {}", n);
+        self.cpu.hpmcounter[(n - HPM_FIRST) as usize]
+    }
+
+    pub fn set_hpm_counter(&mut self, n: u32, value: u64) {
+        assert!(n >= HPM_FIRST && n < HPM_FIRST + HPM_COUNT, "n: {}", n);
+        self.cpu.hpmcounter[(n - HPM_FIRST) as usize] = value;
+    }
+
+    // One of HPM_EVENT_*, or 0 to stop counting
+    pub fn set_hpm_event(&mut self, n: u32, event: u64) {
+        assert!(n >= HPM_FIRST && n < HPM_FIRST + HPM_COUNT, "n: {}", n);
+        self.cpu.hpmevent[(n - HPM_FIRST) as usize] = event;
+    }
+
+    // The event counting bus accesses to the named peripheral
+    pub fn peripheral_event(&self, name: &str) -> Option<u64> {
+        self.peripherals.iter().find(|p| p.name == name).map(|p| HPM_EVENT_BUS_ACCESS + p.id as u64)
+    }
+
+    // A timing model held the hart. The cycles pass without instructions
+    pub fn stall(&mut self, cycles: u64) {
+        self.cpu.cycle = self.cpu.cycle.wrapping_add(cycles);
+        self.cpu.count_events(HPM_EVENT_STALLS, cycles);
     }
 
     pub fn tick(&mut self) {
         // Tick peripherals
-        let mut interrupting = false;
-        for (n, c) in &mut self.peripherals {
+        let mut lines = 0u32;
+        for c in &mut self.peripherals {
             c.device.tick();
-            interrupting = interrupting || c.device.is_interrupting();
+            if let Some(irq) = c.irq {
+                if c.device.is_interrupting() {
+                    lines |= 1 << irq;
+                }
+            }
         }
 
-        // Set MEIP
+        // Devices without a line interrupt directly. So does the interrupt controller
+        let mut interrupting = false;
+        let mut timer = false;
+        let mut software = false;
+        let mut time = None;
+        for c in &mut self.peripherals {
+            c.device.set_interrupt_lines(lines);
+            if c.irq.is_none() {
+                interrupting = interrupting || c.device.is_interrupting();
+            }
+            timer = timer || c.device.timer_interrupt();
+            software = software || c.device.software_interrupt();
+            time = time.or(c.device.time());
+        }
+
+        // Set MEIP, MTIP, MSIP
         self.cpu.ip = set_patch(self.cpu.ip, MEIP, 1, if interrupting { 1 } else { 0 });
+        self.cpu.ip = set_patch(self.cpu.ip, MTIP, 1, if timer { 1 } else { 0 });
+        self.cpu.ip = set_patch(self.cpu.ip, MSIP, 1, if software { 1 } else { 0 });
+
+        // Without a timer, time runs with the clock
+        self.cpu.time = time.unwrap_or(self.cpu.cycle);
 
         // Tick Cpu
         self.cpu.tick(&mut self.peripherals);
@@ -100,26 +292,52 @@ impl Machine {
     }
 }
 
-impl<'a> MasterBusEnd for HashMap<String, PeriConnection> {
+// Devices see the offset from the start of their region
+impl MasterBusEnd for Vec<PeriConnection> {
     fn read_word(&mut self, addr: u32) -> Result<u32, ()> {
-        if let Some((name, peri)) = bus_select_mut(self, addr) {
-            Ok(peri.device.read_word(addr))
+        if let Some(peri) = bus_select_mut(self, addr) {
+            Ok(peri.device.read_word(addr - peri.addr_start))
         } else {
             Err(())
         }
     }
 
     fn write_word(&mut self, addr: u32, value: u32) -> Result<(), ()> {
-        if let Some((name, peri)) = bus_select_mut(self, addr) {
-            Ok(peri.device.write_word(addr, value))
+        match bus_select_mut(self, addr) {
+            Some(peri) if peri.device.is_writable(addr - peri.addr_start) => Ok(peri.device.write_word(addr - peri.addr_start, value)),
+            _ => Err(()),
+        }
+    }
+
+    fn read(&mut self, addr: u32, size: u32) -> Result<u32, ()> {
+        if let Some(peri) = bus_select_mut(self, addr) {
+            Ok(peri.device.read(addr - peri.addr_start, size))
         } else {
             Err(())
         }
     }
 
+    fn write(&mut self, addr: u32, size: u32, value: u32) -> Result<(), ()> {
+        match bus_select_mut(self, addr) {
+            Some(peri) if peri.device.is_writable(addr - peri.addr_start) => Ok(peri.device.write(addr - peri.addr_start, size, value)),
+            _ => Err(()),
+        }
+    }
+
+    fn is_writable(&self, addr: u32) -> bool {
+        match bus_select(self, addr) {
+            Some(i) => self[i].device.is_writable(addr - self[i].addr_start),
+            None => false,
+        }
+    }
+
+    fn peripheral_id(&self, addr: u32) -> Option<u32> {
+        bus_select(self, addr).map(|i| self[i].id)
+    }
+
     fn is_interrupting(&self) -> bool {
         // if any of the peripherals is interrupting, it is interrupting
-        for (_, p) in self {
+        for p in self {
             if p.device.is_interrupting() {
                 return true;
             }
@@ -129,37 +347,65 @@ impl<'a> MasterBusEnd for HashMap<String, PeriConnection> {
     }
 }
 
-fn bus_select<'a, 'b>(peris: &'a HashMap<String, PeriConnection>, addr: u32) -> Option<(&'a str, &'a PeriConnection)> {
-    for (n, p) in peris {
-        if p.addr_start == addr & !((1u32 << p.addr_width) - 1) {
-            return Some((n, p));
-        }
+// Index of the region that holds addr. The last one that starts at or below it, if it reaches
+fn bus_select(peris: &[PeriConnection], addr: u32) -> Option<usize> {
+    let i = match peris.binary_search_by_key(&addr, |p| p.addr_start) {
+        Ok(i) => return Some(i),
+        Err(0) => return None,
+        Err(i) => i - 1,
+    };
+    if (addr as u64) < peris[i].addr_end {
+        Some(i)
+    } else {
+        None
     }
-    None
 }
 
-fn bus_select_mut<'a, 'b>(peris: &'a mut HashMap<String, PeriConnection>, addr: u32) -> Option<(&'a str, &'a mut PeriConnection)> {
-    for (n, p) in peris {
-        if p.addr_start == addr & !((1u32 << p.addr_width) - 1) {
-            return Some((n, p));
-        }
+fn bus_select_mut(peris: &mut [PeriConnection], addr: u32) -> Option<&mut PeriConnection> {
+    match bus_select(peris, addr) {
+        Some(i) => Some(&mut peris[i]),
+        None => None,
     }
-    None
 }
 
-// Peripheral registration
+// Peripheral registration. [addr_start, addr_end)
 struct PeriConnection {
+    id: u32, // Order of attachment, for counting accesses
+    name: String,
     addr_start: u32,
-    addr_width: u8,
+    addr_end: u64,
+    irq: Option<u32>,
     device: Box<Peri>,
 }
 
 pub trait BusEnd {
-    // addr is word aligned
+    // addr is the offset into the device's region, word aligned
 
     fn read_word(&mut self, addr: u32) -> u32;
     fn write_word(&mut self, addr: u32, value: u32);
     fn is_interrupting(&self) -> bool;
+
+    // Byte, halfword or word at addr, aligned to size. The value is in the low bits.
+    // Only the size bytes at addr are enabled: a write must leave the rest of the word alone.
+    // By default these go through the whole word. A device whose reads have side effects
+    // must override write, or a narrow store would trigger them
+    fn read(&mut self, addr: u32, size: u32) -> u32 {
+        sized_read(self.read_word(addr & !0x3), addr, size)
+    }
+
+    fn write(&mut self, addr: u32, size: u32, value: u32) {
+        if size == 4 {
+            self.write_word(addr, value);
+        } else {
+            let word = self.read_word(addr & !0x3);
+            self.write_word(addr & !0x3, sized_write(word, addr, size, value));
+        }
+    }
+
+    // Writes where this is false are store access faults
+    fn is_writable(&self, _addr: u32) -> bool {
+        true
+    }
 }
 
 // Exception can be raised
@@ -169,12 +415,73 @@ trait MasterBusEnd {
     fn read_word(&mut self, addr: u32) -> Result<u32, ()>;
     fn write_word(&mut self, addr: u32, value: u32) -> Result<(), ()>;
     fn is_interrupting(&self) -> bool;
+
+    // Sized transactions, as in BusEnd
+    fn read(&mut self, addr: u32, size: u32) -> Result<u32, ()> {
+        Ok(sized_read(self.read_word(addr & !0x3)?, addr, size))
+    }
+
+    fn write(&mut self, addr: u32, size: u32, value: u32) -> Result<(), ()> {
+        if size == 4 {
+            self.write_word(addr, value)
+        } else {
+            let word = self.read_word(addr & !0x3)?;
+            self.write_word(addr & !0x3, sized_write(word, addr, size, value))
+        }
+    }
+
+    // Checked before the store commits
+    fn is_writable(&self, _addr: u32) -> bool {
+        true
+    }
+
+    // Which peripheral answers at addr, if the bus can tell
+    fn peripheral_id(&self, _addr: u32) -> Option<u32> {
+        None
+    }
 }
 
 pub trait Peri: BusEnd {
     fn tick(&mut self) {
         // nop default
     }
+
+    // Core-local interrupts. Only a timer device drives these
+    fn timer_interrupt(&self) -> bool {
+        false
+    }
+
+    fn software_interrupt(&self) -> bool {
+        false
+    }
+
+    // mtime, for the time CSR
+    fn time(&self) -> Option<u64> {
+        None
+    }
+
+    // Levels of the interrupt lines, bit n for line n. Only an interrupt controller takes these
+    fn set_interrupt_lines(&mut self, _lines: u32) {
+        // nop default
+    }
+}
+
+// The size bytes at addr, out of the word that holds them
+pub fn sized_read(word: u32, addr: u32, size: u32) -> u32 {
+    let shift = (addr & 0x3) * 8;
+    (word >> shift) & size_mask(size)
+}
+
+// The word with the size bytes at addr replaced
+pub fn sized_write(word: u32, addr: u32, size: u32, value: u32) -> u32 {
+    let shift = (addr & 0x3) * 8;
+    let mask = size_mask(size) << shift;
+    (word & !mask) | ((value << shift) & mask)
+}
+
+fn size_mask(size: u32) -> u32 {
+    assert!(size == 1 || size == 2 || size == 4, "size: {}", size);
+    if size == 4 { !0 } else { (1u32 << (size * 8)) - 1 }
 }
 
 fn set_patch(base: u32, offset: u8, length: u8, value: u32) -> u32 {
@@ -27,6 +27,11 @@ impl<W: Write> BusEnd for OutputDevice<W> {
     fn is_interrupting(&self) -> bool {
         false
     }
+
+    // Any write is one byte out, without reading first
+    fn write(&mut self, addr: u32, _size: u32, value: u32) {
+        self.write_word(addr, value);
+    }
 }
 
 impl<W: Write> Peri for OutputDevice<W> {}
@@ -64,6 +69,11 @@ impl<R: Read> BusEnd for InputDevice<R> {
         // ignoring...
     }
 
+    // Writes don't read, so they don't consume input
+    fn write(&mut self, _addr: u32, _size: u32, _value: u32) {
+        // ignoring...
+    }
+
     // interrupt is not used for this synchronuous architecture
     fn is_interrupting(&self) -> bool {
         false
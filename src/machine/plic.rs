@@ -0,0 +1,124 @@
+use super::*;
+
+// Platform-level interrupt controller with a single context, M-mode of hart 0.
+// Register offsets follow the usual SiFive layout
+pub const PRIORITY_OFFSET: u32 = 0x000000; // 4 bytes per source
+pub const PENDING_OFFSET: u32 = 0x001000;
+pub const ENABLE_OFFSET: u32 = 0x002000;
+pub const THRESHOLD_OFFSET: u32 = 0x200000;
+pub const CLAIM_OFFSET: u32 = 0x200004; // Also complete
+
+// Attach with this size
+pub const PLIC_SIZE: u32 = 0x400000;
+
+// Sources are 1 to 31. 0 means no interrupt
+pub const NUM_SOURCES: u32 = 32;
+
+const PRIORITY_MASK: u32 = 0x7;
+
+pub struct Plic {
+    pub priority: [u32; NUM_SOURCES as usize],
+    pub pending: u32,
+    pub enable: u32,
+    pub threshold: u32,
+
+    // Claimed and not completed yet. The gateway holds these sources off
+    in_flight: u32,
+}
+
+impl Plic {
+    pub fn new() -> Plic {
+        Plic {
+            priority: [0; NUM_SOURCES as usize],
+            pending: 0,
+            enable: 0,
+            threshold: 0,
+            in_flight: 0,
+        }
+    }
+
+    // Highest priority pending and enabled source. Ties go to the lower id
+    fn best(&self) -> Option<u32> {
+        let mut best: Option<u32> = None;
+        for id in 1..NUM_SOURCES {
+            if (self.pending & self.enable) >> id & 0x1 == 0 || self.priority[id as usize] == 0 {
+                continue;
+            }
+            match best {
+                Some(b) if self.priority[b as usize] >= self.priority[id as usize] => (),
+                _ => best = Some(id),
+            }
+        }
+        best
+    }
+
+    pub fn claim(&mut self) -> u32 {
+        match self.best() {
+            Some(id) => {
+                self.pending &= !(1 << id);
+                self.in_flight |= 1 << id;
+                id
+            }
+            None => 0,
+        }
+    }
+
+    pub fn complete(&mut self, id: u32) {
+        if id < NUM_SOURCES {
+            self.in_flight &= !(1 << id);
+        }
+    }
+
+    // The register at offset, without claiming. Claim/complete holds nothing
+    fn register(&self, offset: u32) -> u32 {
+        match offset {
+            PENDING_OFFSET => self.pending,
+            ENABLE_OFFSET => self.enable,
+            THRESHOLD_OFFSET => self.threshold,
+            _ if offset < NUM_SOURCES * 4 => self.priority[(offset / 4) as usize],
+            _ => 0,
+        }
+    }
+}
+
+impl BusEnd for Plic {
+    fn read_word(&mut self, offset: u32) -> u32 {
+        match offset {
+            CLAIM_OFFSET => self.claim(),
+            _ => self.register(offset),
+        }
+    }
+
+    fn write_word(&mut self, offset: u32, value: u32) {
+        match offset {
+            PENDING_OFFSET => (), // Read-only
+            ENABLE_OFFSET => self.enable = value & !0x1,
+            THRESHOLD_OFFSET => self.threshold = value & PRIORITY_MASK,
+            CLAIM_OFFSET => self.complete(value),
+            PRIORITY_OFFSET => (), // Source 0 doesn't exist
+            _ if offset < NUM_SOURCES * 4 => self.priority[(offset / 4) as usize] = value & PRIORITY_MASK,
+            _ => (),
+        }
+    }
+
+    // Narrow stores must not claim
+    fn write(&mut self, offset: u32, size: u32, value: u32) {
+        let word = self.register(offset & !0x3);
+        self.write_word(offset & !0x3, sized_write(word, offset, size, value));
+    }
+
+    // Drives MEIP
+    fn is_interrupting(&self) -> bool {
+        match self.best() {
+            Some(id) => self.priority[id as usize] > self.threshold,
+            None => false,
+        }
+    }
+}
+
+impl Peri for Plic {
+    // Level triggered. A source pends again once its claim is completed and the line is still up
+    fn set_interrupt_lines(&mut self, lines: u32) {
+        self.pending |= lines & !self.in_flight & !0x1;
+    }
+}
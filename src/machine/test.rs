@@ -5,6 +5,8 @@ use std;
 use ::asm;
 
 use ::machine::peri::*;
+use ::machine::clint::*;
+use ::machine::plic::*;
 
 #[test]
 fn test_func_stack() {
@@ -262,6 +264,41 @@ fn test_sum() {
     assert_eq!(output[0], 55, "output value");
 }
 
+#[test]
+fn test_compressed() {
+    let body = "\
+(lui t2 output)
+(addi t2 t2 output)
+
+; s0 = iter, s1 = sum, a0 = 11. Compressible
+(li s0 1)
+(li s1 0)
+(li a0 11)
+
+(: LOOP_START)
+(add s1 s1 s0)
+(addi s0 s0 1)
+(bne s0 a0 (&- LOOP_START pc))
+
+(sw t2 s1 0)
+(lui t0 end_pc_target)
+(addi t0 t0 end_pc_target)
+(jalr zero t0 0)
+";
+
+    let plain = String::from(system_header) + body;
+    let compressed = String::from(system_header) + "(.option rvc)\n" + body;
+
+    // 6 of 12 instructions become 16-bit, leaving some 32-bit instructions halfword aligned
+    let plain_len = asm::assemble_mem(&plain).unwrap().len();
+    let compressed_len = asm::assemble_mem(&compressed).unwrap().len();
+    assert_eq!(plain_len, 48);
+    assert_eq!(compressed_len, 36);
+
+    let output = test_for_output(&compressed, true);
+    assert_eq!(&output, &[55u8]);
+}
+
 #[test]
 fn test_exception() {
     let code = String::from(system_header) + "\
@@ -296,6 +333,818 @@ fn test_exception() {
     assert_eq!(&output, &[1u8, 2u8]);
 }
 
+#[test]
+fn test_ecall() {
+    let code = String::from(system_header) + "\
+; write 0x01, ecall, write 0x02 from the handler, terminate
+(j RESET_HANDLER)
+(nop)
+(nop)
+(nop)
+
+(: TRAP_VECTOR)
+(li t1 0x02)
+(sw t3 t1 0)
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+
+(: RESET_HANDLER)
+(lui t3 output)
+(addi t3 t3 output)
+
+(li t1 0x01)
+(sw t3 t1 0)
+(ecall)
+(li t1 0x03)
+(sw t3 t1 0)
+";
+    let output = test_for_output(&code, false);
+
+    assert_eq!(&output, &[1u8, 2u8]);
+}
+
+#[test]
+fn test_misaligned_access() {
+    let code = String::from(system_header) + "\
+; A word across the end of memory and a second memory, read back in parts
+(.equ boundary 0x00010000)
+(.equ value 0x11223344)
+
+(lui t3 output)
+(addi t3 t3 output)
+(lui t0 boundary)
+(addi t0 t0 boundary)
+(lui t1 value)
+(addi t1 t1 value)
+
+(sw t0 t1 -2)
+(lw t2 t0 -2)
+(sw t3 t2 0)
+(lhu t2 t0 0)
+(srli t2 t2 8)
+(sw t3 t2 0)
+(lh t2 t0 -1)
+(sw t3 t2 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let config = Config {
+        misaligned_access: true,
+        ..Default::default()
+    };
+    let output = test_for_output_config(&code, true, config, |m| m.attach("memory2", Memory::new(0x10000, None), 0x10000, 0x10000).unwrap());
+
+    assert_eq!(&output, &[0x44u8, 0x11u8, 0x33u8]);
+}
+
+#[test]
+fn test_misaligned_store_fault() {
+    let code = String::from(system_header) + "\
+; A word across the end of memory and a ROM. Neither half is written
+(.equ boundary 0x00010000)
+(.equ value 0x11223344)
+(j RESET_HANDLER)
+(nop)
+(nop)
+(nop)
+
+(: TRAP_VECTOR)
+(lhu t2 t0 -2)
+(sw t3 t2 0)
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+
+(: RESET_HANDLER)
+(lui t3 output)
+(addi t3 t3 output)
+(lui t0 boundary)
+(addi t0 t0 boundary)
+(lui t1 value)
+(addi t1 t1 value)
+
+(sw t0 t1 -2)
+";
+    let config = Config {
+        misaligned_access: true,
+        ..Default::default()
+    };
+    let output = test_for_output_config(&code, false, config, |m| m.attach("rom", Rom::new(&[0; 4], true), 0x10000, 4).unwrap());
+
+    assert_eq!(&output, &[0u8]);
+}
+
+#[test]
+fn test_rv64() {
+    let code = String::from(system_header) + "\
+; Doublewords in memory, word instructions, and the RV64 16-bit forms of ld and sd
+(.option rvc)
+(.option rv64)
+
+(lui t3 output)
+(addi t3 t3 output)
+
+(li a0 -1)
+(srli a0 a0 32)
+(li a1 0x400)
+(sd a1 a0 8)
+(ld a2 a1 8)
+(srli a3 a2 24)
+(sw t3 a3 0)
+(lw a3 a1 8)
+(srli a3 a3 56)
+(sw t3 a3 0)
+(lwu a3 a1 8)
+(srli a3 a3 28)
+(sw t3 a3 0)
+(addiw a3 a0 2)
+(sw t3 a3 0)
+(csrrs a3 zero misa)
+(srli a3 a3 62)
+(sw t3 a3 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+; Pads to a whole word
+(nop)
+";
+    // Memory loads whole words
+    let code_len = asm::assemble_mem(&code).unwrap().len();
+    assert_eq!(code_len % 4, 0);
+
+    let config = Config {
+        isa: Isa::parse("rv64gc").unwrap(),
+        ..Default::default()
+    };
+    let output = test_for_output_config(&code, true, config, |_| ());
+
+    assert_eq!(&output, &[0xFFu8, 0xFFu8, 0x0Fu8, 0x01u8, 0x02u8]);
+}
+
+#[test]
+fn test_fence_i() {
+    let code = String::from(system_header) + "\
+; Patches an instruction that already ran. Without fence.i the cached one runs again
+(.equ new_inst 0x00200513) ; (addi a0 zero 2)
+
+(lui t3 output)
+(addi t3 t3 output)
+(lui t0 new_inst)
+(addi t0 t0 new_inst)
+(li t1 PATCHED)
+(li s0 0)
+(li t2 2)
+
+(: PATCHED)
+(addi a0 zero 1)
+(sw t3 a0 0)
+(addi s0 s0 1)
+(beq s0 t2 (&- SECOND pc))
+(blt t2 s0 (&- END pc))
+(sw t1 t0 0)
+(fence)
+(j (&- PATCHED pc))
+
+(: SECOND)
+(fence.i)
+(j (&- PATCHED pc))
+
+(: END)
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let output = test_for_output(&code, true);
+    assert_eq!(&output, &[1u8, 2u8, 2u8], "fetch sees stores");
+
+    let config = Config {
+        icache: true,
+        ..Default::default()
+    };
+    let output = test_for_output_config(&code, true, config, |_| ());
+    assert_eq!(&output, &[1u8, 1u8, 2u8], "stale until fence.i");
+}
+
+#[test]
+fn test_isa() {
+    let code = String::from(system_header) + "\
+; misa has the extensions of the ISA string, and S and U
+(lui t3 output)
+(addi t3 t3 output)
+(csrrs t0 zero misa)
+(sw t3 t0 0)
+(srli t0 t0 8)
+(sw t3 t0 0)
+(srli t0 t0 10)
+(sw t3 t0 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let config = Config {
+        isa: Isa::parse("rv32imac_zicsr").unwrap(),
+        ..Default::default()
+    };
+    let output = test_for_output_config(&code, true, config, |_| ());
+
+    assert_eq!(&output, &[0x05u8, 0x11u8, 0x05u8]);
+
+    // The assembler holds to the same ISA
+    let isa = Isa::parse("rv32i").unwrap();
+    for s in &["(mul a0 a0 a0)", "(csrrs a0 zero misa)", "(fence.i)", "(flw ft0 a0 0)", "(ld a0 a0 0)"] {
+        match asm::assemble_mem_with_isa(s, Some(isa)) {
+            Err(asm::AsmError { error: asm::AsmProcessError::NotInIsa(_), .. }) => (),
+            other => panic!("{}: {:?}", s, other),
+        }
+    }
+    assert!(asm::assemble_mem_with_isa("(add a0 a0 a0)", Some(isa)).is_ok());
+    assert_eq!(asm::assemble_mem_with_isa("(.option rvc)\n(addi a0 a0 1)", Some(isa)).unwrap().len(), 4, "no C, no compression");
+    assert!(asm::assemble_mem_with_isa("(.option rv64)", Some(isa)).is_err());
+}
+
+#[test]
+fn test_sized_transactions() {
+    let code = String::from(system_header) + "\
+; Byte and halfword stores to devices don't read them first. The store to input consumes nothing
+(lui t3 output)
+(addi t3 t3 output)
+(lui t4 input)
+(addi t4 t4 input)
+
+(li t0 0x55)
+(sb t4 t0 0)
+(lb t0 t4 0)
+(sb t3 t0 0)
+(li t0 0x102)
+(sh t3 t0 0)
+(lw t0 t4 0)
+(sw t3 t0 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let output = test_for_output_config(&code, true, Default::default(), |m| {
+        m.attach("input", InputDevice::new(&[7u8, 9u8][..]), 0x100004, 4).unwrap();
+    });
+
+    assert_eq!(&output, &[7u8, 2, 9]);
+}
+
+#[test]
+fn test_attach() {
+    let mut m = Machine::new();
+    m.attach("a", Memory::new(0x300, None), 0x1000, 0x300).unwrap();
+    m.attach("b", Memory::new(0x100, None), 0x1300, 0x100).unwrap();
+    m.attach("top", Memory::new(4, None), 0xFFFFFFFC, 4).unwrap();
+
+    assert_eq!(m.attach("c", Memory::new(4, None), 0x1200, 0x10), Err(AttachError::Overlap(String::from("a"))));
+    assert_eq!(m.attach("c", Memory::new(4, None), 0x0F00, 0x101), Err(AttachError::Overlap(String::from("a"))));
+    assert_eq!(m.attach("c", Memory::new(4, None), 0x1380, 1), Err(AttachError::Overlap(String::from("b"))));
+    assert_eq!(m.attach("c", Memory::new(4, None), 0x2000, 0), Err(AttachError::Empty));
+    assert_eq!(m.attach("a", Memory::new(4, None), 0x2000, 4), Err(AttachError::NameTaken));
+
+    // Devices see offsets from their own start
+    m.peripherals.write_word(0x1304, 7).unwrap();
+    assert_eq!(m.peripherals.read_word(0x1304), Ok(7), "b");
+    assert_eq!(m.peripherals.read_word(0x1004), Ok(0), "a");
+    assert_eq!(m.peripherals.read_word(0x12FC), Ok(0), "end of a");
+    assert_eq!(m.peripherals.read_word(0xFFFFFFFC), Ok(0), "top");
+    assert_eq!(m.peripherals.read_word(0x0FFC), Err(()), "below a");
+    assert_eq!(m.peripherals.read_word(0x1400), Err(()), "past b");
+}
+
+#[test]
+fn test_memory() {
+    let mut memory = Memory::new(12, None);
+    memory.load_at(3, &[1, 2, 3, 4, 5]);
+    assert_eq!(&memory.data[..], &[0x01000000, 0x05040302, 0], "load_at");
+
+    let mut sparse = SparseMemory::new();
+    assert_eq!(sparse.read_word(0x7FFFF000), 0, "unwritten");
+    assert_eq!(sparse.num_pages(), 0, "reads allocate nothing");
+    sparse.load_at(0xFFE, &[1, 2, 3, 4]);
+    assert_eq!(sparse.num_pages(), 2, "across pages");
+    assert_eq!(sparse.read_word(0xFFC), 0x02010000, "first page");
+    assert_eq!(sparse.read_word(0x1000), 0x0403, "second page");
+    sparse.write(0x40000001, 1, 0xAB);
+    assert_eq!(sparse.read_word(0x40000000), 0xAB00, "byte");
+    sparse.load_at(0xFFFFFFFE, &[1, 2]);
+    assert_eq!(sparse.read_word(0xFFFFFFFC), 0x02010000, "up to the end of the address space");
+
+    // A large region from the program
+    let code = String::from(system_header) + "\
+(lui t3 output)
+(addi t3 t3 output)
+(lui t0 0x90000000)
+(li t1 0x42)
+(sw t0 t1 0x10)
+(lw t2 t0 0x10)
+(sw t3 t2 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let output = test_for_output_config(&code, true, Default::default(), |m| {
+        m.attach("sparse", SparseMemory::new(), 0x80000000, 0x80000000).unwrap();
+    });
+    assert_eq!(&output, &[0x42u8]);
+}
+
+#[test]
+fn test_boot_rom() {
+    let code = String::from(system_header) + "\
+; Started by the boot ROM. Writes to the ROM are ignored, or fault
+(j RESET_HANDLER)
+(nop)
+(nop)
+(nop)
+
+(: TRAP_VECTOR)
+(csrrs t1 zero mcause)
+(sw t3 t1 0)
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+
+(: RESET_HANDLER)
+(lui t3 output)
+(addi t3 t3 output)
+(li t1 1)
+(sw t3 t1 0)
+
+(lui t0 0x20000)
+(lw t1 t0 0)
+(sw t0 zero 0)
+(lw t2 t0 0)
+(sub t1 t1 t2)
+(sw t3 t1 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let boot = asm::assemble_mem("\
+(lui t3 0x100000)
+(li t1 0xB)
+(sw t3 t1 0)
+(jalr zero zero 0)
+").unwrap();
+    let config = || Config {
+        reset_vector: 0x20000,
+        ..Default::default()
+    };
+
+    let output = test_for_output_config(&code, true, config(), |m| m.attach_boot_rom(Rom::new(&boot, false)).unwrap());
+    assert_eq!(&output, &[0xBu8, 1, 0], "writes ignored");
+
+    let output = test_for_output_config(&code, false, config(), |m| m.attach_boot_rom(Rom::new(&boot, true)).unwrap());
+    assert_eq!(&output, &[0xBu8, 1, STORE_ACCESS_FAULT as u8], "writes fault");
+}
+
+#[test]
+fn test_load_elf() {
+    // At 0x2000, with a word of .bss right after the code
+    let code = asm::assemble_mem("\
+(lui t3 0x100000)
+(lui t0 0x2000)
+(lw t1 t0 32)
+(sw t3 t1 0)
+(li t1 0x5A)
+(sw t3 t1 0)
+(lui t4 0x10000000)
+(jalr zero t4 0)
+").unwrap();
+    let elf = Elf {
+        entry: 0x2000,
+        segments: vec![::elf::Segment { paddr: 0x2000, mem_size: code.len() as u32 + 4, data: code }],
+        symbols: Vec::new(),
+    };
+
+    let huge = Elf {
+        entry: 0x2000,
+        segments: vec![::elf::Segment { paddr: 0x2000, mem_size: 0xFFFF0000, data: Vec::new() }],
+        symbols: Vec::new(),
+    };
+
+    let output = test_for_output_config("(nop)", true, Default::default(), |m| {
+        assert_eq!(m.load_elf(&huge), Err(0x10000), ".bss past the end of memory");
+        m.load(0x2020, &[0xFF; 4]).unwrap();
+        m.load_elf(&elf).unwrap();
+        assert_eq!(m.load(0x200000, &[0]), Err(0x200000), "nothing there");
+    });
+
+    assert_eq!(&output, &[0u8, 0x5A], "bss cleared");
+}
+
+#[test]
+fn test_load_breaks_reservation() {
+    let code = "\
+(lui a0 0x2000)
+(lr.w t1 a0)
+(sc.w t2 a0 t1)
+(lui t3 0x100000)
+(sw t3 t2 0)
+(lui t4 0x10000000)
+(jalr zero t4 0)
+";
+    let output = test_for_output_with(code, true, |m| {
+        m.tick();
+        m.tick();
+        assert_eq!(m.cpu.reservation, Some(0x2000), "lr");
+        m.load(0x2003, &[1]).unwrap();
+        assert_eq!(m.cpu.reservation, None, "written from outside the hart");
+    });
+
+    assert_eq!(&output, &[1u8], "sc fails");
+}
+
+#[test]
+fn test_minstret() {
+    let code = String::from(system_header) + "\
+; count the instructions between two reads of minstret
+(lui t3 output)
+(addi t3 t3 output)
+
+(csrrs t1 zero minstret)
+(nop)
+(nop)
+(csrrs t2 zero minstret)
+(sub t1 t2 t1)
+(sw t3 t1 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let output = test_for_output(&code, true);
+
+    assert_eq!(&output, &[3u8]);
+}
+
+#[test]
+fn test_hpm() {
+    let code = String::from(system_header) + "\
+; Loads from memory and stores to output in a loop, counted by events the host and the
+; program set up
+(lui t3 output)
+(addi t3 t3 output)
+(li t0 1) ; loads
+(csrrw zero t0 mhpmevent5)
+
+(li t1 3)
+(: LOOP)
+(lw t0 zero 0)
+(sw t3 t1 0)
+(addi t1 t1 -1)
+(bne t1 zero (&- LOOP pc))
+
+(csrrs t0 zero mhpmcounter3)
+(sw t3 t0 0)
+(csrrs t0 zero mhpmcounter4)
+(sw t3 t0 0)
+(csrrs t0 zero mhpmcounter5)
+(sw t3 t0 0)
+(csrrs t0 zero mhpmcounter6)
+(sw t3 t0 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let output = test_for_output_config(&code, true, Default::default(), |m| {
+        let output = m.peripheral_event("output").unwrap();
+        m.set_hpm_event(3, output);
+        m.set_hpm_event(4, HPM_EVENT_TAKEN_BRANCHES);
+        m.set_hpm_event(6, HPM_EVENT_STALLS);
+        m.stall(4);
+        assert_eq!(m.hpm_counter(6), 4, "stalls");
+        m.set_hpm_counter(6, u64::max_value());
+        m.stall(2);
+        assert_eq!(m.hpm_counter(6), 1, "stalls wrap");
+        m.set_hpm_counter(6, 4);
+        assert_eq!(m.peripheral_event("nothing"), None, "no peripheral");
+    });
+
+    assert_eq!(&output, &[3u8, 2, 1, 3, 2, 3, 4]);
+}
+
+#[test]
+fn test_timer_interrupt() {
+    let code = String::from(system_header) + "\
+(.equ msip 0x02000000)
+(.equ mtimecmp 0x02004000)
+
+; Timer interrupt, then a software interrupt from the handler. Output the causes
+(j RESET_HANDLER)
+(nop)
+(nop)
+(nop)
+
+(: TRAP_VECTOR)
+(csrrs t1 zero mcause)
+(sw t3 t1 0)
+(andi t1 t1 0xF)
+(li t2 7)
+(bne t1 t2 (&- DONE pc))
+
+; Silence the timer and raise msip
+(li t1 -1)
+(sw t0 t1 4)
+(lui t1 msip)
+(addi t1 t1 msip)
+(li t2 1)
+(sw t1 t2 0)
+(li t1 0x8)
+(csrrs zero t1 mie)
+(csrrsi zero 0x8 mstatus)
+
+(: DONE)
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+
+(: RESET_HANDLER)
+(lui t3 output)
+(addi t3 t3 output)
+(lui t0 mtimecmp)
+(addi t0 t0 mtimecmp)
+
+(li t1 20)
+(sw t0 t1 0)
+(sw t0 zero 4)
+
+(li t1 0x80)
+(csrrs zero t1 mie)
+(csrrsi zero 0x8 mstatus)
+
+(: LOOP)
+(j (&- LOOP pc))
+";
+    let output = test_for_output_with(&code, false, |m| m.attach("clint", Clint::new(1), 0x02000000, CLINT_SIZE).unwrap());
+
+    assert_eq!(&output, &[7u8, 3u8]);
+}
+
+#[test]
+fn test_vectored_trap() {
+    let code = String::from(system_header) + "\
+(.equ msip 0x02000000)
+
+; Reset at 0x40. Vectors from 0x20: exceptions at BASE, interrupts at BASE + 4 * code
+(nop)
+(nop)
+(nop)
+(nop)
+(nop)
+(nop)
+(nop)
+(nop)
+(j (&- EXCEPTION pc))
+(nop)
+(nop)
+(j (&- SOFTWARE pc))
+(nop)
+(nop)
+(nop)
+(j (&- EXCEPTION pc))
+
+(: RESET_HANDLER)
+(lui t3 output)
+(addi t3 t3 output)
+(lui t0 msip)
+(addi t0 t0 msip)
+(li t1 1)
+(sw t0 t1 0)
+(li t1 0x8)
+(csrrs zero t1 mie)
+(csrrsi zero 0x8 mstatus)
+
+(: LOOP)
+(j (&- LOOP pc))
+
+; Output the cause, then ecall to the base
+(: SOFTWARE)
+(csrrs t1 zero mcause)
+(sw t3 t1 0)
+(sw t0 zero 0)
+(ecall)
+
+(: EXCEPTION)
+(csrrs t1 zero mcause)
+(sw t3 t1 0)
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let config = Config {
+        reset_vector: 0x40,
+        mtvec: 0x20 | MTVEC_VECTORED,
+        ..Default::default()
+    };
+    let output = test_for_output_config(&code, false, config, |m| m.attach("clint", Clint::new(1), 0x02000000, CLINT_SIZE).unwrap());
+
+    assert_eq!(&output, &[3u8, ECALL_BASE as u8 + MACHINE]);
+}
+
+#[test]
+fn test_time() {
+    let code = String::from(system_header) + "\
+; mtime runs at half the clock
+(lui t3 output)
+(addi t3 t3 output)
+
+(csrrs t1 zero time)
+(nop)
+(nop)
+(nop)
+(nop)
+(nop)
+(csrrs t2 zero time)
+(sub t1 t2 t1)
+(sw t3 t1 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+";
+    let output = test_for_output_with(&code, true, |m| m.attach("clint", Clint::new(2), 0x02000000, CLINT_SIZE).unwrap());
+
+    assert_eq!(&output, &[3u8]);
+}
+
+#[test]
+fn test_plic() {
+    let mut plic = Plic::new();
+    plic.write_word(PRIORITY_OFFSET + 4 * 2, 1);
+    plic.write_word(PRIORITY_OFFSET + 4 * 3, 2);
+    plic.write_word(PRIORITY_OFFSET + 4 * 4, 0xFF);
+    assert_eq!(plic.read_word(PRIORITY_OFFSET + 4 * 4), 7, "priority WARL");
+    plic.write_word(ENABLE_OFFSET, (1 << 2) | (1 << 3));
+
+    plic.set_interrupt_lines((1 << 2) | (1 << 3) | (1 << 4));
+    assert_eq!(plic.read_word(PENDING_OFFSET), (1 << 2) | (1 << 3) | (1 << 4), "pending");
+    assert!(plic.is_interrupting());
+
+    plic.write_word(THRESHOLD_OFFSET, 2);
+    assert!(!plic.is_interrupting(), "threshold");
+    plic.write_word(THRESHOLD_OFFSET, 0);
+
+    // Highest priority first. 4 is not enabled
+    assert_eq!(plic.read_word(CLAIM_OFFSET), 3, "claim");
+    assert_eq!(plic.read_word(CLAIM_OFFSET), 2, "claim");
+    assert_eq!(plic.read_word(CLAIM_OFFSET), 0, "nothing to claim");
+    assert!(!plic.is_interrupting());
+
+    // Lines still up. Only completed sources pend again
+    plic.set_interrupt_lines((1 << 2) | (1 << 3));
+    assert_eq!(plic.read_word(PENDING_OFFSET), 1 << 4, "in flight");
+    plic.write_word(CLAIM_OFFSET, 3);
+    plic.set_interrupt_lines((1 << 2) | (1 << 3));
+    assert_eq!(plic.read_word(CLAIM_OFFSET), 3, "claim after complete");
+
+    // A byte store completes without claiming first
+    plic.write_word(CLAIM_OFFSET, 3);
+    plic.set_interrupt_lines(1 << 3);
+    plic.write(CLAIM_OFFSET, 1, 2);
+    assert_eq!(plic.read_word(PENDING_OFFSET) & (1 << 3), 1 << 3, "not claimed");
+    assert_eq!(plic.read_word(CLAIM_OFFSET), 3, "claim after byte store");
+
+    // Byte enables on other registers
+    plic.write(PRIORITY_OFFSET + 4 * 3 + 1, 1, 0xFF);
+    assert_eq!(plic.read_word(PRIORITY_OFFSET + 4 * 3), 2, "upper byte of priority");
+}
+
+#[test]
+fn test_external_interrupt() {
+    let code = String::from(system_header) + "\
+(.equ plic_priority 0x0C000000)
+(.equ plic_enable 0x0C002000)
+(.equ plic_claim 0x0C200004)
+(.equ devices 0x00200000)
+
+; Two devices raise their lines. Only line 5 is enabled. Output the claimed id
+(j RESET_HANDLER)
+(nop)
+(nop)
+(nop)
+
+(: TRAP_VECTOR)
+(lui t0 plic_claim)
+(addi t0 t0 plic_claim)
+(lw t1 t0 0)
+(sw t3 t1 0)
+(sw t0 t1 0)
+
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+
+(: RESET_HANDLER)
+(lui t3 output)
+(addi t3 t3 output)
+
+(lui t0 plic_priority)
+(addi t0 t0 plic_priority)
+(li t1 1)
+(sw t0 t1 (*4 5))
+(sw t0 t1 (*4 6))
+
+(lui t0 plic_enable)
+(addi t0 t0 plic_enable)
+(li t1 0x20)
+(sw t0 t1 0)
+
+(li t1 0x800)
+(csrrs zero t1 mie)
+(csrrsi zero 0x8 mstatus)
+
+; Raise line 6, then 5
+(lui t0 devices)
+(addi t0 t0 devices)
+(li t1 1)
+(sw t0 t1 0x10)
+(sw t0 t1 0)
+
+(: LOOP)
+(j (&- LOOP pc))
+";
+    let output = test_for_output_with(&code, false, |m| {
+        m.attach("plic", Plic::new(), 0x0C000000, PLIC_SIZE).unwrap();
+        m.attach_irq("device5", IrqDevice(false), 0x00200000, 4, 5).unwrap();
+        m.attach_irq("device6", IrqDevice(false), 0x00200010, 4, 6).unwrap();
+    });
+
+    assert_eq!(&output, &[5u8]);
+}
+
+// Holds its line up while the last write was nonzero
+struct IrqDevice(bool);
+
+impl BusEnd for IrqDevice {
+    fn read_word(&mut self, _addr: u32) -> u32 {
+        self.0 as u32
+    }
+    fn write_word(&mut self, _addr: u32, value: u32) {
+        self.0 = value != 0;
+    }
+    fn is_interrupting(&self) -> bool {
+        self.0
+    }
+}
+
+impl Peri for IrqDevice {}
+
+#[test]
+fn test_pmp() {
+    let code = String::from(system_header) + "\
+(.equ ram_napot 0x1FFF)
+
+; U-mode may use RAM but not the output device. Output the cause of its store
+(j RESET_HANDLER)
+(nop)
+(nop)
+(nop)
+
+(: TRAP_VECTOR)
+(csrrs t1 zero mcause)
+(sw t3 t1 0)
+(lui t4 end_pc_target)
+(addi t4 t4 end_pc_target)
+(jalr zero t4 0)
+
+(: RESET_HANDLER)
+(lui t3 output)
+(addi t3 t3 output)
+
+; RAM is [0, 0x10000), read, write and execute
+(lui t0 ram_napot)
+(addi t0 t0 ram_napot)
+(csrrw zero t0 pmpaddr0)
+(li t0 0x1F)
+(csrrw zero t0 pmpcfg0)
+
+; mstatus.MPP is U
+(lui t0 USER_CODE)
+(addi t0 t0 USER_CODE)
+(csrrw zero t0 mepc)
+(mret)
+
+(: USER_CODE)
+(li t1 1)
+(sw t3 t1 0)
+";
+    let output = test_for_output(&code, false);
+
+    assert_eq!(&output, &[STORE_ACCESS_FAULT as u8]);
+}
+
 const system_header: &'static str = "\
 ; peripherals
 (.equ output 0x00100000)
@@ -335,7 +1184,16 @@ impl Write for VecPtr {
 // Helper for testing memory and stuff.. for now things that don't involve IO
 // Also things that don't involve exception
 fn test_for_output(code: &str, no_exception: bool) -> Vec<u8> {
-    let bin = match asm::assemble_mem(&code) {
+    test_for_output_with(code, no_exception, |_| ())
+}
+
+// Extra devices can be attached before running
+fn test_for_output_with<F: FnOnce(&mut Machine)>(code: &str, no_exception: bool, attach: F) -> Vec<u8> {
+    test_for_output_config(code, no_exception, Default::default(), attach)
+}
+
+fn test_for_output_config<F: FnOnce(&mut Machine)>(code: &str, no_exception: bool, config: Config, attach: F) -> Vec<u8> {
+    let bin = match asm::assemble_mem_with_isa(&code, Some(config.isa)) {
         Ok(v) => v,
         Err(e) => {
             // Print the line as well as the error
@@ -350,7 +1208,7 @@ fn test_for_output(code: &str, no_exception: bool) -> Vec<u8> {
 
         let mut writer = Vec::<u8>::new();
 
-        ::disasm::disassemble(&mut writer, reader).expect("disassemble");
+        ::disasm::disassemble(&mut writer, reader, config.isa.xlen).expect("disassemble");
 
         let disassembled = std::str::from_utf8(&writer[..]).unwrap();
 
@@ -362,7 +1220,7 @@ fn test_for_output(code: &str, no_exception: bool) -> Vec<u8> {
     // Machine
 
     const MEMORY_START: u32 = 0;
-    const MEMORY_WIDTH: u8 =16;
+    const MEMORY_SIZE: u32 = 0x10000;
     const MEMORY_PROBE: u32 = 0x1000;
 
     const OUTPUT_START: u32 = 0x100000;
@@ -371,17 +1229,18 @@ fn test_for_output(code: &str, no_exception: bool) -> Vec<u8> {
 
     let writer = VecPtr::new();
 
-    let mut memory = Memory::new(Some(MEMORY_PROBE));
+    let mut memory = Memory::new(MEMORY_SIZE, Some(MEMORY_PROBE));
     let mut output_device = OutputDevice::new(writer.clone());
 
     {
 
-        let mut m = Machine::new();
+        let mut m = Machine::with_config(config);
         m.cpu.pc_trail = Some(Vec::new());
 
         memory.load(&bin[..]);
-        m.attach("memory", memory, MEMORY_START, MEMORY_WIDTH);
-        m.attach("output", output_device, OUTPUT_START, 0);
+        m.attach("memory", memory, MEMORY_START, MEMORY_SIZE).unwrap();
+        m.attach("output", output_device, OUTPUT_START, 4).unwrap();
+        attach(&mut m);
         match m.run(TICK_LIMIT, no_exception) {
             Ok(_) => (),
             Err(e) => {
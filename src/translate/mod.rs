@@ -25,6 +25,8 @@ pub enum TranslateError<'a> {
     UnknownInst,
     UnknownSymbol(&'a Expr),
     UnknownCsrName(&'a Expr),
+    UnknownRoundingMode(&'a Expr),
+    UnknownFenceSet(&'a Expr),
     CalcError(CalcError<'a>),
     NotImplemented(&'a Expr),
 }
@@ -62,23 +64,30 @@ fn read_inst<'a>(exprs: &'a [Expr], symtab: &Symtab, pc: u32) -> Result<(&'stati
         return Err(TranslateError::Format);
     }
 
+    let expr_rest = &exprs[1..];
+
     // Determine inst
     let inst = match &exprs[0] {
-        &Expr::Identifier { value: ref v, .. } => inst::inst(v).ok_or(TranslateError::UnknownInst)?,
+        &Expr::Identifier { value: ref v, .. } => {
+            if let Some(cinst) = inst::compressed::compressed_inst(v) {
+                if cinst.inst.args.len() != expr_rest.len() {
+                    return Err(TranslateError::InstArity);
+                }
+                &cinst.inst
+            } else if inst::is_inst(v) {
+                inst::inst_with_arity(v, expr_rest.len()).ok_or(TranslateError::InstArity)?
+            } else {
+                return Err(TranslateError::UnknownInst);
+            }
+        }
         _ => return Err(TranslateError::Format),
     };
 
-    let expr_rest = &exprs[1..];
-
-    if inst.args.len() != expr_rest.len() {
-        return Err(TranslateError::InstArity);
-    }
-
     // Resolve symbols
     let mut args = Vec::<u32>::new();
 
     for (e, &a) in expr_rest.iter().zip(inst.args) {
-        // For some reason, only identifier passed to csr argument is special
+        // For some reason, only identifier passed to csr, rounding mode and fence arguments is special
         match e {
             &Expr::Identifier { value: ref s, .. } => {
                 match a.arg_type {
@@ -88,6 +97,18 @@ fn read_inst<'a>(exprs: &'a [Expr], symtab: &Symtab, pc: u32) -> Result<(&'stati
                             None => return Err(TranslateError::UnknownCsrName(e)),
                         }
                     }
+                    ArgType::RoundingMode => {
+                        match inst::rounding_mode_value(s) {
+                            Some(v) => args.push(v),
+                            None => return Err(TranslateError::UnknownRoundingMode(e)),
+                        }
+                    }
+                    ArgType::FenceSet => {
+                        match inst::fence_set_value(s) {
+                            Some(v) => args.push(v),
+                            None => return Err(TranslateError::UnknownFenceSet(e)),
+                        }
+                    }
                     _ => evaluate(e, symtab, &mut args)?,
                 }
             }
@@ -39,6 +39,7 @@ impl SymtabNode {
 // Produce symtab populated with registers
 
 use ::arch::inst::register::abi_name;
+use ::arch::inst::register::float_abi_name;
 
 static placeholder_symbol: Symbol = Symbol {
     ext: false,
@@ -101,6 +102,18 @@ impl Symtab {
             symtab.insert(name, sym).unwrap();
         }
 
+        for i in 0u8..32u8 {
+            let name = float_abi_name(i);
+
+            let sym = Symbol {
+                ext: false,
+                mutable: false,
+                value: Value::FloatRegister(i),
+            };
+
+            symtab.insert(name, sym).unwrap();
+        }
+
         symtab
     }
 
@@ -227,6 +240,7 @@ pub enum Value {
     General(i64),
     Location(u32),
     Register(u8),
+    FloatRegister(u8),
     Csr(u32),
 
     // Internal use
@@ -241,6 +255,7 @@ impl Value {
             &Value::General(v) => v as u32, // truncate...
             &Value::Location(v) => v,
             &Value::Register(v) => v as u32,
+            &Value::FloatRegister(v) => v as u32,
             &Value::Csr(v) => v,
             _ => panic!("{:?}", self),
         }
@@ -252,6 +267,7 @@ impl Value {
             &Value::General(v) => v,
             &Value::Location(v) => v as i64, // No sign extension
             &Value::Register(v) => v as i64, // Shouldn't this be an error?
+            &Value::FloatRegister(v) => v as i64,
             &Value::Csr(v) => v as i64, // ...
             _ => panic!("{:?}", self),
         }
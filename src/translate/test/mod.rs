@@ -13,6 +13,19 @@ fn test_insts() {
     let cases: &[(&str, u32)] = &[
         ("(nop)", 0x00000013),
         ("(lw t1 t3 0)", 0x000E2303),
+        ("(mul t0 t1 t2)", 0x027302B3),
+        ("(remu a0 a1 a2)", 0x02C5F533),
+        ("(lr.w t0 a0)", 0x100522AF),
+        ("(amoadd.w.aqrl t0 a0 a1)", 0x06B522AF),
+        ("(fadd.s ft0 ft1 ft2)", 0x0020F053),
+        ("(fmadd.d fa0 fa1 fa2 fa3 rtz)", 0x6AC59543),
+        ("(flw ft0 a0 4)", 0x00452007),
+        ("(c.addi a0 1)", 0x0505),
+        ("(c.lw a0 a1 4)", 0x41C8),
+        ("(c.jr ra)", 0x8082),
+        ("(c.swsp ra 12)", 0xC606),
+        ("(sfence.vma a0 a1)", 0x12B50073),
+        ("(sfence.vma zero zero)", 0x12000073),
 //        ("(sw t1 t2 0)", 0x0063A023), // TODO check why fails
 //        ("(hret)", 0x20200073),
     ];